@@ -0,0 +1,137 @@
+//! Every admin action on the economy, kept in `bank_audit`.
+
+use crate::{data::DB, utils::db_id};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use poise::serenity_prelude::UserId;
+use serde::Deserialize;
+use surrealdb::types::SurrealValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, SurrealValue)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum AuditAction {
+    GiveCharity,
+    Freeze,
+    Unfreeze,
+    Reverse,
+    SetBalance,
+    Export,
+    Earnings,
+}
+
+/// One admin action, as shown by `/bank_admin audit_log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub admin: UserId,
+    pub action: AuditAction,
+    pub target: Option<UserId>,
+    pub details: String,
+    pub acted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct AuditRow {
+    admin: i64,
+    action: AuditAction,
+    target: Option<i64>,
+    details: String,
+    acted_at: DateTime<Utc>,
+}
+
+/// That `admin` did `action`, to `target` if it was aimed at someone.
+///
+/// Actions that change something write this with [`RECORD_AUDIT`] in the same transaction.
+#[derive(Debug, Clone, SurrealValue)]
+pub struct NewAuditEntry {
+    admin: i64,
+    action: AuditAction,
+    target: Option<i64>,
+    details: String,
+}
+
+impl NewAuditEntry {
+    pub fn new(
+        admin: UserId,
+        action: AuditAction,
+        target: Option<UserId>,
+        details: impl Into<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            admin: db_id(admin)?,
+            action,
+            target: target.map(db_id).transpose()?,
+            details: details.into(),
+        })
+    }
+}
+
+/// Records `$audit`, a [`NewAuditEntry`], in `bank_audit`.
+pub(crate) const RECORD_AUDIT: &str = "CREATE bank_audit CONTENT $audit";
+
+pub struct BankAudit;
+
+impl BankAudit {
+    /// Records an action that didn't change anything else, like an export.
+    pub async fn record(audit: NewAuditEntry) -> Result<()> {
+        DB.query(RECORD_AUDIT)
+            .bind(("audit", audit))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// The latest `limit` actions, newest first.
+    pub async fn recent(limit: u64) -> Result<Vec<AuditEntry>> {
+        let rows: Vec<AuditRow> = DB
+            .query(
+                "SELECT id, admin, action, target, details, acted_at FROM bank_audit \
+                 ORDER BY acted_at DESC, id DESC LIMIT $limit",
+            )
+            .bind(("limit", limit))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AuditEntry {
+                admin: UserId::new(row.admin as u64),
+                action: row.action,
+                target: row.target.map(|target| UserId::new(target as u64)),
+                details: row.details,
+                acted_at: row.acted_at,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) async fn assert_admin_actions_are_audited() {
+        let (admin, target) = (UserId::new(99_461), UserId::new(99_462));
+
+        for audit in [
+            NewAuditEntry::new(admin, AuditAction::Freeze, Some(target), "Alt account"),
+            NewAuditEntry::new(admin, AuditAction::Export, None, "csv"),
+        ] {
+            BankAudit::record(audit.unwrap()).await.unwrap();
+        }
+
+        let recent = BankAudit::recent(2).await.unwrap();
+        assert_eq!(
+            recent
+                .iter()
+                .map(|entry| (entry.admin, entry.action, entry.target))
+                .collect::<Vec<_>>(),
+            [
+                (admin, AuditAction::Export, None),
+                (admin, AuditAction::Freeze, Some(target)),
+            ]
+        );
+        assert_eq!(recent[1].details, "Alt account");
+    }
+}
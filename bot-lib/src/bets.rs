@@ -0,0 +1,711 @@
+//! Betting pools from `/bet`, where users wager on how a question turns out.
+//!
+//! Stakes are taken in the same transaction that records the wager. Once the creator or a
+//! moderator picks the winning option, the whole pool is split between the winners by how much
+//! they staked. Each wager is marked as settled in the same transaction that pays it, so settling
+//! again after a restart pays exactly the wagers that weren't paid yet.
+
+use crate::{
+    data::DB,
+    economy::{Bank, RECORD_CHANGE},
+    utils::{db_id, db_record_id},
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, eyre};
+use itertools::Itertools;
+use poise::serenity_prelude::UserId;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// As many as `/bet create` has parameters for.
+pub const MAX_OPTIONS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, SurrealValue)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum PoolStatus {
+    /// Still waiting on an outcome, even once it stops taking wagers.
+    Open,
+    Resolved,
+    Cancelled,
+}
+
+/// A question to bet on, with how much is staked on each option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BetPool {
+    /// What `/bet` commands take to pick this pool.
+    pub id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    pub creator: UserId,
+    pub closes_at: DateTime<Utc>,
+    pub status: PoolStatus,
+    /// The index of the option that won, once resolved.
+    pub outcome: Option<usize>,
+    /// How much is staked on each option, in the same order.
+    pub stakes: Vec<i64>,
+}
+
+impl BetPool {
+    pub fn takes_wagers(&self, now: DateTime<Utc>) -> bool {
+        self.status == PoolStatus::Open && self.closes_at > now
+    }
+
+    pub fn total(&self) -> i64 {
+        self.stakes.iter().sum()
+    }
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct PoolRow {
+    id: String,
+    question: String,
+    options: Vec<String>,
+    creator: i64,
+    closes_at: DateTime<Utc>,
+    status: PoolStatus,
+    outcome: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct OptionStake {
+    option: i64,
+    stake: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, SurrealValue)]
+struct WagerRow {
+    user_id: i64,
+    option: i64,
+    stake: i64,
+    settled: bool,
+}
+
+/// What [`Bets::add_wager`] found.
+#[derive(Debug, Deserialize, SurrealValue)]
+struct AddedWager {
+    /// Whether the pool was still open and the wager on the same option.
+    placed: bool,
+    staked: Option<i64>,
+    balance: Option<i64>,
+}
+
+/// How a [`Bets::place`] went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// `stake` is everything they have on the pool now, including earlier wagers.
+    Placed {
+        stake: i64,
+        balance: i64,
+    },
+    NotFound,
+    Closed,
+    /// Whoever resolves a pool can't bet on it.
+    OwnPool,
+    /// They already backed another option.
+    OtherOption {
+        option: usize,
+    },
+    TooPoor {
+        balance: i64,
+    },
+    Frozen,
+}
+
+/// What one wager got back when its pool ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Payout {
+    pub user_id: UserId,
+    pub stake: i64,
+    pub payout: i64,
+}
+
+/// How a [`Bets::resolve`] or [`Bets::cancel`] went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ending {
+    /// `refunded` if the pool was cancelled or nobody backed the winning option.
+    Ended {
+        pool: BetPool,
+        payouts: Vec<Payout>,
+        refunded: bool,
+    },
+    NotFound,
+    AlreadyEnded(PoolStatus),
+}
+
+fn pool_record(pool_id: &str) -> RecordId {
+    RecordId::new("bet_pool", pool_id.to_owned())
+}
+
+/// What each wager gets back when `outcome` wins: the whole pool, split between the winning
+/// wagers by stake.
+///
+/// Coins left over from rounding down go to the biggest winning stakes. If the pool was
+/// cancelled or nobody backed `outcome`, everyone gets their stake back.
+fn payouts(wagers: &[WagerRow], outcome: Option<usize>) -> Vec<i64> {
+    let won = |wager: &WagerRow| outcome.is_some_and(|outcome| wager.option == outcome as i64);
+
+    let backed: i64 = wagers
+        .iter()
+        .filter(|&wager| won(wager))
+        .map(|wager| wager.stake)
+        .sum();
+    if backed == 0 {
+        return wagers.iter().map(|wager| wager.stake).collect();
+    }
+
+    let total: i64 = wagers.iter().map(|wager| wager.stake).sum();
+    let mut payouts = wagers
+        .iter()
+        .map(|wager| {
+            if won(wager) {
+                (i128::from(wager.stake) * i128::from(total) / i128::from(backed)) as i64
+            } else {
+                0
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let leftover = total - payouts.iter().sum::<i64>();
+    let biggest_winners = (0..wagers.len())
+        .filter(|&index| won(&wagers[index]))
+        .sorted_by_key(|&index| (Reverse(wagers[index].stake), wagers[index].user_id))
+        .take(leftover as usize)
+        .collect::<Vec<_>>();
+    for index in biggest_winners {
+        payouts[index] += 1;
+    }
+
+    payouts
+}
+
+pub struct Bets;
+
+impl Bets {
+    /// Opens a pool, returning its ID.
+    pub async fn create(
+        creator: UserId,
+        question: String,
+        options: Vec<String>,
+        closes_at: DateTime<Utc>,
+    ) -> Result<String> {
+        if !(2..=MAX_OPTIONS).contains(&options.len()) {
+            return Err(eyre!("a pool can't have {} options", options.len()));
+        }
+
+        let id: Option<String> = DB
+            .query(
+                "CREATE ONLY bet_pool SET question = $question, options = $options, \
+                 creator = $creator, closes_at = $closes_at \
+                 RETURN VALUE record::id(id)",
+            )
+            .bind(("question", question))
+            .bind(("options", options))
+            .bind(("creator", db_id(creator)?))
+            .bind(("closes_at", closes_at))
+            .await?
+            .check()?
+            .take(0)?;
+
+        id.ok_or_else(|| eyre!("bet pool CREATE returned no record"))
+    }
+
+    pub async fn get(pool_id: &str) -> Result<Option<BetPool>> {
+        let row: Option<PoolRow> = DB
+            .query(
+                "SELECT record::id(id) AS id, question, options, creator, closes_at, status, \
+                 outcome FROM ONLY $pool",
+            )
+            .bind(("pool", pool_record(pool_id)))
+            .await?
+            .check()?
+            .take(0)?;
+
+        match row {
+            Some(row) => Ok(Some(Self::with_stakes(row).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Every pool still waiting on an outcome, closing soonest first.
+    pub async fn open() -> Result<Vec<BetPool>> {
+        let rows: Vec<PoolRow> = DB
+            .query(
+                "SELECT record::id(id) AS id, question, options, creator, closes_at, status, \
+                 outcome FROM bet_pool WHERE status = 'open' ORDER BY closes_at",
+            )
+            .await?
+            .check()?
+            .take(0)?;
+
+        let mut pools = Vec::with_capacity(rows.len());
+        for row in rows {
+            pools.push(Self::with_stakes(row).await?);
+        }
+
+        Ok(pools)
+    }
+
+    async fn with_stakes(row: PoolRow) -> Result<BetPool> {
+        let stakes: Vec<OptionStake> = DB
+            .query(
+                "SELECT option, math::sum(stake) AS stake FROM bet_wager \
+                 WHERE pool = $pool GROUP BY option",
+            )
+            .bind(("pool", pool_record(&row.id)))
+            .await?
+            .check()?
+            .take(0)?;
+
+        let mut pool = BetPool {
+            stakes: vec![0; row.options.len()],
+            id: row.id,
+            question: row.question,
+            options: row.options,
+            creator: UserId::new(row.creator as u64),
+            closes_at: row.closes_at,
+            status: row.status,
+            outcome: row.outcome.map(|outcome| outcome as usize),
+        };
+        for OptionStake { option, stake } in stakes {
+            if let Some(total) = pool.stakes.get_mut(option as usize) {
+                *total = stake;
+            }
+        }
+
+        Ok(pool)
+    }
+
+    /// Everything staked on the pool, in the order it was first placed.
+    async fn wagers(pool_id: &str) -> Result<Vec<WagerRow>> {
+        let wagers: Vec<WagerRow> = DB
+            .query(
+                "SELECT user_id, option, stake, settled, placed_at FROM bet_wager \
+                 WHERE pool = $pool ORDER BY placed_at, user_id",
+            )
+            .bind(("pool", pool_record(pool_id)))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(wagers)
+    }
+
+    /// Which option `bettor` backed on the pool, if any.
+    async fn backed(pool_id: &str, bettor: UserId) -> Result<Option<usize>> {
+        let option: Option<i64> = DB
+            .query("RETURN type::record('bet_wager', [$pool, $user_id]).option")
+            .bind(("pool", pool_record(pool_id)))
+            .bind(("user_id", db_id(bettor)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(option.map(|option| option as usize))
+    }
+
+    /// Stakes `stake` on `option`, adding to anything `bettor` already staked on it.
+    pub async fn place(
+        pool_id: &str,
+        bettor: UserId,
+        option: usize,
+        stake: i64,
+    ) -> Result<Placement> {
+        if stake <= 0 {
+            return Err(eyre!("wagers can't have a stake of {stake}"));
+        }
+
+        let Some(pool) = Self::get(pool_id).await? else {
+            return Ok(Placement::NotFound);
+        };
+        let Some(option_name) = pool.options.get(option) else {
+            return Err(eyre!("pool {pool_id} has no option {option}"));
+        };
+
+        if !pool.takes_wagers(Utc::now()) {
+            return Ok(Placement::Closed);
+        }
+
+        if pool.creator == bettor {
+            return Ok(Placement::OwnPool);
+        }
+
+        if let Some(backed) = Self::backed(pool_id, bettor).await?
+            && backed != option
+        {
+            return Ok(Placement::OtherOption { option: backed });
+        }
+
+        let reason = format!("Bet on \"{option_name}\" for \"{}\"", pool.question);
+        let added = Self::add_wager(pool_id, bettor, option, stake, reason).await?;
+
+        if !added.placed {
+            // The pool ended, or they backed another option, since it was read.
+            return match Self::backed(pool_id, bettor).await? {
+                Some(backed) if backed != option => Ok(Placement::OtherOption { option: backed }),
+                _ => Ok(Placement::Closed),
+            };
+        }
+        if let (Some(stake), Some(balance)) = (added.staked, added.balance) {
+            return Ok(Placement::Placed { stake, balance });
+        }
+        if Bank::freeze(bettor).await?.is_some() {
+            return Ok(Placement::Frozen);
+        }
+
+        Ok(Placement::TooPoor {
+            balance: Bank::get(bettor).await?.balance,
+        })
+    }
+
+    /// Takes `stake` from `bettor` and adds it to the wager, in one transaction, if the pool
+    /// still takes it.
+    async fn add_wager(
+        pool_id: &str,
+        bettor: UserId,
+        option: usize,
+        stake: i64,
+        reason: String,
+    ) -> Result<AddedWager> {
+        let added: Option<AddedWager> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $wager = type::record('bet_wager', [$pool, $user_id]); \
+                 LET $placed = $pool.status = 'open' AND $pool.closes_at > time::now() \
+                     AND ($wager.option ?? $option) = $option; \
+                 LET $after = IF $placed {{ \
+                     (UPDATE $account SET balance += $amount \
+                         WHERE balance >= -$amount AND !$freeze.exists() RETURN AFTER)[0] \
+                 }}; \
+                 LET $staked = IF $after {{ \
+                     {RECORD_CHANGE}; \
+                     (UPSERT ONLY $wager SET pool = $pool, user_id = $user_id, option = $option, \
+                         stake = (stake ?? 0) + $stake RETURN VALUE stake) \
+                 }}; \
+                 RETURN {{ placed: $placed, staked: $staked, balance: $after.balance }}; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("pool", pool_record(pool_id)))
+            .bind(("user_id", db_id(bettor)?))
+            .bind(("option", option as i64))
+            .bind(("stake", stake))
+            .bind(("account", db_record_id("bank_account", bettor)?))
+            .bind(("freeze", db_record_id("bank_freeze", bettor)?))
+            .bind(("amount", -stake))
+            .bind(("reason", reason))
+            .bind(("gambling_net", None::<i64>))
+            .await?
+            .check()?
+            // BEGIN and the four LETs come first.
+            .take(5)?;
+
+        added.ok_or_else(|| eyre!("bet wager transaction returned nothing"))
+    }
+
+    /// Ends the pool with `outcome` as the winning option and pays everyone out.
+    pub async fn resolve(pool_id: &str, outcome: usize, resolved_by: UserId) -> Result<Ending> {
+        let Some(pool) = Self::get(pool_id).await? else {
+            return Ok(Ending::NotFound);
+        };
+        if outcome >= pool.options.len() {
+            return Err(eyre!("pool {pool_id} has no option {outcome}"));
+        }
+
+        Self::end(pool_id, PoolStatus::Resolved, Some(outcome), resolved_by).await
+    }
+
+    /// Ends the pool without an outcome and refunds everyone.
+    pub async fn cancel(pool_id: &str, cancelled_by: UserId) -> Result<Ending> {
+        Self::end(pool_id, PoolStatus::Cancelled, None, cancelled_by).await
+    }
+
+    async fn end(
+        pool_id: &str,
+        status: PoolStatus,
+        outcome: Option<usize>,
+        ended_by: UserId,
+    ) -> Result<Ending> {
+        let ended: Vec<String> = DB
+            .query(
+                "UPDATE $pool SET status = $status, outcome = $outcome, ended_by = $ended_by, \
+                 ended_at = time::now() WHERE status = 'open' RETURN VALUE record::id(id)",
+            )
+            .bind(("pool", pool_record(pool_id)))
+            .bind(("status", status))
+            .bind(("outcome", outcome.map(|outcome| outcome as i64)))
+            .bind(("ended_by", db_id(ended_by)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        let Some(pool) = Self::get(pool_id).await? else {
+            return Ok(Ending::NotFound);
+        };
+        if ended.is_empty() {
+            return Ok(Ending::AlreadyEnded(pool.status));
+        }
+
+        Self::settle(pool).await
+    }
+
+    /// Pays out every wager on an ended pool that hasn't been paid yet.
+    async fn settle(pool: BetPool) -> Result<Ending> {
+        let wagers = Self::wagers(&pool.id).await?;
+        let payouts = payouts(&wagers, pool.outcome);
+        let refunded = pool
+            .outcome
+            .is_none_or(|outcome| pool.stakes.get(outcome).is_none_or(|&stake| stake == 0));
+
+        for (wager, &payout) in wagers.iter().zip(&payouts) {
+            if wager.settled {
+                continue;
+            }
+
+            let (reason, gambling_net) = if refunded {
+                (format!("Refund: bet on \"{}\"", pool.question), None)
+            } else if payout > 0 {
+                (
+                    format!("Won the bet on \"{}\"", pool.question),
+                    Some(payout - wager.stake),
+                )
+            } else {
+                (
+                    format!("Lost the bet on \"{}\"", pool.question),
+                    Some(payout - wager.stake),
+                )
+            };
+            Self::pay(&pool.id, wager.user_id, payout, reason, gambling_net).await?;
+        }
+
+        Ok(Ending::Ended {
+            payouts: wagers
+                .iter()
+                .zip(payouts)
+                .map(|(wager, payout)| Payout {
+                    user_id: UserId::new(wager.user_id as u64),
+                    stake: wager.stake,
+                    payout,
+                })
+                .collect(),
+            pool,
+            refunded,
+        })
+    }
+
+    /// Marks a wager as settled and pays `payout` for it in one transaction, unless it was
+    /// already settled. Returns whether it was paid.
+    async fn pay(
+        pool_id: &str,
+        bettor: i64,
+        payout: i64,
+        reason: String,
+        gambling_net: Option<i64>,
+    ) -> Result<bool> {
+        let balance: Option<i64> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $claimed = (UPDATE type::record('bet_wager', [$pool, $user_id]) \
+                     SET settled = true WHERE settled = false RETURN VALUE settled); \
+                 LET $balance = IF $claimed {{ \
+                     {RECORD_CHANGE}; \
+                     (UPSERT ONLY $account SET balance += $amount RETURN VALUE balance) \
+                 }}; \
+                 RETURN $balance; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("pool", pool_record(pool_id)))
+            .bind(("user_id", bettor))
+            .bind(("account", RecordId::new("bank_account", bettor)))
+            .bind(("amount", payout))
+            .bind(("reason", reason))
+            .bind(("gambling_net", gambling_net))
+            .await?
+            .check()?
+            // BEGIN and both LETs come first.
+            .take(3)?;
+
+        Ok(balance.is_some())
+    }
+}
+
+/// Pays out pools that ended before the last restart could finish paying them.
+pub async fn setup_bets() -> Result<()> {
+    let pool_ids: Vec<String> = DB
+        .query(
+            "SELECT VALUE record::id(pool) FROM bet_wager \
+             WHERE settled = false AND pool.status != 'open'",
+        )
+        .await?
+        .check()?
+        .take(0)?;
+
+    for pool_id in pool_ids.into_iter().unique() {
+        if let Some(pool) = Bets::get(&pool_id).await? {
+            Bets::settle(pool).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn wager(user_id: i64, option: i64, stake: i64) -> WagerRow {
+        WagerRow {
+            user_id,
+            option,
+            stake,
+            settled: false,
+        }
+    }
+
+    #[test]
+    fn winners_split_the_pool_by_stake() {
+        let wagers = [wager(1, 0, 30), wager(2, 1, 50), wager(3, 0, 20)];
+
+        assert_eq!(payouts(&wagers, Some(0)), [60, 0, 40]);
+        assert_eq!(payouts(&wagers, Some(1)), [0, 100, 0]);
+    }
+
+    #[test]
+    fn rounding_leftovers_go_to_the_biggest_winners() {
+        let wagers = [
+            wager(1, 0, 1),
+            wager(2, 0, 2),
+            wager(3, 1, 4),
+            wager(4, 0, 2),
+        ];
+
+        // 9 coins over 5 staked gives 1.8, 3.6 and 3.6, which round down to 7.
+        assert_eq!(payouts(&wagers, Some(0)), [1, 4, 0, 4]);
+        assert_eq!(payouts(&wagers, Some(0)).iter().sum::<i64>(), 9);
+    }
+
+    #[test]
+    fn stakes_come_back_without_winners() {
+        let wagers = [wager(1, 0, 30), wager(2, 1, 50)];
+
+        assert_eq!(payouts(&wagers, Some(2)), [30, 50]);
+        assert_eq!(payouts(&wagers, None), [30, 50]);
+    }
+
+    pub(crate) async fn assert_pools_pay_out_parimutuel() {
+        let (creator, alice, bob, carol) = (
+            UserId::new(99_951),
+            UserId::new(99_952),
+            UserId::new(99_953),
+            UserId::new(99_954),
+        );
+        for bettor in [alice, bob, carol] {
+            Bank::change(bettor, 100, String::from("Test funds"))
+                .await
+                .unwrap();
+        }
+
+        let options = vec![String::from("Yes"), String::from("No")];
+        let pool_id = Bets::create(
+            creator,
+            String::from("Will it rain?"),
+            options.clone(),
+            Utc::now() + TimeDelta::hours(1),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            Bets::place(&pool_id, alice, 0, 30).await.unwrap(),
+            Placement::Placed {
+                stake: 30,
+                balance: 70
+            }
+        );
+        assert_eq!(
+            Bets::place(&pool_id, alice, 0, 10).await.unwrap(),
+            Placement::Placed {
+                stake: 40,
+                balance: 60
+            }
+        );
+        assert_eq!(
+            Bets::place(&pool_id, alice, 1, 10).await.unwrap(),
+            Placement::OtherOption { option: 0 }
+        );
+        assert_eq!(
+            Bets::place(&pool_id, bob, 1, 500).await.unwrap(),
+            Placement::TooPoor { balance: 100 }
+        );
+        assert_eq!(
+            Bets::backed(&pool_id, bob).await.unwrap(),
+            None,
+            "a wager that couldn't be paid for isn't recorded"
+        );
+        assert_eq!(
+            Bets::place(&pool_id, creator, 1, 1).await.unwrap(),
+            Placement::OwnPool
+        );
+        Bets::place(&pool_id, bob, 1, 50).await.unwrap();
+        Bets::place(&pool_id, carol, 0, 20).await.unwrap();
+
+        let pool = Bets::get(&pool_id).await.unwrap().unwrap();
+        assert_eq!(pool.stakes, [60, 50]);
+        assert_eq!(Bets::open().await.unwrap(), [pool]);
+
+        let Ending::Ended {
+            payouts, refunded, ..
+        } = Bets::resolve(&pool_id, 0, creator).await.unwrap()
+        else {
+            panic!("the pool should have ended");
+        };
+        assert!(!refunded);
+        assert_eq!(
+            payouts
+                .iter()
+                .map(|payout| (payout.user_id, payout.payout))
+                .collect::<Vec<_>>(),
+            [(alice, 74), (bob, 0), (carol, 36)]
+        );
+        assert_eq!(Bank::get(alice).await.unwrap().balance, 134);
+        assert_eq!(Bank::get(bob).await.unwrap().balance, 50);
+        assert_eq!(Bank::get(carol).await.unwrap().balance, 116);
+
+        assert_eq!(
+            Bets::cancel(&pool_id, creator).await.unwrap(),
+            Ending::AlreadyEnded(PoolStatus::Resolved)
+        );
+        assert_eq!(
+            Bets::place(&pool_id, bob, 1, 10).await.unwrap(),
+            Placement::Closed
+        );
+        setup_bets().await.unwrap();
+        assert_eq!(Bank::get(alice).await.unwrap().balance, 134);
+        let won = Bank::history(alice, 0, 1).await.unwrap().remove(0);
+        assert_eq!(
+            (won.amount, won.reason.as_str()),
+            (74, "Won the bet on \"Will it rain?\"")
+        );
+        assert!(
+            !Bets::pay(&pool_id, alice.get() as i64, 74, String::new(), None)
+                .await
+                .unwrap(),
+            "a settled wager isn't paid again"
+        );
+        assert_eq!(Bank::get(alice).await.unwrap().balance, 134);
+
+        let cancelled = Bets::create(creator, String::from("Snow?"), options, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(
+            Bets::place(&cancelled, bob, 0, 10).await.unwrap(),
+            Placement::Closed
+        );
+        assert!(matches!(
+            Bets::cancel(&cancelled, creator).await.unwrap(),
+            Ending::Ended { refunded: true, .. }
+        ));
+        assert!(Bets::open().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! Wagers held while a `/bank casino` game is played, kept in `casino_game` until it's decided.
+//!
+//! The wager is taken in the same transaction that creates the row, and the row is deleted in the
+//! same transaction that pays the game out, so a game the bot restarted in the middle of is still
+//! there to refund on startup.
+
+use crate::{
+    data::DB,
+    economy::{Bank, BankAccount, RECORD_CHANGE},
+    utils::{db_id, db_record_id},
+};
+use color_eyre::eyre::{Result, eyre};
+use poise::serenity_prelude::UserId;
+use serde::Deserialize;
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// How a [`CasinoGames::start`] went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hold {
+    /// The wager is held in `game` until [`CasinoGames::finish`].
+    Held(RecordId),
+    TooPoor {
+        balance: i64,
+    },
+    Frozen,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct GameRow {
+    id: RecordId,
+    user_id: i64,
+    wager: i64,
+}
+
+pub struct CasinoGames;
+
+impl CasinoGames {
+    /// Takes `wager` from `user_id` and holds it for a game.
+    pub async fn start(user_id: UserId, wager: i64, reason: String) -> Result<Hold> {
+        let game: Option<RecordId> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $after = (UPDATE $account SET balance += $amount \
+                     WHERE balance >= -$amount AND !$freeze.exists() RETURN AFTER)[0]; \
+                 LET $game = IF $after {{ \
+                     {RECORD_CHANGE}; \
+                     (CREATE ONLY casino_game SET user_id = $user_id, wager = -$amount \
+                         RETURN VALUE id) \
+                 }}; \
+                 RETURN $game; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("user_id", db_id(user_id)?))
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+            .bind(("amount", -wager))
+            .bind(("reason", reason))
+            .bind(("gambling_net", None::<i64>))
+            .await?
+            .check()?
+            // BEGIN and both LETs come first.
+            .take(3)?;
+
+        if let Some(game) = game {
+            return Ok(Hold::Held(game));
+        }
+        if Bank::freeze(user_id).await?.is_some() {
+            return Ok(Hold::Frozen);
+        }
+
+        Ok(Hold::TooPoor {
+            balance: Bank::get(user_id).await?.balance,
+        })
+    }
+
+    /// Pays `payout` for `game` and forgets it, in one transaction.
+    ///
+    /// `gambling_net` is what the game won or lost, and `None` for a refund. Returns `None` if the
+    /// game was already paid.
+    pub async fn finish(
+        game: RecordId,
+        payout: i64,
+        gambling_net: Option<i64>,
+        reason: String,
+    ) -> Result<Option<BankAccount>> {
+        let account: Option<BankAccount> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $held = (DELETE ONLY $game RETURN BEFORE); \
+                 LET $account = IF $held {{ type::record('bank_account', $held.user_id) }}; \
+                 LET $after = IF $held {{ \
+                     {RECORD_CHANGE}; \
+                     (UPSERT ONLY $account SET balance += $amount RETURN AFTER) \
+                 }}; \
+                 RETURN $after; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("game", game))
+            .bind(("amount", payout))
+            .bind(("reason", reason))
+            .bind(("gambling_net", gambling_net))
+            .await?
+            .check()?
+            // BEGIN and the three LETs come first.
+            .take(4)?;
+
+        Ok(account)
+    }
+}
+
+/// Refunds the games that were still being played when the bot last stopped.
+pub async fn setup_casino_games() -> Result<()> {
+    let games: Vec<GameRow> = DB
+        .query("SELECT id, user_id, wager FROM casino_game")
+        .await?
+        .check()?
+        .take(0)?;
+
+    for game in games {
+        let reason = format!("Refund: Casino wager of {}", game.wager);
+        CasinoGames::finish(game.id, game.wager, None, reason)
+            .await?
+            .ok_or_else(|| eyre!("casino game for {} was refunded twice", game.user_id))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) async fn assert_held_wagers_survive_restarts() {
+        let player = UserId::new(99_961);
+
+        assert_eq!(
+            CasinoGames::start(player, 10, String::from("Casino wager of 10"))
+                .await
+                .unwrap(),
+            Hold::TooPoor { balance: 0 }
+        );
+        Bank::change(player, 100, String::from("Income"))
+            .await
+            .unwrap();
+
+        let Hold::Held(won) = CasinoGames::start(player, 10, String::from("Casino wager of 10"))
+            .await
+            .unwrap()
+        else {
+            panic!("a covered wager must be held");
+        };
+        let Hold::Held(interrupted) =
+            CasinoGames::start(player, 20, String::from("Casino wager of 20"))
+                .await
+                .unwrap()
+        else {
+            panic!("a covered wager must be held");
+        };
+        assert_eq!(Bank::get(player).await.unwrap().balance, 70);
+
+        let account = CasinoGames::finish(won.clone(), 19, Some(9), String::from("Slots"))
+            .await
+            .unwrap();
+        assert_eq!(account.map(|account| account.balance), Some(89));
+        assert_eq!(
+            CasinoGames::finish(won, 19, Some(9), String::from("Slots"))
+                .await
+                .unwrap()
+                .map(|account| account.balance),
+            None,
+            "a game can't be paid twice"
+        );
+
+        setup_casino_games().await.unwrap();
+        assert_eq!(Bank::get(player).await.unwrap().balance, 109);
+        let refund = Bank::history(player, 0, 1).await.unwrap().remove(0);
+        assert_eq!(refund.reason, "Refund: Casino wager of 20");
+        assert_eq!(
+            CasinoGames::finish(interrupted, 20, None, String::new())
+                .await
+                .unwrap()
+                .map(|account| account.balance),
+            None
+        );
+    }
+}
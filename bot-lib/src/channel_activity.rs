@@ -0,0 +1,145 @@
+//! Rolling per-channel activity counters.
+//!
+//! Kept in memory and bucketed by hour; losing them on restart only means
+//! activity-scaled thresholds start from a quiet channel again.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use poise::serenity_prelude::{ChannelId, Message, UserId};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{collections::VecDeque, sync::LazyLock};
+
+/// The longest window anyone can ask about. Older buckets are dropped.
+pub const MAX_ACTIVITY_WINDOW_HOURS: u64 = 24 * 7;
+
+static CHANNEL_ACTIVITY: LazyLock<Mutex<FxHashMap<ChannelId, ChannelActivity>>> =
+    LazyLock::new(|| Mutex::new(FxHashMap::default()));
+
+struct HourBucket {
+    hour: i64,
+    messages: u64,
+    authors: FxHashSet<UserId>,
+}
+
+#[derive(Default)]
+struct ChannelActivity {
+    buckets: VecDeque<HourBucket>,
+}
+
+/// Activity observed in a channel over some window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ActivitySummary {
+    pub messages: u64,
+    pub unique_authors: u64,
+}
+
+fn hour_of(time: DateTime<Utc>) -> i64 {
+    time.timestamp().div_euclid(3600)
+}
+
+impl ChannelActivity {
+    fn record(&mut self, author: UserId, now: DateTime<Utc>) {
+        let hour = hour_of(now);
+
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.hour == hour => {
+                bucket.messages += 1;
+                bucket.authors.insert(author);
+            }
+            _ => self.buckets.push_back(HourBucket {
+                hour,
+                messages: 1,
+                authors: FxHashSet::from_iter([author]),
+            }),
+        }
+
+        let oldest_kept = hour - MAX_ACTIVITY_WINDOW_HOURS as i64;
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| bucket.hour <= oldest_kept)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn summarize(&self, window_hours: u64, now: DateTime<Utc>) -> ActivitySummary {
+        let oldest_included = hour_of(now) - window_hours.min(MAX_ACTIVITY_WINDOW_HOURS) as i64;
+        let mut authors = FxHashSet::default();
+        let mut messages = 0;
+
+        for bucket in self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.hour > oldest_included)
+        {
+            messages += bucket.messages;
+            authors.extend(bucket.authors.iter().copied());
+        }
+
+        ActivitySummary {
+            messages,
+            unique_authors: authors.len() as u64,
+        }
+    }
+}
+
+/// Count a new message towards its channel's activity.
+pub fn record_message(message: &Message) {
+    if message.author.bot {
+        return;
+    }
+
+    CHANNEL_ACTIVITY
+        .lock()
+        .entry(message.channel_id)
+        .or_default()
+        .record(message.author.id, Utc::now());
+}
+
+/// Summarize a channel's activity over the last `window_hours` hours.
+pub fn channel_activity(channel_id: ChannelId, window_hours: u64) -> ActivitySummary {
+    CHANNEL_ACTIVITY
+        .lock()
+        .get(&channel_id)
+        .map(|activity| activity.summarize(window_hours, Utc::now()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn summary_only_counts_the_requested_window() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut activity = ChannelActivity::default();
+
+        activity.record(UserId::new(1), now - TimeDelta::hours(30));
+        activity.record(UserId::new(1), now - TimeDelta::hours(2));
+        activity.record(UserId::new(2), now - TimeDelta::hours(2));
+        activity.record(UserId::new(2), now);
+
+        assert_eq!(
+            activity.summarize(24, now),
+            ActivitySummary {
+                messages: 3,
+                unique_authors: 2,
+            }
+        );
+        assert_eq!(activity.summarize(48, now).messages, 4);
+        assert_eq!(activity.summarize(1, now).messages, 1);
+    }
+
+    #[test]
+    fn old_buckets_are_dropped() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut activity = ChannelActivity::default();
+
+        activity.record(UserId::new(1), now - TimeDelta::days(30));
+        activity.record(UserId::new(1), now);
+
+        assert_eq!(activity.buckets.len(), 1);
+    }
+}
@@ -1,8 +1,19 @@
-use super::build_history_message;
+use super::send_history;
 use crate::data::PoiseContext;
-use crate::{SayThenDelete, commands::is_stefan, economy::Bank};
+use crate::{
+    SayThenDelete,
+    bank_audit::{AuditAction, BankAudit, NewAuditEntry},
+    commands::is_stefan,
+    earnings::EarningSettings,
+    economy::{Bank, LedgerEntry, Reversal},
+    utils::GetRelativeTimestamp,
+};
 use color_eyre::eyre::Result;
-use poise::serenity_prelude::{Mentionable, User};
+use humantime::{format_duration, parse_duration};
+use poise::{
+    CreateReply,
+    serenity_prelude::{CreateAttachment, Mentionable, User},
+};
 
 #[poise::command(
     slash_command,
@@ -11,6 +22,13 @@ use poise::serenity_prelude::{Mentionable, User};
         "inspect_history",
         "inspect_balance",
         "global_rankings",
+        "earnings",
+        "freeze",
+        "unfreeze",
+        "reverse",
+        "set_balance",
+        "export_ledger",
+        "audit_log",
     )
 )]
 pub async fn bank_admin(_ctx: PoiseContext<'_>) -> Result<()> {
@@ -28,10 +46,11 @@ pub async fn give_charity(
     charity_recipient: User,
     amount: i64,
 ) -> Result<()> {
-    Bank::change(
+    Bank::give_charity(
         charity_recipient.id,
         amount,
         String::from("Stefan is very generous"),
+        ctx.author().id,
     )
     .await?;
 
@@ -45,20 +64,10 @@ pub async fn give_charity(
     Ok(())
 }
 
-/// See the last 20 transactions for a user
+/// Page through a user's transactions
 #[poise::command(slash_command, ephemeral = true)]
 pub async fn inspect_history(ctx: PoiseContext<'_>, user: User) -> Result<()> {
-    let user_id = user.id;
-
-    let Some(history) = Bank::get_history(user_id).await? else {
-        ctx.say("No history found for that user").await?;
-        return Ok(());
-    };
-
-    ctx.say_then_delete(build_history_message(history.into_iter(), user_id))
-        .await?;
-
-    Ok(())
+    send_history(ctx, user.id, true).await
 }
 
 /// See a user's balance
@@ -91,3 +100,354 @@ pub async fn global_rankings(ctx: PoiseContext<'_>) -> Result<()> {
 
     Ok(())
 }
+
+/// View or change what taking part in the server pays. Zero turns a reward off.
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn earnings(
+    ctx: PoiseContext<'_>,
+    #[description = "Paid for getting starboarded"] starboarded: Option<u64>,
+    #[description = "Paid to a yeeter whose yeet passes"] yeet_won: Option<u64>,
+    #[description = "Paid to a victim who survives the vote"] yeet_survived: Option<u64>,
+    #[description = "Paid for a message in a class channel"] class_message: Option<u64>,
+    #[description = "How often class messages pay, like '5m'"] class_message_cooldown: Option<
+        String,
+    >,
+) -> Result<()> {
+    let mut settings = EarningSettings::current();
+    let mut changed = false;
+
+    for (input, setting) in [
+        (starboarded, &mut settings.starboarded),
+        (yeet_won, &mut settings.yeet_won),
+        (yeet_survived, &mut settings.yeet_survived),
+        (class_message, &mut settings.class_message),
+    ] {
+        if let Some(input) = input {
+            *setting = input;
+            changed = true;
+        }
+    }
+
+    if let Some(input) = class_message_cooldown {
+        let Ok(cooldown) = parse_duration(input.trim()) else {
+            ctx.say("❌ Invalid time format! Say something like '90s' or '5m'")
+                .await?;
+            return Ok(());
+        };
+
+        settings.class_message_cooldown = cooldown;
+        changed = true;
+    }
+
+    if changed {
+        settings.save(ctx.author().id).await?;
+        EarningSettings::reload().await?;
+    }
+
+    ctx.say(format!(
+        "### Earnings{}\n\
+         Starboarded: {}\n\
+         Winning a yeet: {}\n\
+         Surviving a yeet: {}\n\
+         Class message: {} every {}",
+        if changed { " (updated)" } else { "" },
+        settings.starboarded,
+        settings.yeet_won,
+        settings.yeet_survived,
+        settings.class_message,
+        format_duration(settings.class_message_cooldown),
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Stop a user from spending, gambling, transferring or claiming income
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn freeze(
+    ctx: PoiseContext<'_>,
+    #[description = "Whose account to freeze"] user: User,
+    #[description = "Why, shown to them and in the audit log"] reason: String,
+) -> Result<()> {
+    if let Some(freeze) = Bank::set_frozen(user.id, ctx.author().id, reason).await? {
+        ctx.say(format!(
+            "{} was already frozen by {} {}: {}",
+            user.mention(),
+            freeze.frozen_by.mention(),
+            freeze.frozen_at.discord_relative_timestamp(),
+            freeze.reason
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(format!("{}'s account is frozen.", user.mention()))
+        .await?;
+
+    Ok(())
+}
+
+/// Let a frozen user use their account again
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn unfreeze(
+    ctx: PoiseContext<'_>,
+    #[description = "Whose account to unfreeze"] user: User,
+    #[description = "Why, for the audit log"] reason: Option<String>,
+) -> Result<()> {
+    if !Bank::unfreeze(user.id, ctx.author().id, reason.unwrap_or_default()).await? {
+        ctx.say(format!("{}'s account isn't frozen.", user.mention()))
+            .await?;
+        return Ok(());
+    }
+
+    ctx.say(format!("{}'s account is unfrozen.", user.mention()))
+        .await?;
+
+    Ok(())
+}
+
+/// Undo a transaction, using the ID from inspect_history
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn reverse(
+    ctx: PoiseContext<'_>,
+    #[description = "The transaction ID, like bank_change:abc123"] transaction: String,
+    #[description = "Why, shown in their history"] reason: String,
+) -> Result<()> {
+    let transaction = transaction.trim().trim_matches('`');
+
+    let message = match Bank::reverse(transaction, reason, ctx.author().id).await? {
+        Reversal::NotFound => format!("There's no transaction `{transaction}`."),
+        Reversal::AlreadyReversed => format!("`{transaction}` was already reversed."),
+        Reversal::Done {
+            user_id,
+            change,
+            balance,
+        } => {
+            let mut message = format!(
+                "Reversed {} for {}. Their new balance is {balance}.",
+                change.amount,
+                user_id.mention()
+            );
+            if let Some(counterparty) = change.counterparty {
+                message.push_str(&format!(
+                    " This was a transfer with {}, whose side wasn't touched.",
+                    counterparty.mention()
+                ));
+            }
+            message
+        }
+    };
+
+    ctx.say(message).await?;
+
+    Ok(())
+}
+
+/// Set a user's balance to an exact amount
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn set_balance(
+    ctx: PoiseContext<'_>,
+    #[description = "Whose balance to set"] user: User,
+    #[description = "The new balance"] balance: i64,
+    #[description = "Why, shown in their history"] reason: String,
+) -> Result<()> {
+    let before = Bank::get(user.id).await?.balance;
+    let account = Bank::set_balance(user.id, balance, reason, ctx.author().id).await?;
+
+    ctx.say(format!(
+        "{}'s balance went from {before} to {}.",
+        user.mention(),
+        account.balance
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum LedgerFormat {
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn ledger_csv(ledger: &[LedgerEntry]) -> String {
+    let optional = |value: Option<i64>| value.map(|value| value.to_string()).unwrap_or_default();
+
+    let mut csv =
+        String::from("id,user_id,amount,reason,counterparty,gambling_net,reverses,changed_at\n");
+
+    for entry in ledger {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            csv_field(&entry.id),
+            entry.user_id,
+            entry.amount,
+            csv_field(&entry.reason),
+            optional(entry.counterparty),
+            optional(entry.gambling_net),
+            csv_field(entry.reverses.as_deref().unwrap_or_default()),
+            entry
+                .changed_at
+                .map(|changed_at| changed_at.to_rfc3339())
+                .unwrap_or_default(),
+        ));
+    }
+
+    csv
+}
+
+/// Download every transaction ever made
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn export_ledger(
+    ctx: PoiseContext<'_>,
+    #[description = "What format to export in"] format: LedgerFormat,
+) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+
+    let ledger = Bank::ledger().await?;
+    let (contents, filename) = match format {
+        LedgerFormat::Csv => (ledger_csv(&ledger), "ledger.csv"),
+        LedgerFormat::Json => (serde_json::to_string_pretty(&ledger)?, "ledger.json"),
+    };
+
+    BankAudit::record(NewAuditEntry::new(
+        ctx.author().id,
+        AuditAction::Export,
+        None,
+        format!("{} transactions as {filename}", ledger.len()),
+    )?)
+    .await?;
+    ctx.send(
+        CreateReply::default()
+            .content(format!("{} transactions.", ledger.len()))
+            .attachment(CreateAttachment::bytes(contents.into_bytes(), filename)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// See the latest admin actions on the economy
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn audit_log(ctx: PoiseContext<'_>) -> Result<()> {
+    let entries = BankAudit::recent(20).await?;
+
+    if entries.is_empty() {
+        ctx.say("No admin actions yet.").await?;
+        return Ok(());
+    }
+
+    let mut message_text = String::from("### Audit log:\n");
+
+    for entry in entries {
+        let action = match entry.action {
+            AuditAction::GiveCharity => "gave charity to",
+            AuditAction::Freeze => "froze",
+            AuditAction::Unfreeze => "unfroze",
+            AuditAction::Reverse => "reversed a transaction for",
+            AuditAction::SetBalance => "set the balance of",
+            AuditAction::Export => "exported the ledger",
+            AuditAction::Earnings => "changed the earnings",
+        };
+
+        message_text.push_str(&format!(
+            "{} {} {action}",
+            entry.acted_at.discord_relative_timestamp(),
+            entry.admin.mention()
+        ));
+        if let Some(target) = entry.target {
+            message_text.push_str(&format!(" {}", target.mention()));
+        }
+        if !entry.details.is_empty() {
+            message_text.push_str(&format!(": {}", entry.details));
+        }
+        message_text.push('\n');
+    }
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ledger_csv_quotes_reasons_that_need_it() {
+        let ledger = [
+            LedgerEntry {
+                id: String::from("bank_change:[1, 0]"),
+                user_id: 1,
+                amount: -5,
+                reason: String::from("Paid <@2>: \"rent\""),
+                counterparty: Some(2),
+                gambling_net: None,
+                reverses: None,
+                changed_at: None,
+            },
+            LedgerEntry {
+                id: String::from("bank_change:abc"),
+                user_id: 1,
+                amount: 5,
+                reason: String::from("Reversed: oops"),
+                counterparty: None,
+                gambling_net: Some(-5),
+                reverses: Some(String::from("bank_change:[1, 0]")),
+                changed_at: None,
+            },
+        ];
+
+        assert_eq!(
+            ledger_csv(&ledger),
+            "id,user_id,amount,reason,counterparty,gambling_net,reverses,changed_at\n\
+             \"bank_change:[1, 0]\",1,-5,\"Paid <@2>: \"\"rent\"\"\",2,,,\n\
+             bank_change:abc,1,5,Reversed: oops,,-5,\"bank_change:[1, 0]\",\n"
+        );
+    }
+}
@@ -1,24 +1,27 @@
-use super::build_history_message;
-use crate::{SayThenDelete, data::PoiseContext, economy::Bank};
-use color_eyre::eyre::Result;
-use parking_lot::Mutex;
-use poise::{
-    CreateReply,
-    serenity_prelude::{
-        CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
-        UserId,
-    },
+use super::{casino, leaderboard, pay, request_payment, send_history};
+use crate::{
+    SayThenDelete,
+    data::PoiseContext,
+    economy::{Bank, Debit},
+    income::{DailyClaim, Income, IncomeClaim},
+    utils::GetRelativeTimestamp,
 };
+use color_eyre::eyre::Result;
 use rand::RngExt;
-use std::{
-    collections::HashMap,
-    sync::LazyLock,
-    time::{Duration, Instant},
-};
 
 #[poise::command(
     slash_command,
-    subcommands("balance", "income", "gamble", "history", "casino")
+    subcommands(
+        "balance",
+        "income",
+        "daily",
+        "gamble",
+        "history",
+        "casino",
+        "pay",
+        "request_payment",
+        "leaderboard"
+    )
 )]
 pub async fn bank(_ctx: PoiseContext<'_>) -> Result<()> {
     Ok(())
@@ -27,61 +30,77 @@ pub async fn bank(_ctx: PoiseContext<'_>) -> Result<()> {
 /// What's my balance?
 #[poise::command(slash_command, ephemeral = true)]
 pub async fn balance(ctx: PoiseContext<'_>) -> Result<()> {
+    let user_id = ctx.author().id;
+
     ctx.say_then_delete(format!(
-        "Your balance is {}",
-        Bank::get(ctx.author().id).await?.balance
+        "Your balance is {}{}",
+        Bank::get(user_id).await?.balance,
+        match Bank::freeze(user_id).await? {
+            Some(freeze) => format!(". Your account is frozen: {}", freeze.reason),
+            None => String::new(),
+        }
     ))
     .await?;
 
     Ok(())
 }
 
-const PER_USER_INCOME_TIMEOUT_SECONDS: u64 = 60;
-
-static INSTANT_BY_USER_ID: LazyLock<Mutex<HashMap<UserId, Instant>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
-static BONUS_BY_USER_ID: LazyLock<Mutex<HashMap<UserId, i64>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
-
-pub fn reset_user_bonus(user_id: UserId) {
-    BONUS_BY_USER_ID.lock().remove(&user_id);
-}
-
-pub fn get_user_bonus(user_id: UserId) -> i64 {
-    *BONUS_BY_USER_ID
-        .lock()
-        .entry(user_id)
-        .and_modify(|bonus| *bonus += 1)
-        .or_insert(0)
-}
-
 /// Get some income (5 coins, once per minute, bonus if you repeat without gambling)
 #[poise::command(slash_command, ephemeral = true)]
 pub async fn income(ctx: PoiseContext<'_>) -> Result<()> {
     let user_id = ctx.author().id;
 
-    let old_time = INSTANT_BY_USER_ID.lock().insert(user_id, Instant::now());
+    if Bank::freeze(user_id).await?.is_some() {
+        ctx.say_then_delete("Your account is frozen.").await?;
+        return Ok(());
+    }
 
-    if let Some(last_time) = old_time
-        && last_time.elapsed() < Duration::from_secs(PER_USER_INCOME_TIMEOUT_SECONDS)
-    {
+    let (IncomeClaim::Paid { bonus, .. }, account) = Income::claim(user_id).await? else {
         ctx.say_then_delete("Federal law requires you calm down")
             .await?;
         return Ok(());
-    }
-
-    let bonus_amount = get_user_bonus(user_id);
-
-    Bank::change(user_id, 5 + bonus_amount, String::from("Income")).await?;
+    };
 
     ctx.say_then_delete(format!(
         "Paycheck deposited{}! Your new balance is {}",
-        if bonus_amount > 0 {
-            format!(" with a bonus of {bonus_amount}")
+        if bonus > 0 {
+            format!(" with a bonus of {bonus}")
         } else {
             String::new()
         },
-        Bank::get(user_id).await?.balance
+        account.balance
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Collect your daily reward. Come back every day to build a streak!
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn daily(ctx: PoiseContext<'_>) -> Result<()> {
+    let user_id = ctx.author().id;
+
+    if Bank::freeze(user_id).await?.is_some() {
+        ctx.say_then_delete("Your account is frozen.").await?;
+        return Ok(());
+    }
+
+    let (claim, account) = Income::claim_daily(user_id).await?;
+    let (amount, streak) = match claim {
+        DailyClaim::Paid { amount, streak } => (amount, streak),
+        DailyClaim::AlreadyClaimed { next_at } => {
+            ctx.say_then_delete(format!(
+                "You already collected today's reward. The next one is ready {}",
+                next_at.discord_relative_timestamp()
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    ctx.say_then_delete(format!(
+        "Collected {amount} on a {streak} day streak! Your new balance is {}",
+        account.balance
     ))
     .await?;
 
@@ -97,7 +116,7 @@ pub async fn gamble(
 ) -> Result<()> {
     let user_id = ctx.author().id;
 
-    reset_user_bonus(user_id);
+    Income::reset_bonus(user_id).await?;
 
     if amount <= 0 {
         ctx.say_then_delete("Trying to gamble negative money? No.")
@@ -111,25 +130,32 @@ pub async fn gamble(
         return Ok(());
     }
 
-    let balance = Bank::get(user_id).await?.balance;
-    if balance < amount {
-        ctx.say_then_delete(format!("You don't have enough money! You have ${balance}"))
-            .await?;
-        return Ok(());
-    }
-
     let winnings = (amount as f64 / odds_of_success).round() as i64 - 1 - amount;
     let success = rand::rng().random_bool(odds_of_success);
 
     let change = if success { winnings } else { -amount };
 
-    let account = Bank::change(
+    let debit = Bank::gamble(
         user_id,
+        amount,
         change,
         format!("Gamble for {amount} at odds {odds_of_success}"),
     )
     .await?;
 
+    let account = match debit {
+        Debit::Done(account) => account,
+        Debit::TooPoor { balance } => {
+            ctx.say_then_delete(format!("You don't have enough money! You have ${balance}"))
+                .await?;
+            return Ok(());
+        }
+        Debit::Frozen => {
+            ctx.say_then_delete("Your account is frozen.").await?;
+            return Ok(());
+        }
+    };
+
     ctx.say_then_delete(format!(
         "You {}! Your new balance is {}",
         if success { "won" } else { "lost" },
@@ -143,65 +169,5 @@ pub async fn gamble(
 /// Inspect your own history
 #[poise::command(slash_command, ephemeral = true)]
 pub async fn history(ctx: PoiseContext<'_>) -> Result<()> {
-    let author = ctx.author().id;
-
-    let Some(history) = Bank::get_history(author).await? else {
-        ctx.say_then_delete("No history found for that user")
-            .await?;
-        return Ok(());
-    };
-
-    ctx.say_then_delete(build_history_message(history.into_iter(), author))
-        .await?;
-
-    Ok(())
-}
-
-/// Start the casino games
-#[poise::command(slash_command, ephemeral = true)]
-pub async fn casino(ctx: PoiseContext<'_>) -> Result<()> {
-    // create a menu to pick the game
-
-    let msg = ctx
-        .send(
-            CreateReply::default()
-                .content("Pick a game")
-                .components(vec![CreateActionRow::Buttons(vec![
-                    CreateButton::new("Test button").label("WOWIE"),
-                ])]),
-        )
-        .await?;
-
-    let msg = msg.into_message().await?;
-    let interaction = msg
-        .await_component_interaction(ctx)
-        .timeout(Duration::from_secs(10))
-        .await;
-
-    match interaction {
-        Some(interaction) => {
-            interaction
-                .create_response(
-                    ctx,
-                    CreateInteractionResponse::UpdateMessage(
-                        CreateInteractionResponseMessage::new()
-                            .content("Done")
-                            .components(vec![CreateActionRow::Buttons(vec![
-                                CreateButton::new("IT WORKED").label("IT WORKED"),
-                            ])]),
-                    ),
-                )
-                .await?;
-
-            tokio::time::sleep(Duration::from_millis(500)).await;
-
-            interaction.delete_response(ctx).await.ok();
-        }
-        _ => {
-            msg.delete(ctx).await.ok();
-            return Ok(());
-        }
-    }
-
-    Ok(())
+    send_history(ctx, ctx.author().id, false).await
 }
@@ -0,0 +1,652 @@
+//! The `/bank casino` games, each played out with buttons on a single ephemeral message.
+//!
+//! The wager is taken up front and held in `casino_game` until it's paid back with the winnings,
+//! minus the same 1 coin skim as `gamble`. Walking away before a game is decided refunds it,
+//! except from a blackjack hand, which stands on it. A game the bot restarts in the middle of is
+//! refunded on startup.
+
+use crate::{
+    SayThenDelete,
+    casino_games::{CasinoGames, Hold},
+    data::PoiseContext,
+    income::Income,
+};
+use bot_traits::ForwardRefToTracing;
+use color_eyre::eyre::{OptionExt, Result};
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton,
+        CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+        Message,
+    },
+};
+use rand::RngExt;
+use std::time::Duration;
+
+const CLICK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The balance change for a game that paid back `multiplier` times the wager.
+fn settle(wager: i64, multiplier: f64) -> i64 {
+    if multiplier <= 0.0 {
+        -wager
+    } else {
+        (wager as f64 * multiplier).round() as i64 - 1 - wager
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Game {
+    Blackjack,
+    Slots,
+    Roulette,
+    HighLow,
+}
+
+impl Game {
+    const ALL: [Self; 4] = [Self::Blackjack, Self::Slots, Self::Roulette, Self::HighLow];
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::Blackjack => "casino_blackjack",
+            Self::Slots => "casino_slots",
+            Self::Roulette => "casino_roulette",
+            Self::HighLow => "casino_high_low",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Blackjack => "Blackjack",
+            Self::Slots => "Slots",
+            Self::Roulette => "Roulette",
+            Self::HighLow => "High-low",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|game| game.id() == id)
+    }
+}
+
+const SLOT_SYMBOLS: [&str; 6] = ["🍒", "🍋", "🍊", "🍇", "🔔", "💎"];
+
+/// Three of a kind and pairs each pay back half of what's wagered on average.
+fn slots_multiplier([a, b, c]: [usize; 3]) -> f64 {
+    if a == b && b == c {
+        18.0
+    } else if a == b || b == c || a == c {
+        1.2
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RouletteBet {
+    Red,
+    Black,
+    Odd,
+    Even,
+    Low,
+    High,
+    Zero,
+}
+
+const RED_NUMBERS: [u8; 18] = [
+    1, 3, 5, 7, 9, 12, 14, 16, 18, 19, 21, 23, 25, 27, 30, 32, 34, 36,
+];
+
+impl RouletteBet {
+    const ALL: [Self; 7] = [
+        Self::Red,
+        Self::Black,
+        Self::Odd,
+        Self::Even,
+        Self::Low,
+        Self::High,
+        Self::Zero,
+    ];
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::Red => "casino_roulette_red",
+            Self::Black => "casino_roulette_black",
+            Self::Odd => "casino_roulette_odd",
+            Self::Even => "casino_roulette_even",
+            Self::Low => "casino_roulette_low",
+            Self::High => "casino_roulette_high",
+            Self::Zero => "casino_roulette_zero",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Red => "Red",
+            Self::Black => "Black",
+            Self::Odd => "Odd",
+            Self::Even => "Even",
+            Self::Low => "1-18",
+            Self::High => "19-36",
+            Self::Zero => "0",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|bet| bet.id() == id)
+    }
+
+    fn wins(self, number: u8) -> bool {
+        match self {
+            Self::Red => RED_NUMBERS.contains(&number),
+            Self::Black => number != 0 && !RED_NUMBERS.contains(&number),
+            Self::Odd => number % 2 == 1,
+            Self::Even => number != 0 && number.is_multiple_of(2),
+            Self::Low => (1..=18).contains(&number),
+            Self::High => (19..=36).contains(&number),
+            Self::Zero => number == 0,
+        }
+    }
+
+    /// What a win pays back on a wheel with a single zero.
+    fn multiplier(self) -> f64 {
+        match self {
+            Self::Zero => 37.0,
+            _ => 37.0 / 18.0,
+        }
+    }
+}
+
+/// What guessing that the next card is strictly `higher` (or lower) than `card` pays back.
+///
+/// Cards go from 1 (ace) to 13 (king), so some guesses can't win at all.
+fn high_low_multiplier(card: u8, higher: bool) -> Option<f64> {
+    let winning_cards = if higher { 13 - card } else { card - 1 };
+
+    (winning_cards > 0).then(|| 13.0 / f64::from(winning_cards))
+}
+
+fn card_name(card: u8) -> String {
+    match card {
+        1 => String::from("A"),
+        11 => String::from("J"),
+        12 => String::from("Q"),
+        13 => String::from("K"),
+        card => card.to_string(),
+    }
+}
+
+fn hand_name(cards: &[u8]) -> String {
+    cards
+        .iter()
+        .map(|&card| card_name(card))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn hand_value(cards: &[u8]) -> u8 {
+    let value: u8 = cards.iter().map(|&card| card.min(10)).sum();
+    let has_ace = cards.contains(&1);
+
+    if has_ace && value + 10 <= 21 {
+        value + 10
+    } else {
+        value
+    }
+}
+
+fn is_blackjack(cards: &[u8]) -> bool {
+    cards.len() == 2 && hand_value(cards) == 21
+}
+
+/// What a finished blackjack hand pays back, at the usual 3:2 for a natural.
+fn blackjack_multiplier(player: &[u8], dealer: &[u8]) -> f64 {
+    let (player_value, dealer_value) = (hand_value(player), hand_value(dealer));
+
+    match (is_blackjack(player), is_blackjack(dealer)) {
+        _ if player_value > 21 => 0.0,
+        (true, false) => 2.5,
+        (false, true) => 0.0,
+        _ if dealer_value > 21 || player_value > dealer_value => 2.0,
+        _ if player_value == dealer_value => 1.0,
+        _ => 0.0,
+    }
+}
+
+fn draw_card() -> u8 {
+    rand::rng().random_range(1..=13)
+}
+
+fn button(id: &str, label: &str) -> CreateButton {
+    CreateButton::new(id)
+        .label(label)
+        .style(ButtonStyle::Secondary)
+}
+
+async fn update(
+    ctx: PoiseContext<'_>,
+    interaction: &ComponentInteraction,
+    content: String,
+    components: Vec<CreateActionRow>,
+) -> Result<()> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(components),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn next_click(ctx: PoiseContext<'_>, message: &Message) -> Option<ComponentInteraction> {
+    message
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(CLICK_TIMEOUT)
+        .await
+}
+
+/// The click a game was decided on, whose message the result replaces the game with.
+enum LastClick {
+    /// Still needs a response.
+    Pending(ComponentInteraction),
+    /// Already responded to, like a blackjack hand that was walked away from.
+    Answered(ComponentInteraction),
+}
+
+impl LastClick {
+    async fn show(self, ctx: PoiseContext<'_>, content: String) -> Result<()> {
+        match self {
+            Self::Pending(interaction) => update(ctx, &interaction, content, Vec::new()).await,
+            Self::Answered(interaction) => {
+                interaction
+                    .edit_response(
+                        ctx,
+                        EditInteractionResponse::new()
+                            .content(content)
+                            .components(Vec::new()),
+                    )
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// How a game ended: what it paid back, a description for the history, and what to show.
+struct Finish {
+    multiplier: f64,
+    reason: String,
+    summary: String,
+}
+
+/// Play a casino game. KingFisher skims 1 coin off every payout.
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn casino(
+    ctx: PoiseContext<'_>,
+    #[description = "Amount to wager"] wager: i64,
+) -> Result<()> {
+    let user_id = ctx.author().id;
+
+    Income::reset_bonus(user_id).await?;
+
+    if wager <= 0 {
+        ctx.say_then_delete("Trying to gamble negative money? No.")
+            .await?;
+        return Ok(());
+    }
+
+    // The wager is held until the game ends, so it can't be spent twice meanwhile.
+    let wagered = format!("Casino wager of {wager}");
+    let game = match CasinoGames::start(user_id, wager, wagered.clone()).await? {
+        Hold::Held(game) => game,
+        Hold::TooPoor { balance } => {
+            ctx.say_then_delete(format!("You don't have enough money! You have ${balance}"))
+                .await?;
+            return Ok(());
+        }
+        Hold::Frozen => {
+            ctx.say_then_delete("Your account is frozen.").await?;
+            return Ok(());
+        }
+    };
+
+    let played = play(ctx, wager).await;
+
+    let (last_click, finish) = match played {
+        Ok(Played::Finished(last_click, finish)) => (last_click, finish),
+        Ok(Played::Abandoned(message)) => {
+            CasinoGames::finish(game, wager, None, format!("Refund: {wagered}")).await?;
+            message.delete(ctx).await.ok();
+            return Ok(());
+        }
+        Err(error) => {
+            // Left held otherwise, to be refunded on the next startup.
+            CasinoGames::finish(game, wager, None, format!("Refund: {wagered}"))
+                .await
+                .trace_err_ok();
+            return Err(error);
+        }
+    };
+
+    let change = settle(wager, finish.multiplier);
+    let account = CasinoGames::finish(game, wager + change, Some(change), finish.reason)
+        .await?
+        .ok_or_eyre("casino game was already paid out")?;
+
+    last_click
+        .show(
+            ctx,
+            format!(
+                "{}\n\nYou {} {}! Your new balance is {}",
+                finish.summary,
+                if change >= 0 { "won" } else { "lost" },
+                change.abs(),
+                account.balance
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Played {
+    Finished(LastClick, Finish),
+    /// Walked away before the game could be decided.
+    Abandoned(Message),
+}
+
+async fn play(ctx: PoiseContext<'_>, wager: i64) -> Result<Played> {
+    let message = ctx
+        .send(
+            CreateReply::default()
+                .content(format!("Pick a game to play for {wager}"))
+                .components(vec![CreateActionRow::Buttons(
+                    Game::ALL
+                        .into_iter()
+                        .map(|game| button(game.id(), game.label()))
+                        .collect(),
+                )]),
+        )
+        .await?
+        .into_message()
+        .await?;
+
+    let Some(interaction) = next_click(ctx, &message).await else {
+        return Ok(Played::Abandoned(message));
+    };
+
+    let Some(game) = Game::from_id(&interaction.data.custom_id) else {
+        return Ok(Played::Abandoned(message));
+    };
+
+    let played = match game {
+        Game::Blackjack => play_blackjack(ctx, &message, interaction, wager).await?,
+        Game::Slots => Some(play_slots(interaction, wager)),
+        Game::Roulette => play_roulette(ctx, &message, interaction, wager).await?,
+        Game::HighLow => play_high_low(ctx, &message, interaction, wager).await?,
+    };
+
+    Ok(match played {
+        Some((interaction, finish)) => Played::Finished(interaction, finish),
+        None => Played::Abandoned(message),
+    })
+}
+
+fn play_slots(interaction: ComponentInteraction, wager: i64) -> (LastClick, Finish) {
+    let reels: [usize; 3] =
+        std::array::from_fn(|_| rand::rng().random_range(0..SLOT_SYMBOLS.len()));
+    let shown = reels.map(|reel| SLOT_SYMBOLS[reel]).concat();
+
+    (
+        LastClick::Pending(interaction),
+        Finish {
+            multiplier: slots_multiplier(reels),
+            reason: format!("Slots for {wager}: {shown}"),
+            summary: format!("🎰 {shown}"),
+        },
+    )
+}
+
+async fn play_roulette(
+    ctx: PoiseContext<'_>,
+    message: &Message,
+    interaction: ComponentInteraction,
+    wager: i64,
+) -> Result<Option<(LastClick, Finish)>> {
+    let rows = RouletteBet::ALL
+        .chunks(4)
+        .map(|bets| {
+            CreateActionRow::Buttons(
+                bets.iter()
+                    .map(|bet| button(bet.id(), bet.label()))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    update(
+        ctx,
+        &interaction,
+        format!("Where do you put your {wager}?"),
+        rows,
+    )
+    .await?;
+
+    let Some(interaction) = next_click(ctx, message).await else {
+        return Ok(None);
+    };
+    let Some(bet) = RouletteBet::from_id(&interaction.data.custom_id) else {
+        return Ok(None);
+    };
+
+    let number = rand::rng().random_range(0..=36);
+    let multiplier = if bet.wins(number) {
+        bet.multiplier()
+    } else {
+        0.0
+    };
+
+    Ok(Some((
+        LastClick::Pending(interaction),
+        Finish {
+            multiplier,
+            reason: format!("Roulette for {wager} on {}: {number}", bet.label()),
+            summary: format!(
+                "The ball lands on **{number}**. You bet on {}.",
+                bet.label()
+            ),
+        },
+    )))
+}
+
+async fn play_high_low(
+    ctx: PoiseContext<'_>,
+    message: &Message,
+    interaction: ComponentInteraction,
+    wager: i64,
+) -> Result<Option<(LastClick, Finish)>> {
+    let card = draw_card();
+
+    let buttons = [
+        ("casino_higher", "Higher", true),
+        ("casino_lower", "Lower", false),
+    ]
+    .into_iter()
+    .map(
+        |(id, label, higher)| match high_low_multiplier(card, higher) {
+            Some(multiplier) => button(id, &format!("{label} ({multiplier:.2}x)")),
+            None => button(id, label).disabled(true),
+        },
+    )
+    .collect();
+
+    update(
+        ctx,
+        &interaction,
+        format!(
+            "The card is **{}**. Is the next one higher or lower?",
+            card_name(card)
+        ),
+        vec![CreateActionRow::Buttons(buttons)],
+    )
+    .await?;
+
+    let Some(interaction) = next_click(ctx, message).await else {
+        return Ok(None);
+    };
+    let higher = interaction.data.custom_id == "casino_higher";
+
+    let next = draw_card();
+    let won = if higher { next > card } else { next < card };
+    let multiplier = match high_low_multiplier(card, higher) {
+        Some(multiplier) if won => multiplier,
+        _ => 0.0,
+    };
+    let guess = if higher { "higher" } else { "lower" };
+
+    Ok(Some((
+        LastClick::Pending(interaction),
+        Finish {
+            multiplier,
+            reason: format!(
+                "High-low for {wager}: {} then {} ({guess})",
+                card_name(card),
+                card_name(next)
+            ),
+            summary: format!(
+                "**{}**, then **{}**. You guessed {guess}.",
+                card_name(card),
+                card_name(next)
+            ),
+        },
+    )))
+}
+
+async fn play_blackjack(
+    ctx: PoiseContext<'_>,
+    message: &Message,
+    mut interaction: ComponentInteraction,
+    wager: i64,
+) -> Result<Option<(LastClick, Finish)>> {
+    let mut player = vec![draw_card(), draw_card()];
+    let mut dealer = vec![draw_card()];
+    let mut answered = false;
+
+    while hand_value(&player) < 21 {
+        update(
+            ctx,
+            &interaction,
+            format!(
+                "Dealer: **{}**\nYou: **{}** ({})",
+                hand_name(&dealer),
+                hand_name(&player),
+                hand_value(&player)
+            ),
+            vec![CreateActionRow::Buttons(vec![
+                button("casino_hit", "Hit"),
+                button("casino_stand", "Stand"),
+            ])],
+        )
+        .await?;
+
+        // Walking away from a hand stands on it, so a bad one can't be dodged.
+        let Some(click) = next_click(ctx, message).await else {
+            answered = true;
+            break;
+        };
+        interaction = click;
+
+        if interaction.data.custom_id != "casino_hit" {
+            break;
+        }
+
+        player.push(draw_card());
+    }
+
+    if hand_value(&player) <= 21 {
+        while hand_value(&dealer) < 17 {
+            dealer.push(draw_card());
+        }
+    }
+
+    let last_click = if answered {
+        LastClick::Answered(interaction)
+    } else {
+        LastClick::Pending(interaction)
+    };
+
+    Ok(Some((
+        last_click,
+        Finish {
+            multiplier: blackjack_multiplier(&player, &dealer),
+            reason: format!(
+                "Blackjack for {wager}: {} against {}",
+                hand_value(&player),
+                hand_value(&dealer)
+            ),
+            summary: format!(
+                "Dealer: **{}** ({})\nYou: **{}** ({})",
+                hand_name(&dealer),
+                hand_value(&dealer),
+                hand_name(&player),
+                hand_value(&player)
+            ),
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payouts_are_skimmed_like_gamble() {
+        assert_eq!(settle(10, 0.0), -10);
+        assert_eq!(settle(10, 1.0), -1, "a push still costs the skim");
+        assert_eq!(settle(10, 2.0), 9);
+        assert_eq!(settle(18, 37.0 / 18.0), 18);
+    }
+
+    #[test]
+    fn games_pay_fair_odds_before_the_skim() {
+        let slots: f64 = (0..SLOT_SYMBOLS.len())
+            .flat_map(|a| (0..SLOT_SYMBOLS.len()).map(move |b| (a, b)))
+            .flat_map(|(a, b)| (0..SLOT_SYMBOLS.len()).map(move |c| [a, b, c]))
+            .map(slots_multiplier)
+            .sum::<f64>()
+            / 216.0;
+        assert!((slots - 1.0).abs() < 1e-9);
+
+        for bet in RouletteBet::ALL {
+            let wins = (0..=36).filter(|&number| bet.wins(number)).count();
+            assert!((bet.multiplier() * wins as f64 / 37.0 - 1.0).abs() < 1e-9);
+        }
+
+        assert_eq!(high_low_multiplier(13, true), None);
+        assert_eq!(high_low_multiplier(1, false), None);
+        assert_eq!(high_low_multiplier(7, true), Some(13.0 / 6.0));
+    }
+
+    #[test]
+    fn blackjack_hands_are_scored() {
+        assert_eq!(hand_value(&[1, 13]), 21);
+        assert_eq!(hand_value(&[1, 1, 9]), 21);
+        assert_eq!(hand_value(&[1, 9, 5]), 15);
+        assert!(is_blackjack(&[1, 12]));
+        assert!(!is_blackjack(&[5, 6, 10]));
+
+        assert_eq!(blackjack_multiplier(&[1, 12], &[10, 9]), 2.5);
+        assert_eq!(blackjack_multiplier(&[10, 9], &[10, 7]), 2.0);
+        assert_eq!(blackjack_multiplier(&[10, 7], &[10, 7]), 1.0);
+        assert_eq!(blackjack_multiplier(&[10, 5, 9], &[10, 5, 9]), 0.0);
+        assert_eq!(blackjack_multiplier(&[10, 7], &[10, 5, 9]), 2.0);
+        assert_eq!(blackjack_multiplier(&[5, 6, 10], &[1, 10]), 0.0);
+    }
+}
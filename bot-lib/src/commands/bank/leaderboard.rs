@@ -0,0 +1,119 @@
+use super::send_pages;
+use crate::{
+    data::PoiseContext,
+    economy::{Bank, Leaderboard},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::Result;
+use poise::{
+    ChoiceParameter,
+    serenity_prelude::{Mentionable, UserId},
+};
+use std::future;
+
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+/// How far back a leaderboard looks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Window {
+    #[name = "Past day"]
+    Day,
+    #[name = "Past week"]
+    Week,
+    #[name = "Past month"]
+    Month,
+    #[default]
+    #[name = "All time"]
+    AllTime,
+}
+
+impl Window {
+    fn since(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Day => Some(now - TimeDelta::days(1)),
+            Self::Week => Some(now - TimeDelta::weeks(1)),
+            Self::Month => Some(now - TimeDelta::days(30)),
+            Self::AllTime => None,
+        }
+    }
+}
+
+fn build_leaderboard_message(
+    title: &str,
+    rankings: &[(UserId, i64)],
+    caller: UserId,
+    page: usize,
+) -> String {
+    let pages = rankings.len().div_ceil(LEADERBOARD_PAGE_SIZE);
+    let mut message_text = format!("### {title} ({}/{pages}):\n", page + 1);
+
+    let start = page * LEADERBOARD_PAGE_SIZE;
+    for (rank, (user_id, score)) in rankings
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(LEADERBOARD_PAGE_SIZE)
+    {
+        message_text.push_str(&format!("`#{}` {}: {score}\n", rank + 1, user_id.mention()));
+    }
+
+    match rankings.iter().position(|&(user_id, _)| user_id == caller) {
+        Some(rank) => message_text.push_str(&format!(
+            "\nYou're #{} with {}.",
+            rank + 1,
+            rankings[rank].1
+        )),
+        None => message_text.push_str("\nYou aren't on this board yet."),
+    }
+
+    message_text
+}
+
+/// Who's on top? Also ranks gambling profit and the biggest wins
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn leaderboard(
+    ctx: PoiseContext<'_>,
+    #[description = "What to rank by"] board: Option<Leaderboard>,
+    #[description = "How far back to look"] window: Option<Window>,
+) -> Result<()> {
+    let board = board.unwrap_or(Leaderboard::Balance);
+    let window = window.unwrap_or_default();
+
+    let rankings = Bank::leaderboard(board, window.since(Utc::now())).await?;
+
+    if rankings.is_empty() {
+        ctx.say("Nobody is on this board yet.").await?;
+        return Ok(());
+    }
+
+    let title = format!("{} ({})", board.name(), window.name());
+    let pages = rankings.len().div_ceil(LEADERBOARD_PAGE_SIZE) as u64;
+    let caller = ctx.author().id;
+
+    send_pages(ctx, pages, ("Previous", "Next"), |page| {
+        let message_text = build_leaderboard_message(&title, &rankings, caller, page as usize);
+        future::ready(Ok(message_text))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaderboard_pages_show_the_callers_rank() {
+        let rankings = (1..=12)
+            .map(|n| (UserId::new(n), 100 - n as i64))
+            .collect::<Vec<_>>();
+
+        let first = build_leaderboard_message("Richest", &rankings, UserId::new(11), 0);
+        assert!(first.starts_with("### Richest (1/2):\n`#1` <@1>: 99\n"));
+        assert!(!first.contains("`#11`"));
+        assert!(first.ends_with("You're #11 with 89."));
+
+        let second = build_leaderboard_message("Richest", &rankings, UserId::new(99), 1);
+        assert!(second.contains("`#11` <@11>: 89\n`#12` <@12>: 88\n"));
+        assert!(second.ends_with("You aren't on this board yet."));
+    }
+}
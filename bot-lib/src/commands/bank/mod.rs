@@ -1,20 +1,135 @@
 mod bank_admin;
 mod bank_user;
+mod casino;
+mod leaderboard;
+mod transfers;
 
-use crate::economy::Change;
+use crate::{
+    SayThenDelete,
+    data::PoiseContext,
+    economy::{Bank, Change},
+    utils::GetRelativeTimestamp,
+};
 pub use bank_admin::*;
 pub use bank_user::*;
-use poise::serenity_prelude::{Mentionable, UserId};
+pub use casino::*;
+use color_eyre::eyre::Result;
+pub use leaderboard::*;
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        ButtonStyle, CreateActionRow, CreateButton, CreateInteractionResponse,
+        CreateInteractionResponseMessage, Mentionable, UserId,
+    },
+};
+use std::time::Duration;
+pub use transfers::*;
 
-fn build_history_message(history: impl DoubleEndedIterator<Item = Change>, user: UserId) -> String {
-    let mut message_text = String::from("### History:\n");
+const HISTORY_PAGE_SIZE: u64 = 15;
+
+/// `show_ids` adds what admins need to reverse each change.
+fn build_history_message(
+    changes: &[Change],
+    user: UserId,
+    page: u64,
+    pages: u64,
+    show_ids: bool,
+) -> String {
+    let mut message_text = format!("### History ({}/{pages}):\n", page + 1);
 
     message_text.push_str(&user.mention().to_string());
     message_text.push('\n');
 
-    for Change { amount, reason } in history.rev().take(20) {
-        message_text.push_str(&format!("`{amount:>9}`: {reason}\n"));
+    for change in changes {
+        message_text.push_str(&format!("`{:>9}`: {}", change.amount, change.reason));
+        if let Some(changed_at) = change.changed_at {
+            message_text.push_str(&format!(" ({})", changed_at.discord_relative_timestamp()));
+        }
+        if show_ids {
+            message_text.push_str(&format!(" `{}`", change.id));
+        }
+        message_text.push('\n');
     }
 
     message_text
 }
+
+fn page_buttons(labels: (&str, &str), page: u64, pages: u64) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new("bank_page_back")
+            .label(labels.0)
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new("bank_page_forward")
+            .label(labels.1)
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages),
+    ])]
+}
+
+/// Shows one of `pages` at a time, with buttons labelled `labels` to flip through them, until
+/// the buttons go quiet.
+async fn send_pages<F>(
+    ctx: PoiseContext<'_>,
+    pages: u64,
+    labels: (&str, &str),
+    render: impl Fn(u64) -> F,
+) -> Result<()>
+where
+    F: Future<Output = Result<String>>,
+{
+    let mut page = 0;
+
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(render(page).await?)
+                .components(page_buttons(labels, page, pages)),
+        )
+        .await?;
+    let message = reply.message().await?;
+
+    while let Some(interaction) = message
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(Duration::from_secs(60))
+        .await
+    {
+        page = match interaction.data.custom_id.as_str() {
+            "bank_page_back" => page.saturating_sub(1),
+            _ => (page + 1).min(pages - 1),
+        };
+
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(render(page).await?)
+                        .components(page_buttons(labels, page, pages)),
+                ),
+            )
+            .await?;
+    }
+
+    reply.delete(ctx).await.ok();
+
+    Ok(())
+}
+
+/// Shows `user`'s history a page at a time, newest first.
+async fn send_history(ctx: PoiseContext<'_>, user: UserId, show_ids: bool) -> Result<()> {
+    let pages = Bank::history_len(user).await?.div_ceil(HISTORY_PAGE_SIZE);
+
+    if pages == 0 {
+        ctx.say_then_delete("No history found for that user")
+            .await?;
+        return Ok(());
+    }
+
+    send_pages(ctx, pages, ("Newer", "Older"), |page| async move {
+        let changes = Bank::history(user, page * HISTORY_PAGE_SIZE, HISTORY_PAGE_SIZE).await?;
+        Ok(build_history_message(&changes, user, page, pages, show_ids))
+    })
+    .await
+}
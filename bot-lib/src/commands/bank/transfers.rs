@@ -0,0 +1,175 @@
+use crate::{
+    SayThenDelete,
+    data::PoiseContext,
+    economy::{Bank, Transfer},
+};
+use color_eyre::eyre::Result;
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        ButtonStyle, CreateActionRow, CreateButton, CreateInteractionResponse,
+        CreateInteractionResponseMessage, EditMessage, Mentionable, User,
+    },
+};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+fn with_memo(reason: String, memo: Option<&str>) -> String {
+    match memo {
+        Some(memo) => format!("{reason}: {memo}"),
+        None => reason,
+    }
+}
+
+/// Why `author` can't move money to or from `other`, if they can't.
+fn refuse_transfer(author: &User, other: &User, amount: i64) -> Option<&'static str> {
+    if amount <= 0 {
+        Some("You can only move a positive amount of money.")
+    } else if author.id == other.id {
+        Some("That's your own money already.")
+    } else if other.bot {
+        Some("Bots don't have bank accounts.")
+    } else {
+        None
+    }
+}
+
+/// Pay another user from your balance
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn pay(
+    ctx: PoiseContext<'_>,
+    #[description = "Who to pay"] recipient: User,
+    #[description = "Amount to pay"] amount: i64,
+    #[description = "What the payment is for"] memo: Option<String>,
+) -> Result<()> {
+    let author = ctx.author();
+
+    if let Some(refusal) = refuse_transfer(author, &recipient, amount) {
+        ctx.say_then_delete(refusal).await?;
+        return Ok(());
+    }
+
+    let transfer = Bank::transfer(
+        author.id,
+        recipient.id,
+        amount,
+        with_memo(format!("Paid {}", recipient.mention()), memo.as_deref()),
+        with_memo(format!("Paid by {}", author.mention()), memo.as_deref()),
+    )
+    .await?;
+
+    match transfer {
+        Transfer::Done { from_balance, .. } => {
+            ctx.say(format!(
+                "You paid {} {amount}. Your new balance is {from_balance}",
+                recipient.mention()
+            ))
+            .await?;
+        }
+        Transfer::TooPoor { balance } => {
+            ctx.say_then_delete(format!("You don't have enough money! You have ${balance}"))
+                .await?;
+        }
+        Transfer::Frozen { user_id } => {
+            ctx.say_then_delete(format!("{}'s account is frozen.", user_id.mention()))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask another user to pay you
+#[poise::command(slash_command, guild_only, rename = "request")]
+pub async fn request_payment(
+    ctx: PoiseContext<'_>,
+    #[description = "Who should pay you"] payer: User,
+    #[description = "Amount to ask for"] amount: i64,
+    #[description = "What the payment is for"] memo: Option<String>,
+) -> Result<()> {
+    let author = ctx.author();
+
+    if let Some(refusal) = refuse_transfer(author, &payer, amount) {
+        ctx.send(CreateReply::default().content(refusal).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let asked = format!(
+        "{} is asking {} for {amount}{}",
+        author.mention(),
+        payer.mention(),
+        memo.as_deref()
+            .map(|memo| format!(" for \"{memo}\""))
+            .unwrap_or_default()
+    );
+
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new("bank_request_accept")
+            .label("Pay")
+            .style(ButtonStyle::Success),
+        CreateButton::new("bank_request_decline")
+            .label("Decline")
+            .style(ButtonStyle::Danger),
+    ]);
+
+    let mut message = ctx
+        .send(
+            CreateReply::default()
+                .content(&asked)
+                .components(vec![buttons]),
+        )
+        .await?
+        .into_message()
+        .await?;
+
+    let Some(interaction) = message
+        .await_component_interaction(ctx)
+        .author_id(payer.id)
+        .timeout(REQUEST_TIMEOUT)
+        .await
+    else {
+        message
+            .edit(
+                ctx,
+                EditMessage::new()
+                    .content(format!("{asked}\n\nThe request expired."))
+                    .components(Vec::new()),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let outcome = if interaction.data.custom_id == "bank_request_accept" {
+        let transfer = Bank::transfer(
+            payer.id,
+            author.id,
+            amount,
+            with_memo(format!("Paid {}", author.mention()), memo.as_deref()),
+            with_memo(format!("Paid by {}", payer.mention()), memo.as_deref()),
+        )
+        .await?;
+
+        match transfer {
+            Transfer::Done { .. } => format!("{} paid up.", payer.mention()),
+            Transfer::TooPoor { .. } => format!("{} can't afford it.", payer.mention()),
+            Transfer::Frozen { user_id } => format!("{}'s account is frozen.", user_id.mention()),
+        }
+    } else {
+        format!("{} declined.", payer.mention())
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("{asked}\n\n{outcome}"))
+                    .components(Vec::new()),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,486 @@
+use crate::{
+    bets::{BetPool, Bets, Ending, Payout, Placement, PoolStatus},
+    data::PoiseContext,
+    utils::{GetRelativeTimestamp, SendReplyEphemeral},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::Result;
+use humantime::parse_duration;
+use poise::serenity_prelude::{AutocompleteChoice, Mentionable, ResolvedOption, ResolvedValue};
+
+const MAX_QUESTION_LENGTH: usize = 200;
+/// Discord cuts autocomplete choices off at 100 characters.
+const MAX_OPTION_LENGTH: usize = 80;
+const CLOSES_IN: std::ops::RangeInclusive<TimeDelta> = TimeDelta::minutes(1)..=TimeDelta::days(30);
+
+#[poise::command(
+    slash_command,
+    subcommands(
+        "bet_create",
+        "bet_place",
+        "bet_show",
+        "bet_list",
+        "bet_resolve",
+        "bet_cancel",
+    ),
+    rename = "bet",
+    guild_only
+)]
+pub async fn bet(_ctx: PoiseContext<'_>) -> Result<()> {
+    Ok(())
+}
+
+fn describe_pool(pool: &BetPool, now: DateTime<Utc>) -> String {
+    let total = pool.total();
+    let mut message_text = format!("### {}\n", pool.question);
+
+    for (index, (option, &stake)) in pool.options.iter().zip(&pool.stakes).enumerate() {
+        let won = if pool.outcome == Some(index) {
+            " ✅"
+        } else {
+            ""
+        };
+        let share = if total > 0 { stake * 100 / total } else { 0 };
+        message_text.push_str(&format!(
+            "`{}.` **{option}**{won}: ${stake} ({share}%)\n",
+            index + 1
+        ));
+    }
+
+    let state = match pool.status {
+        PoolStatus::Open if pool.takes_wagers(now) => format!(
+            "Taking bets until {}.",
+            pool.closes_at.discord_relative_timestamp()
+        ),
+        PoolStatus::Open => format!(
+            "Stopped taking bets {}. Waiting on {} or a moderator to resolve it.",
+            pool.closes_at.discord_relative_timestamp(),
+            pool.creator.mention()
+        ),
+        PoolStatus::Resolved => String::from("Resolved."),
+        PoolStatus::Cancelled => String::from("Cancelled, so everyone got their stake back."),
+    };
+    message_text.push_str(&format!(
+        "\nThe pool is ${total}, started by {}. {state}",
+        pool.creator.mention()
+    ));
+
+    message_text
+}
+
+/// The value of the `pool` parameter the user already filled in, for autocompleting the others.
+fn chosen_pool(ctx: PoiseContext<'_>) -> Option<String> {
+    fn find(options: Vec<ResolvedOption<'_>>) -> Option<String> {
+        options.into_iter().find_map(|option| match option.value {
+            ResolvedValue::String(pool) if option.name == "pool" => Some(pool.to_owned()),
+            ResolvedValue::SubCommand(options) => find(options),
+            _ => None,
+        })
+    }
+
+    let poise::Context::Application(ctx) = ctx else {
+        return None;
+    };
+
+    find(ctx.interaction.data.options())
+}
+
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_owned();
+    }
+
+    let mut truncated = text.chars().take(max_length - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+async fn autocomplete_pool(_ctx: PoiseContext<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+
+    Bets::open()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|pool| pool.question.to_lowercase().contains(&partial))
+        .map(|pool| AutocompleteChoice::new(truncate(&pool.question, 100), pool.id))
+        .collect()
+}
+
+async fn autocomplete_option(ctx: PoiseContext<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(pool_id) = chosen_pool(ctx) else {
+        return Vec::new();
+    };
+    let Ok(Some(pool)) = Bets::get(&pool_id).await else {
+        return Vec::new();
+    };
+    let partial = partial.to_lowercase();
+
+    pool.options
+        .iter()
+        .enumerate()
+        .filter(|(_, option)| option.to_lowercase().contains(&partial))
+        .map(|(index, option)| AutocompleteChoice::new(option.clone(), (index + 1).to_string()))
+        .collect()
+}
+
+/// Picks an option by its number or its name.
+fn find_option(pool: &BetPool, option: &str) -> Option<usize> {
+    let option = option.trim();
+
+    match option.parse::<usize>() {
+        Ok(number) => (1..=pool.options.len())
+            .contains(&number)
+            .then(|| number - 1),
+        Err(_) => pool
+            .options
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(option)),
+    }
+}
+
+/// Finds the pool picked with autocomplete, or says why it couldn't.
+async fn find_pool(ctx: PoiseContext<'_>, pool_id: &str) -> Result<Option<BetPool>> {
+    let pool = Bets::get(pool_id.trim()).await?;
+
+    if pool.is_none() {
+        ctx.say("There's no such bet. Pick one from the list.")
+            .await?;
+    }
+
+    Ok(pool)
+}
+
+/// Whether the caller can resolve or cancel the pool.
+async fn can_end(ctx: PoiseContext<'_>, pool: &BetPool) -> bool {
+    if pool.creator == ctx.author().id {
+        return true;
+    }
+
+    ctx.author_member()
+        .await
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.moderate_members())
+}
+
+/// Start a bet that others can wager on
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, rename = "create")]
+pub async fn bet_create(
+    ctx: PoiseContext<'_>,
+    #[description = "What's being bet on"] question: String,
+    #[description = "The first option"] option_1: String,
+    #[description = "The second option"] option_2: String,
+    #[description = "How long bets are taken for, like 1h or 2days"] closes_in: String,
+    #[description = "Another option"] option_3: Option<String>,
+    #[description = "Another option"] option_4: Option<String>,
+    #[description = "Another option"] option_5: Option<String>,
+    #[description = "Another option"] option_6: Option<String>,
+) -> Result<()> {
+    let question = question.trim().to_owned();
+    let options = [
+        Some(option_1),
+        Some(option_2),
+        option_3,
+        option_4,
+        option_5,
+        option_6,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|option| option.trim().to_owned())
+    .filter(|option| !option.is_empty())
+    .collect::<Vec<_>>();
+
+    let problem = if question.is_empty() || question.chars().count() > MAX_QUESTION_LENGTH {
+        Some(format!(
+            "Questions must be 1 to {MAX_QUESTION_LENGTH} characters."
+        ))
+    } else if options.len() < 2 {
+        Some(String::from("A bet needs at least two options."))
+    } else if options
+        .iter()
+        .any(|option| option.chars().count() > MAX_OPTION_LENGTH)
+    {
+        Some(format!(
+            "Options can be at most {MAX_OPTION_LENGTH} characters."
+        ))
+    } else if options.iter().enumerate().any(|(index, option)| {
+        options[..index]
+            .iter()
+            .any(|earlier| earlier.eq_ignore_ascii_case(option))
+    }) {
+        Some(String::from("Every option has to be different."))
+    } else {
+        None
+    };
+
+    if let Some(problem) = problem {
+        ctx.reply_ephemeral(problem).await?;
+        return Ok(());
+    }
+
+    let closes_in = parse_duration(&closes_in)
+        .ok()
+        .and_then(|closes_in| TimeDelta::from_std(closes_in).ok())
+        .filter(|closes_in| CLOSES_IN.contains(closes_in));
+    let Some(closes_in) = closes_in else {
+        ctx.reply_ephemeral("Bets can close in 1 minute to 30 days, like `1h` or `2days`.")
+            .await?;
+        return Ok(());
+    };
+
+    let now = Utc::now();
+    let pool_id = Bets::create(ctx.author().id, question, options, now + closes_in).await?;
+
+    if let Some(pool) = Bets::get(&pool_id).await? {
+        ctx.say(format!(
+            "{}\nPlace a bet with `/bet place`.",
+            describe_pool(&pool, now)
+        ))
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Put money on an option
+#[poise::command(slash_command, ephemeral = true, rename = "place")]
+pub async fn bet_place(
+    ctx: PoiseContext<'_>,
+    #[description = "Which bet"]
+    #[autocomplete = "autocomplete_pool"]
+    pool: String,
+    #[description = "What you're betting on"]
+    #[autocomplete = "autocomplete_option"]
+    option: String,
+    #[description = "How much to bet"]
+    #[min = 1]
+    amount: i64,
+) -> Result<()> {
+    let Some(pool) = find_pool(ctx, &pool).await? else {
+        return Ok(());
+    };
+    let Some(option) = find_option(&pool, &option) else {
+        ctx.say("That isn't one of the options.").await?;
+        return Ok(());
+    };
+
+    if amount < 1 {
+        ctx.say("You have to bet something.").await?;
+        return Ok(());
+    }
+
+    let message = match Bets::place(&pool.id, ctx.author().id, option, amount).await? {
+        Placement::Placed { stake, balance } => format!(
+            "You have ${stake} on **{}**. Your new balance is ${balance}.",
+            pool.options[option]
+        ),
+        Placement::NotFound => String::from("There's no such bet. Pick one from the list."),
+        Placement::Closed => String::from("That bet isn't taking wagers anymore."),
+        Placement::OwnPool => String::from("You can't bet on your own question."),
+        Placement::OtherOption { option } => format!(
+            "You already bet on **{}**. You can only add to that.",
+            pool.options[option]
+        ),
+        Placement::TooPoor { balance } => {
+            format!("You don't have enough money! You have ${balance}")
+        }
+        Placement::Frozen => String::from("Your account is frozen."),
+    };
+
+    ctx.say(message).await?;
+
+    Ok(())
+}
+
+/// See the options and stakes on a bet
+#[poise::command(slash_command, ephemeral = true, rename = "show")]
+pub async fn bet_show(
+    ctx: PoiseContext<'_>,
+    #[description = "Which bet"]
+    #[autocomplete = "autocomplete_pool"]
+    pool: String,
+) -> Result<()> {
+    let Some(pool) = find_pool(ctx, &pool).await? else {
+        return Ok(());
+    };
+
+    ctx.say(describe_pool(&pool, Utc::now())).await?;
+
+    Ok(())
+}
+
+/// See every bet waiting on an outcome
+#[poise::command(slash_command, ephemeral = true, rename = "list")]
+pub async fn bet_list(ctx: PoiseContext<'_>) -> Result<()> {
+    let pools = Bets::open().await?;
+
+    if pools.is_empty() {
+        ctx.say("There aren't any bets going. Start one with `/bet create`.")
+            .await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let mut message_text = String::from("### Bets:\n");
+
+    for pool in &pools {
+        let state = if pool.takes_wagers(now) {
+            format!("closes {}", pool.closes_at.discord_relative_timestamp())
+        } else {
+            String::from("waiting on a result")
+        };
+        message_text.push_str(&format!(
+            "**{}**: ${} in the pool, {state}\n",
+            pool.question,
+            pool.total()
+        ));
+    }
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+fn describe_ending(pool: &BetPool, payouts: &[Payout], refunded: bool) -> String {
+    let mut message_text = describe_pool(pool, Utc::now());
+
+    if payouts.is_empty() {
+        message_text.push_str("\nNobody bet on it.");
+    } else if refunded {
+        if pool.status == PoolStatus::Resolved {
+            message_text.push_str("\nNobody backed the winner, so everyone got their stake back.");
+        }
+    } else {
+        message_text.push('\n');
+        for payout in payouts.iter().filter(|payout| payout.payout > 0) {
+            message_text.push_str(&format!(
+                "\n{} won ${} on ${}",
+                payout.user_id.mention(),
+                payout.payout,
+                payout.stake
+            ));
+        }
+    }
+
+    message_text
+}
+
+/// Pick the winning option and pay out (creator or moderator only)
+#[poise::command(slash_command, rename = "resolve")]
+pub async fn bet_resolve(
+    ctx: PoiseContext<'_>,
+    #[description = "Which bet"]
+    #[autocomplete = "autocomplete_pool"]
+    pool: String,
+    #[description = "The option that won"]
+    #[autocomplete = "autocomplete_option"]
+    outcome: String,
+) -> Result<()> {
+    let Some(pool) = find_pool(ctx, &pool).await? else {
+        return Ok(());
+    };
+    let Some(outcome) = find_option(&pool, &outcome) else {
+        ctx.say("That isn't one of the options.").await?;
+        return Ok(());
+    };
+
+    if !can_end(ctx, &pool).await {
+        ctx.say("Only whoever started the bet or a moderator can resolve it.")
+            .await?;
+        return Ok(());
+    }
+
+    let message = match Bets::resolve(&pool.id, outcome, ctx.author().id).await? {
+        Ending::Ended {
+            pool,
+            payouts,
+            refunded,
+        } => describe_ending(&pool, &payouts, refunded),
+        Ending::NotFound => String::from("There's no such bet. Pick one from the list."),
+        Ending::AlreadyEnded(_) => String::from("That bet already ended."),
+    };
+
+    ctx.say(message).await?;
+
+    Ok(())
+}
+
+/// Call off a bet and refund everyone (creator or moderator only)
+#[poise::command(slash_command, rename = "cancel")]
+pub async fn bet_cancel(
+    ctx: PoiseContext<'_>,
+    #[description = "Which bet"]
+    #[autocomplete = "autocomplete_pool"]
+    pool: String,
+) -> Result<()> {
+    let Some(pool) = find_pool(ctx, &pool).await? else {
+        return Ok(());
+    };
+
+    if !can_end(ctx, &pool).await {
+        ctx.say("Only whoever started the bet or a moderator can cancel it.")
+            .await?;
+        return Ok(());
+    }
+
+    let message = match Bets::cancel(&pool.id, ctx.author().id).await? {
+        Ending::Ended {
+            pool,
+            payouts,
+            refunded,
+        } => describe_ending(&pool, &payouts, refunded),
+        Ending::NotFound => String::from("There's no such bet. Pick one from the list."),
+        Ending::AlreadyEnded(_) => String::from("That bet already ended."),
+    };
+
+    ctx.say(message).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poise::serenity_prelude::UserId;
+
+    fn pool(status: PoolStatus, outcome: Option<usize>) -> BetPool {
+        BetPool {
+            id: String::from("abc"),
+            question: String::from("Will it rain?"),
+            options: vec![String::from("Yes"), String::from("No")],
+            creator: UserId::new(1),
+            closes_at: Utc::now() + TimeDelta::hours(1),
+            status,
+            outcome,
+            stakes: vec![30, 10],
+        }
+    }
+
+    #[test]
+    fn options_are_picked_by_number_or_name() {
+        let pool = pool(PoolStatus::Open, None);
+
+        assert_eq!(find_option(&pool, "2"), Some(1));
+        assert_eq!(find_option(&pool, " yes "), Some(0));
+        assert_eq!(find_option(&pool, "0"), None);
+        assert_eq!(find_option(&pool, "3"), None);
+        assert_eq!(find_option(&pool, "Maybe"), None);
+    }
+
+    #[test]
+    fn pools_show_each_options_share() {
+        let open = describe_pool(&pool(PoolStatus::Open, None), Utc::now());
+        assert!(
+            open.starts_with(
+                "### Will it rain?\n`1.` **Yes**: $30 (75%)\n`2.` **No**: $10 (25%)\n"
+            )
+        );
+        assert!(open.contains("The pool is $40, started by <@1>. Taking bets until"));
+
+        let resolved = describe_pool(&pool(PoolStatus::Resolved, Some(1)), Utc::now());
+        assert!(resolved.contains("**No** ✅: $10"));
+        assert!(resolved.ends_with("Resolved."));
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{SayThenDelete, data::PoiseContext};
+use crate::{SayThenDelete, data::PoiseContext, shop::Shop};
 use bot_traits::ForwardRefToTracing;
 use color_eyre::eyre::Result;
 use futures::StreamExt;
@@ -37,8 +37,10 @@ pub async fn clip_that(
 
     let old_time = INSTANT_BY_USER_ID.lock().insert(user_id, Instant::now());
 
+    // A clip bought from the shop skips the cooldown.
     if let Some(last_time) = old_time
         && last_time.elapsed() < Duration::from_secs(300)
+        && !Shop::use_clip(user_id).await?
     {
         ctx.say_then_delete("300 more seconds of no clipping :)")
             .await?;
@@ -1,10 +1,14 @@
-use crate::data::{DB, PoiseContext};
+use crate::{
+    TimeoutExt,
+    data::{DB, PoiseContext},
+    timeout_history::TimeoutCause,
+};
 use chrono::{DateTime, TimeZone, Utc};
 use chrono_tz::America::Denver;
 use color_eyre::eyre::{OptionExt, Result};
 use poise::serenity_prelude::{
     self as serenity, ButtonStyle, CreateActionRow, CreateButton, CreateInteractionResponse,
-    CreateInteractionResponseMessage, CreateMessage, EditMember, GuildId, User, UserId,
+    CreateInteractionResponseMessage, CreateMessage, GuildId, User, UserId,
 };
 use std::time::Duration;
 use surrealdb::types::SurrealValue;
@@ -133,10 +137,12 @@ async fn apply_timeout_and_notify(
     // Apply timeout only if in a guild
     if let Some(guild_id) = message.guild_id {
         let result = guild_id
-            .edit_member(
+            .timeout(
                 ctx,
-                user_id,
-                EditMember::new().disable_communication_until(timeout_end.to_rfc3339()),
+                &user_id,
+                (timeout_end - Utc::now()).to_std().unwrap_or_default(),
+                TimeoutCause::MessageLimit,
+                imposed_by.unwrap_or(user_id),
             )
             .await;
 
@@ -2,6 +2,7 @@ mod admin;
 mod anon_notify;
 mod aur_search;
 mod bank;
+mod bet;
 mod cice;
 mod class_commands;
 mod clip_that;
@@ -15,6 +16,8 @@ mod reroll_reply;
 mod sathya;
 mod set_bot_role;
 mod set_dog_role;
+mod shop;
+mod starboard;
 mod timeout;
 mod track_flight;
 mod yeet;
@@ -23,6 +26,7 @@ pub use admin::*;
 pub use anon_notify::*;
 pub use aur_search::*;
 pub use bank::*;
+pub use bet::*;
 pub use cice::*;
 pub use class_commands::*;
 pub use clip_that::*;
@@ -36,6 +40,8 @@ pub use reroll_reply::*;
 pub use sathya::*;
 pub use set_bot_role::*;
 pub use set_dog_role::*;
+pub use shop::*;
+pub use starboard::*;
 pub use timeout::*;
 pub use track_flight::*;
 pub use yeet::*;
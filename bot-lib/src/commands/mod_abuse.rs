@@ -1,14 +1,14 @@
 use crate::{
+    TimeoutExt,
     data::PoiseContext,
+    scheduled_actions::{ScheduledAction, schedule},
+    timeout_history::TimeoutCause,
     utils::{GetRelativeTimestamp, SendReplyEphemeral},
 };
 use color_eyre::eyre::{ContextCompat, Result};
 use human_repr::HumanDuration;
 use humantime::parse_duration;
-use poise::{
-    CreateReply,
-    serenity_prelude::{EditMember, Mentionable, User},
-};
+use poise::serenity_prelude::{Mentionable, User};
 use rand::prelude::*;
 use std::time::Duration;
 
@@ -43,19 +43,18 @@ pub async fn mod_abuse(
         return ctx.reply_ephemeral("_huh_").await;
     }
 
-    let timeout_end = chrono::Utc::now() + time;
-
     let guild_id = ctx.guild_id().wrap_err("No guild ID?")?;
 
-    if guild_id
-        .edit_member(
+    let Ok((_, timeout_end)) = guild_id
+        .timeout(
             ctx,
-            target.id,
-            EditMember::new().disable_communication_until(timeout_end.to_rfc3339()),
+            &target.id,
+            time,
+            TimeoutCause::ModAbuse,
+            ctx.author().id,
         )
         .await
-        .is_err()
-    {
+    else {
         ctx.say(format!("Everyone laugh at {} with timeout privileges that dared to abuse them (to attempt to kill {}) and then STILL FAIL.", ctx.author().mention(), target.mention())).await?;
 
         return Ok(());
@@ -93,21 +92,26 @@ pub async fn mod_abuse(
         ))
         .await?;
 
-    tokio::time::sleep(time - std::time::Duration::from_secs(1)).await;
+    let announcement = reply_handle.message().await?;
 
-    reply_handle
-        .edit(
-            ctx,
-            CreateReply::default().content(format!(
+    // Discord ends the timeout by itself, so only the announcement needs updating.
+    schedule(
+        ctx.serenity_context(),
+        timeout_end,
+        ScheduledAction::EditMessage {
+            channel_id: announcement.channel_id,
+            message_id: announcement.id,
+            content: format!(
                 "❗mod abuse alert❗\n\n{} {} for {} by {}{}.\n\nThey have since returned.",
                 target.mention(),
                 participate_verbiage[1],
                 time.human_duration(),
                 ctx.author().mention(),
                 reason
-            )),
-        )
-        .await?;
+            ),
+        },
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,247 @@
+use crate::{
+    data::PoiseContext,
+    shop::{
+        InventoryItem, MAX_NICKNAME_LENGTH, MAX_REPLY_LENGTH, Order, Purchase, Shop, ShopItem,
+        ShopItemKind, TRIGGER_LENGTH, normalize_trigger,
+    },
+    utils::GetRelativeTimestamp,
+};
+use color_eyre::eyre::{ContextCompat, Result};
+use humantime::format_duration;
+
+#[poise::command(slash_command, subcommands("list", "buy", "inventory"))]
+pub async fn shop(_ctx: PoiseContext<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// What's for sale?
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn list(ctx: PoiseContext<'_>) -> Result<()> {
+    let items = ctx.data().config.read().await.shop.clone();
+
+    if items.is_empty() {
+        ctx.say("The shop is empty right now.").await?;
+        return Ok(());
+    }
+
+    let mut message_text = String::from("### Shop\n");
+
+    for item in &items {
+        message_text.push_str(&format!(
+            "**{}** ({}): {} _{}_\n",
+            item.name,
+            item.price,
+            item.description,
+            describe(item.kind)
+        ));
+    }
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+fn describe(kind: ShopItemKind) -> String {
+    match kind {
+        ShopItemKind::Role { duration: None, .. } => String::from("Yours to keep."),
+        ShopItemKind::Role {
+            duration: Some(duration),
+            ..
+        }
+        | ShopItemKind::Nickname { duration }
+        | ShopItemKind::AutoReply { duration } => format!("Lasts {}.", format_duration(duration)),
+        ShopItemKind::ClipThatUses { uses: 1 } => String::from("1 use."),
+        ShopItemKind::ClipThatUses { uses } => format!("{uses} uses."),
+    }
+}
+
+async fn autocomplete_item(ctx: PoiseContext<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+
+    ctx.data()
+        .config
+        .read()
+        .await
+        .shop
+        .iter()
+        .filter(|item| item.name.to_lowercase().contains(&partial))
+        .map(|item| item.name.clone())
+        .collect()
+}
+
+/// Builds the order for `item`, or explains what's missing from it.
+fn order_for(
+    item: &ShopItem,
+    nickname: Option<String>,
+    trigger: Option<String>,
+    reply: Option<String>,
+) -> Result<Order, String> {
+    match item.kind {
+        ShopItemKind::Nickname { .. } => {
+            let nickname = nickname
+                .map(|nickname| nickname.trim().to_owned())
+                .filter(|nickname| !nickname.is_empty())
+                .ok_or_else(|| format!("Pick a `nickname` to buy {}.", item.name))?;
+
+            if nickname.chars().count() > MAX_NICKNAME_LENGTH {
+                return Err(format!(
+                    "Nicknames can be at most {MAX_NICKNAME_LENGTH} characters."
+                ));
+            }
+
+            Ok(Order::Nickname(nickname))
+        }
+        ShopItemKind::AutoReply { .. } => {
+            let (Some(trigger), Some(reply)) = (trigger, reply) else {
+                return Err(format!(
+                    "Pick a `trigger` and a `reply` to buy {}.",
+                    item.name
+                ));
+            };
+            let trigger = normalize_trigger(&trigger);
+            let reply = reply.trim().to_owned();
+
+            if !TRIGGER_LENGTH.contains(&trigger.chars().count()) {
+                return Err(format!(
+                    "Triggers must be {} to {} characters.",
+                    TRIGGER_LENGTH.start(),
+                    TRIGGER_LENGTH.end()
+                ));
+            }
+
+            if reply.is_empty() || reply.chars().count() > MAX_REPLY_LENGTH {
+                return Err(format!(
+                    "Replies must be 1 to {MAX_REPLY_LENGTH} characters."
+                ));
+            }
+
+            Ok(Order::AutoReply { trigger, reply })
+        }
+        ShopItemKind::Role { .. } | ShopItemKind::ClipThatUses { .. } => Ok(Order::Plain),
+    }
+}
+
+/// Buy something from the shop
+#[poise::command(slash_command, ephemeral = true, guild_only)]
+pub async fn buy(
+    ctx: PoiseContext<'_>,
+    #[description = "What to buy"]
+    #[autocomplete = "autocomplete_item"]
+    item: String,
+    #[description = "Your new nickname, for nickname items"] nickname: Option<String>,
+    #[description = "What the bot replies to, for auto-reply items"] trigger: Option<String>,
+    #[description = "What the bot replies with, for auto-reply items"] reply: Option<String>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().wrap_err("No guild ID?")?;
+    let item = ctx
+        .data()
+        .config
+        .read()
+        .await
+        .shop
+        .iter()
+        .find(|listed| listed.name.eq_ignore_ascii_case(item.trim()))
+        .cloned();
+
+    let Some(item) = item else {
+        ctx.say("That isn't in the shop. Try `/shop list`.").await?;
+        return Ok(());
+    };
+
+    let order = match order_for(&item, nickname, trigger, reply) {
+        Ok(order) => order,
+        Err(problem) => {
+            ctx.say(problem).await?;
+            return Ok(());
+        }
+    };
+
+    let purchase = Shop::buy(
+        ctx.serenity_context(),
+        guild_id,
+        ctx.author().id,
+        &item,
+        order,
+    )
+    .await?;
+
+    let message = match purchase {
+        Purchase::Bought {
+            expires_at: Some(expires_at),
+            balance,
+        } => format!(
+            "You bought {}, which runs out {}. Your new balance is {balance}.",
+            item.name,
+            expires_at.discord_relative_timestamp()
+        ),
+        Purchase::Bought {
+            expires_at: None,
+            balance,
+        } => format!("You bought {}. Your new balance is {balance}.", item.name),
+        Purchase::TooPoor { price, balance } => {
+            format!("That costs {price}, but you only have {balance}.")
+        }
+        Purchase::AlreadyOwned {
+            expires_at: Some(expires_at),
+        } => format!(
+            "You already have {}. You can buy it again once it runs out {}.",
+            item.name,
+            expires_at.discord_relative_timestamp()
+        ),
+        Purchase::AlreadyOwned { expires_at: None } => {
+            format!("You already have {}.", item.name)
+        }
+        Purchase::TriggerTaken => String::from("Someone else already bought that trigger."),
+        Purchase::Frozen => String::from("Your account is frozen."),
+    };
+
+    ctx.say(message).await?;
+
+    Ok(())
+}
+
+fn describe_owned(owned: &InventoryItem) -> String {
+    let mut line = format!("**{}**", owned.item);
+
+    if let Some(trigger) = &owned.trigger {
+        line.push_str(&format!(" (\"{trigger}\")"));
+    }
+
+    if let Some(uses_left) = owned.uses_left {
+        line.push_str(&format!(", {uses_left} left"));
+    }
+
+    match owned.expires_at {
+        Some(expires_at) => line.push_str(&format!(
+            ", runs out {}",
+            expires_at.discord_relative_timestamp()
+        )),
+        None if owned.uses_left.is_none() => line.push_str(", yours to keep"),
+        None => {}
+    }
+
+    line
+}
+
+/// What have I bought?
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn inventory(ctx: PoiseContext<'_>) -> Result<()> {
+    let items = Shop::inventory(ctx.author().id).await?;
+
+    if items.is_empty() {
+        ctx.say("You haven't bought anything that's still around.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut message_text = String::from("### Inventory\n");
+
+    for owned in &items {
+        message_text.push_str(&describe_owned(owned));
+        message_text.push('\n');
+    }
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
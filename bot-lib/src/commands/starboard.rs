@@ -0,0 +1,491 @@
+use crate::{
+    data::PoiseContext,
+    starboard::{
+        ActivityMetric, ActivityThreshold, ChannelThresholdOverride, Starboard, StarboardFormat,
+        StarboardRecord,
+    },
+    starboard_history::{StarboardEntry, StarboardHistory},
+    utils::GetRelativeTimestamp,
+};
+use color_eyre::eyre::Result;
+use humantime::{format_duration, parse_duration};
+use itertools::Itertools;
+use poise::serenity_prelude::{ChannelId, CreateAllowedMentions, GuildId, Mentionable, User};
+
+#[poise::command(
+    slash_command,
+    subcommands(
+        "starboard_top",
+        "starboard_random",
+        "starboard_stats",
+        "starboard_list",
+        "starboard_create",
+        "starboard_edit",
+        "starboard_delete",
+    ),
+    rename = "starboard",
+    guild_only
+)]
+pub async fn starboard(_ctx: PoiseContext<'_>) -> Result<()> {
+    Ok(())
+}
+
+fn describe_entry(entry: &StarboardEntry, guild_id: Option<GuildId>) -> String {
+    format!(
+        "{} by {} in {}{} {}",
+        entry.message_id().link(entry.channel_id(), guild_id),
+        entry.author_id().mention(),
+        entry.channel_id().mention(),
+        match (&entry.reaction, entry.reaction_count) {
+            (Some(reaction), Some(count)) => format!(" with {count} {reaction}"),
+            _ => String::new(),
+        },
+        entry.time.discord_relative_timestamp(),
+    )
+}
+
+/// See who has been starboarded the most
+#[poise::command(slash_command, ephemeral = true, rename = "top")]
+pub async fn starboard_top(
+    ctx: PoiseContext<'_>,
+    #[description = "How far back to look, like '7d' or '4w' (defaults to all time)"]
+    window: Option<String>,
+) -> Result<()> {
+    let window = match window.as_deref().map(parse_duration) {
+        Some(Ok(window)) => Some(window),
+        Some(Err(_)) => {
+            ctx.say("Invalid time format! Say something like '7d' or '4w'")
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
+
+    let top_authors = StarboardHistory::top_authors(window, 10).await?;
+
+    if top_authors.is_empty() {
+        ctx.say("Nobody has been starboarded in that time.").await?;
+        return Ok(());
+    }
+
+    let mut message_text = String::from("### Starboard leaderboard:\n");
+
+    for (rank, (user_id, count)) in top_authors.into_iter().enumerate() {
+        message_text.push_str(&format!("{}. {}: {}\n", rank + 1, user_id.mention(), count));
+    }
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+/// Resurface a random starboarded message
+#[poise::command(slash_command, rename = "random")]
+pub async fn starboard_random(ctx: PoiseContext<'_>) -> Result<()> {
+    let Some(entry) = StarboardHistory::random_entry().await? else {
+        ctx.say("Nothing has been starboarded yet.").await?;
+        return Ok(());
+    };
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "From the hall of fame: {}",
+                describe_entry(&entry, ctx.guild_id())
+            ))
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// See how often someone has been starboarded
+#[poise::command(slash_command, ephemeral = true, rename = "stats")]
+pub async fn starboard_stats(
+    ctx: PoiseContext<'_>,
+    #[description = "The user to look up (defaults to yourself)"] user: Option<User>,
+) -> Result<()> {
+    let user = user.as_ref().unwrap_or_else(|| ctx.author());
+    let stats = StarboardHistory::author_stats(user.id).await?;
+
+    if stats.entries == 0 {
+        ctx.say(format!("{} has never been starboarded.", user.mention()))
+            .await?;
+        return Ok(());
+    }
+
+    let mut message_text = format!(
+        "### Starboard stats for {}\n**Entries:** {}\n**Total reactions:** {}\n",
+        user.mention(),
+        stats.entries,
+        stats.reactions
+    );
+
+    let best_entries = StarboardHistory::best_entries(user.id, 3).await?;
+
+    if !best_entries.is_empty() {
+        message_text.push_str("**Best entries:**\n");
+
+        for entry in &best_entries {
+            message_text.push_str(&format!("- {}\n", describe_entry(entry, ctx.guild_id())));
+        }
+    }
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+fn describe_starboard(starboard: &Starboard) -> String {
+    let mut description = format!(
+        "- {}: {} reactions, {} format",
+        ChannelId::new(starboard.channel_id).mention(),
+        starboard.reaction_count,
+        match starboard.format {
+            StarboardFormat::Forward => "forward",
+            StarboardFormat::Embed => "embed",
+        }
+    );
+
+    if let Some(max_message_age) = starboard.max_message_age {
+        description.push_str(&format!(
+            "\n  - Ignores messages older than {}",
+            format_duration(max_message_age)
+        ));
+    }
+
+    if let Some(activity_threshold) = &starboard.activity_threshold {
+        description.push_str(&format!(
+            "\n  - Scaled by {} over {}h (baseline {}, between {} and {} reactions)",
+            match activity_threshold.metric {
+                ActivityMetric::Messages => "messages",
+                ActivityMetric::Authors => "authors",
+            },
+            activity_threshold.window_hours,
+            activity_threshold.baseline,
+            activity_threshold.min_reaction_count,
+            activity_threshold.max_reaction_count,
+        ));
+    }
+
+    if let Some(channel_overrides) = starboard
+        .channel_overrides
+        .as_ref()
+        .filter(|overrides| !overrides.is_empty())
+    {
+        description.push_str(&format!(
+            "\n  - Overrides: {}",
+            channel_overrides
+                .iter()
+                .map(|channel_override| format!(
+                    "{} needs {}",
+                    ChannelId::new(channel_override.channel_id).mention(),
+                    channel_override.reaction_count
+                ))
+                .join(", ")
+        ));
+    }
+
+    if let Some(ignored_channel_ids) = starboard
+        .ignored_channel_ids
+        .as_ref()
+        .filter(|ignored| !ignored.is_empty())
+    {
+        description.push_str(&format!(
+            "\n  - Ignores: {}",
+            ignored_channel_ids
+                .iter()
+                .map(|&channel_id| ChannelId::new(channel_id).mention().to_string())
+                .join(" ")
+        ));
+    }
+
+    if let Some(banned_reactions) = starboard
+        .banned_reactions
+        .as_ref()
+        .filter(|banned| !banned.is_empty())
+    {
+        description.push_str(&format!(
+            "\n  - Banned reactions: {}",
+            banned_reactions.join(" ")
+        ));
+    }
+
+    description
+}
+
+/// List the configured starboards
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    rename = "list",
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn starboard_list(ctx: PoiseContext<'_>) -> Result<()> {
+    let starboards = Starboard::all();
+
+    if starboards.is_empty() {
+        ctx.say("No starboards are configured. Use `/starboard create` to add one.")
+            .await?;
+        return Ok(());
+    }
+
+    let message_text = format!(
+        "### Starboards:\n{}",
+        starboards
+            .iter()
+            .map(|starboard| describe_starboard(starboard))
+            .join("\n")
+    );
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+/// Create a starboard (moderator only)
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    rename = "create",
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn starboard_create(
+    ctx: PoiseContext<'_>,
+    #[description = "The channel starboarded messages are posted in"] channel: ChannelId,
+    #[description = "How many of one reaction a message needs"] reaction_count: u64,
+    #[description = "How starboarded messages are posted (defaults to forward)"] format: Option<
+        StarboardFormat,
+    >,
+) -> Result<()> {
+    if Starboard::find(channel).is_some() {
+        ctx.say("❌ That channel already has a starboard. Use `/starboard edit` instead.")
+            .await?;
+        return Ok(());
+    }
+
+    if reaction_count == 0 {
+        ctx.say("❌ A starboard needs at least one reaction.")
+            .await?;
+        return Ok(());
+    }
+
+    StarboardRecord {
+        sending_channel_id: channel.get(),
+        reaction_count,
+        banned_reactions: None,
+        ignored_channel_ids: None,
+        activity_threshold: None,
+        channel_overrides: None,
+        format: format.unwrap_or_default(),
+        max_message_age: None,
+    }
+    .save()
+    .await?;
+    Starboard::reload().await?;
+
+    ctx.say(format!("✅ Created a starboard in {}.", channel.mention()))
+        .await?;
+
+    Ok(())
+}
+
+/// Change a starboard's settings (moderator only)
+#[allow(clippy::too_many_arguments)]
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    rename = "edit",
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn starboard_edit(
+    ctx: PoiseContext<'_>,
+    #[description = "The channel the starboard posts in"] channel: ChannelId,
+    #[description = "How many of one reaction a message needs"] reaction_count: Option<u64>,
+    #[description = "How starboarded messages are posted"] format: Option<StarboardFormat>,
+    #[description = "Space separated unicode reactions to ignore, or 'none'"]
+    banned_reactions: Option<String>,
+    #[description = "Start or stop ignoring messages from this channel"]
+    toggle_ignored_channel: Option<ChannelId>,
+    #[description = "A source channel with its own threshold"] override_channel: Option<ChannelId>,
+    #[description = "The threshold for override_channel, 0 removes the override"]
+    override_reaction_count: Option<u64>,
+    #[description = "Activity at which reaction_count applies unchanged, 0 disables scaling"]
+    activity_baseline: Option<u64>,
+    #[description = "What activity scaling counts"] activity_metric: Option<ActivityMetric>,
+    #[description = "How many hours of activity to look at"] activity_window_hours: Option<u64>,
+    #[description = "The lowest scaled threshold"] activity_min_reaction_count: Option<u64>,
+    #[description = "The highest scaled threshold"] activity_max_reaction_count: Option<u64>,
+    #[description = "Ignore messages older than this, like '1w' or '30d', or 'default'"]
+    max_message_age: Option<String>,
+) -> Result<()> {
+    let Some(starboard) = Starboard::find(channel) else {
+        ctx.say("❌ That channel doesn't have a starboard.").await?;
+        return Ok(());
+    };
+
+    let mut record = starboard.to_record();
+
+    if let Some(reaction_count) = reaction_count {
+        if reaction_count == 0 {
+            ctx.say("❌ A starboard needs at least one reaction.")
+                .await?;
+            return Ok(());
+        }
+
+        record.reaction_count = reaction_count;
+    }
+
+    if let Some(format) = format {
+        record.format = format;
+    }
+
+    if let Some(max_message_age) = max_message_age {
+        record.max_message_age = match max_message_age.trim() {
+            "default" => None,
+            max_message_age => {
+                let Ok(max_message_age) = parse_duration(max_message_age) else {
+                    ctx.say("❌ Invalid time format! Say something like '1w' or '30d'")
+                        .await?;
+                    return Ok(());
+                };
+
+                Some(max_message_age)
+            }
+        };
+    }
+
+    if let Some(banned_reactions) = banned_reactions {
+        record.banned_reactions = match banned_reactions.trim() {
+            "none" => None,
+            banned_reactions => Some(
+                banned_reactions
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect(),
+            ),
+        };
+    }
+
+    if let Some(toggled) = toggle_ignored_channel {
+        let ignored_channel_ids = record.ignored_channel_ids.get_or_insert_default();
+
+        if let Some(position) = ignored_channel_ids
+            .iter()
+            .position(|&channel_id| channel_id == toggled.get())
+        {
+            ignored_channel_ids.remove(position);
+        } else {
+            ignored_channel_ids.push(toggled.get());
+        }
+    }
+
+    match (override_channel, override_reaction_count) {
+        (Some(override_channel), Some(override_reaction_count)) => {
+            let channel_overrides = record.channel_overrides.get_or_insert_default();
+            channel_overrides
+                .retain(|channel_override| channel_override.channel_id != override_channel.get());
+
+            if override_reaction_count > 0 {
+                channel_overrides.push(ChannelThresholdOverride {
+                    channel_id: override_channel.get(),
+                    reaction_count: override_reaction_count,
+                });
+            }
+        }
+        (None, None) => {}
+        _ => {
+            ctx.say("❌ `override_channel` and `override_reaction_count` go together.")
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let editing_activity = activity_metric.is_some()
+        || activity_window_hours.is_some()
+        || activity_min_reaction_count.is_some()
+        || activity_max_reaction_count.is_some();
+
+    record.activity_threshold = match (activity_baseline, record.activity_threshold) {
+        (Some(0), _) => None,
+        (Some(baseline), Some(activity_threshold)) => Some(ActivityThreshold {
+            baseline,
+            ..activity_threshold
+        }),
+        (Some(baseline), None) => Some(ActivityThreshold {
+            metric: ActivityMetric::default(),
+            window_hours: 24,
+            baseline,
+            min_reaction_count: 1,
+            max_reaction_count: record.reaction_count.saturating_mul(2),
+        }),
+        (None, None) if editing_activity => {
+            ctx.say("❌ Set `activity_baseline` to turn on activity scaling first.")
+                .await?;
+            return Ok(());
+        }
+        (None, activity_threshold) => activity_threshold,
+    };
+
+    if let Some(activity_threshold) = record.activity_threshold.as_mut() {
+        if let Some(metric) = activity_metric {
+            activity_threshold.metric = metric;
+        }
+        if let Some(window_hours) = activity_window_hours {
+            activity_threshold.window_hours = window_hours;
+        }
+        if let Some(min_reaction_count) = activity_min_reaction_count {
+            activity_threshold.min_reaction_count = min_reaction_count;
+        }
+        if let Some(max_reaction_count) = activity_max_reaction_count {
+            activity_threshold.max_reaction_count = max_reaction_count;
+        }
+
+        if activity_threshold.min_reaction_count > activity_threshold.max_reaction_count {
+            ctx.say("❌ The minimum scaled threshold can't be above the maximum.")
+                .await?;
+            return Ok(());
+        }
+    }
+
+    record.save().await?;
+    Starboard::reload().await?;
+
+    let Some(starboard) = Starboard::find(channel) else {
+        ctx.say("✅ Saved.").await?;
+        return Ok(());
+    };
+
+    ctx.say(format!("✅ Saved.\n{}", describe_starboard(&starboard)))
+        .await?;
+
+    Ok(())
+}
+
+/// Delete a starboard (moderator only)
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    rename = "delete",
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn starboard_delete(
+    ctx: PoiseContext<'_>,
+    #[description = "The channel the starboard posts in"] channel: ChannelId,
+) -> Result<()> {
+    if !Starboard::delete(channel).await? {
+        ctx.say("❌ That channel doesn't have a starboard.").await?;
+        return Ok(());
+    }
+
+    Starboard::reload().await?;
+
+    ctx.say(format!(
+        "✅ Deleted the starboard in {}. Its history is kept.",
+        channel.mention()
+    ))
+    .await?;
+
+    Ok(())
+}
@@ -1,7 +1,13 @@
-use crate::{data::PoiseContext, utils::GetRelativeTimestamp};
+use crate::{
+    TimeoutExt,
+    data::PoiseContext,
+    scheduled_actions::{ScheduledAction, schedule},
+    timeout_history::{TimeoutCause, TimeoutHistory},
+    utils::GetRelativeTimestamp,
+};
 use color_eyre::eyre::{ContextCompat, Result};
-use humantime::parse_duration;
-use poise::serenity_prelude::{EditMember, Mentionable};
+use humantime::{format_duration, parse_duration};
+use poise::serenity_prelude::{Mentionable, User};
 use std::time::Duration;
 
 #[poise::command(slash_command, ephemeral = true, guild_only)]
@@ -35,19 +41,12 @@ pub async fn timeout(
         return Ok(());
     }
 
-    let timeout_end = chrono::Utc::now() + time;
-
     let guild_id = ctx.guild_id().wrap_err("No guild ID?")?;
 
-    if guild_id
-        .edit_member(
-            ctx,
-            author.id,
-            EditMember::new().disable_communication_until(timeout_end.to_rfc3339()),
-        )
+    let Ok((_, timeout_end)) = guild_id
+        .timeout(ctx, &author.id, time, TimeoutCause::SelfTimeout, author.id)
         .await
-        .is_err()
-    {
+    else {
         ctx.say("Failed to time out! Ur too powerful :(").await?;
         return Ok(());
     };
@@ -68,28 +67,82 @@ pub async fn timeout(
         return Ok(());
     }
 
-    let reply_handle = ctx
-        .say(format!(
-            "{} has timed themselves out. They will return {}",
-            author.mention(),
-            // Snippet to get nick < global name < name
-            // guild_id
-            //     .member(ctx, author.id)
-            //     .await?
-            //     .nick
-            //     .unwrap_or_else(|| {
-            //         author
-            //             .global_name
-            //             .clone()
-            //             .unwrap_or_else(|| author.name.clone())
-            //     }),
-            timeout_end.discord_relative_timestamp(),
-        ))
-        .await?;
-
-    tokio::time::sleep(time - std::time::Duration::from_secs(1)).await;
-
-    reply_handle.delete(ctx).await.ok();
+    ctx.say(format!(
+        "{} has timed themselves out. They will return {}",
+        author.mention(),
+        // Snippet to get nick < global name < name
+        // guild_id
+        //     .member(ctx, author.id)
+        //     .await?
+        //     .nick
+        //     .unwrap_or_else(|| {
+        //         author
+        //             .global_name
+        //             .clone()
+        //             .unwrap_or_else(|| author.name.clone())
+        //     }),
+        timeout_end.discord_relative_timestamp(),
+    ))
+    .await?;
+
+    // The reply is ephemeral and can't be edited for long, so say when it's over by DM.
+    schedule(
+        ctx.serenity_context(),
+        timeout_end,
+        ScheduledAction::DirectMessage {
+            user_id: author.id,
+            content: format!(
+                "Your {} timeout is over. Welcome back!",
+                format_duration(time)
+            ),
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// See how often someone has been timed out, and why
+#[poise::command(slash_command, ephemeral = true, rename = "timeout_history")]
+pub async fn timeout_history(
+    ctx: PoiseContext<'_>,
+    #[description = "Whose history to show, yours by default"] user: Option<User>,
+) -> Result<()> {
+    let user = user.as_ref().unwrap_or_else(|| ctx.author());
+
+    let totals = TimeoutHistory::totals(user.id).await?;
+
+    if totals.is_empty() {
+        ctx.say(format!("{} has never been timed out.", user.mention()))
+            .await?;
+        return Ok(());
+    }
+
+    let mut message_text = format!("### Timeout history of {}\n", user.mention());
+
+    for total in &totals {
+        message_text.push_str(&format!(
+            "**{}**: {} time{}, {} in total\n",
+            total.cause.as_str(),
+            total.count,
+            if total.count == 1 { "" } else { "s" },
+            format_duration(total.duration),
+        ));
+    }
+
+    message_text.push_str("\n**Most recent:**\n");
+
+    for event in TimeoutHistory::recent(user.id, 10).await? {
+        message_text.push_str(&format!(
+            "{}: {} for {}, by {}\n",
+            event.start_time.discord_relative_timestamp(),
+            event.cause.as_str(),
+            format_duration(event.duration),
+            event.caused_by.mention(),
+        ));
+    }
+
+    ctx.say(message_text).await?;
 
     Ok(())
 }
@@ -1,87 +1,46 @@
+use crate::earnings::{EarningSettings, earn};
 use crate::economy::YeetLeaderboard;
+use crate::scheduled_actions::{ScheduledAction, schedule};
+use crate::starboard::Starboard;
+use crate::timeout_history::{TimeoutCause, TimeoutHistory};
+use crate::yeet_charges::{ChargeStatus, ChargeUse, YeetCharges};
+use crate::yeet_eligibility::{Refusal, YeetEligibility, check_daily_limit, check_tenure};
+use crate::yeet_parries::{ParryOutcome, ParryTiming, YeetParries};
+use crate::yeet_perks::{PerkGrant, Purchase, YeetPerk, YeetPerks};
+use crate::yeet_settings::{BallotKind, YeetSettings};
+use crate::yeet_stats::YeetStats;
+use crate::yeet_votes::{ActiveYeet, BallotVotes, YeetOutcome, YeetVotes};
 use crate::{MentionableExt, TimeoutExt, data::PoiseContext, utils::GetRelativeTimestamp};
 use bot_traits::ForwardRefToTracing;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use color_eyre::eyre::{OptionExt, Result, bail};
+use humantime::{format_duration, parse_duration};
 use itertools::Itertools;
-use parking_lot::Mutex;
 use poise::serenity_prelude::{
-    ChannelId, Context, CreateMessage, EditMessage, GuildId, Mentionable, Message, MessageBuilder,
-    MessageId, ReactionType, User, UserId,
+    ButtonStyle, ChannelId, ComponentInteraction, Context, CreateActionRow, CreateButton,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, GuildId,
+    Mentionable, Message, MessageBuilder, ReactionType, Role, RoleId, User, UserId,
 };
 use rand::RngExt;
-use rustc_hash::{FxHashMap, FxHashSet};
-use std::{
-    num::Saturating,
-    sync::{Arc, LazyLock},
-    time::{Duration, Instant},
-};
-use tokio::{
-    join,
-    time::{interval, sleep},
-};
-use tokio_stream::wrappers::IntervalStream;
-
-#[derive(Clone)]
-pub struct YeetContext {
-    yeeter: UserId,
-    victim: UserId,
-    guild_id: GuildId,
-    channel_id: ChannelId,
-    message_id: MessageId,
-    start_time: Instant,
-    is_yeet_amongus_easter_egg: bool,
-}
-
-impl YeetContext {
-    fn new(
-        yeeter: UserId,
-        victim: UserId,
-        guild_id: GuildId,
-        channel_id: ChannelId,
-        message_id: MessageId,
-        is_yeet_amongus_easter_egg: bool,
-    ) -> Arc<Self> {
-        Arc::new(Self {
-            yeeter,
-            victim,
-            guild_id,
-            channel_id,
-            message_id,
-            start_time: Instant::now(),
-            is_yeet_amongus_easter_egg,
-        })
-    }
-}
+use std::{sync::Arc, time::Duration};
+use tokio::{join, time::sleep};
 
-pub const YEET_DEFAULT_OPPORTUNITIES: Saturating<usize> = Saturating(3);
-pub const YEET_REQUIRED_REACTION_COUNT: u64 = 6;
 pub const YEET_NO_REACTION: char = '❌';
 pub const YEET_YES_REACTION: char = '✅';
-pub const YEET_DURATION_SECONDS: u64 = 300;
-pub const YEET_REFRESH_CHARGE_SECONDS: u64 = 3600;
-pub const YEET_VOTING_SECONDS: u64 = 90;
-pub const YEET_PARRY_SECONDS: u64 = 5;
-pub const YEET_PARRY_COOLDOWN_SECONDS: u64 = 60;
-
-pub(crate) static YEET_MAP: LazyLock<Mutex<FxHashMap<MessageId, Arc<YeetContext>>>> =
-    LazyLock::new(|| Mutex::new(FxHashMap::default()));
-pub(crate) static YEET_STARBOARD_EXCLUSIONS: LazyLock<Mutex<FxHashSet<MessageId>>> =
-    LazyLock::new(|| Mutex::new(FxHashSet::default()));
-pub(crate) static YEET_OPPORTUNITIES: LazyLock<Mutex<Saturating<usize>>> =
-    LazyLock::new(|| Mutex::new(YEET_DEFAULT_OPPORTUNITIES));
-pub(crate) static YEET_PARRY_MAP: LazyLock<Mutex<FxHashMap<UserId, (Instant, u64)>>> =
-    LazyLock::new(|| Mutex::new(FxHashMap::default()));
-
-fn has_yeet_opportunities() -> bool {
-    let mut yeet_opportunities = YEET_OPPORTUNITIES.lock();
-
-    if yeet_opportunities.0 > 0 {
-        *yeet_opportunities -= 1;
-        true
-    } else {
-        false
-    }
+const YEET_BALLOT_YAY_ID: &str = "yeet_ballot_yay";
+const YEET_BALLOT_NAY_ID: &str = "yeet_ballot_nay";
+/// Discord doesn't allow longer timeouts.
+const MAX_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24 * 28);
+/// The longest any other yeet setting can be, so the deadlines worked out from them stay within
+/// the dates chrono can represent.
+const MAX_SETTING: Duration = Duration::from_secs(60 * 60 * 24 * 365);
+/// The most a parry can scale a timeout by, in percent.
+const MAX_PARRY_PERCENT: u64 = 1000;
+
+/// `duration` scaled by `percent`, but never past what Discord allows.
+fn scale_timeout(duration: Duration, percent: u64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * percent as f64 / 100.0)
+        .map_or(MAX_TIMEOUT, |scaled| scaled.min(MAX_TIMEOUT))
 }
 
 fn create_yeet_message(
@@ -89,10 +48,11 @@ fn create_yeet_message(
     victim: &User,
     ctx: &Context,
     channel_id: ChannelId,
+    deadline: DateTime<Utc>,
     is_yeet_amongus_easter_egg: bool,
+    ballot: BallotKind,
 ) -> Result<CreateMessage> {
-    let time = (chrono::Utc::now() + Duration::from_secs(YEET_VOTING_SECONDS))
-        .discord_relative_timestamp();
+    let time = deadline.discord_relative_timestamp();
 
     let message_content = if is_yeet_amongus_easter_egg {
         let ctx = ctx.clone();
@@ -117,7 +77,8 @@ fn create_yeet_message(
             .push("Do you want to yeet ")
             .mention(victim)
             .push(format!(
-                "? ({YEET_REQUIRED_REACTION_COUNT} {YEET_YES_REACTION}'s needed)\n",
+                "? ({} {YEET_YES_REACTION}'s needed)\n",
+                YeetSettings::current().required_count
             ))
             .push(format!(
                 "Or, vote {YEET_NO_REACTION} to yeet the author: ||"
@@ -129,9 +90,12 @@ fn create_yeet_message(
             .build()
     };
 
-    Ok(CreateMessage::new()
-        .content(message_content)
-        .reactions([YEET_YES_REACTION, YEET_NO_REACTION]))
+    let message = CreateMessage::new().content(message_content);
+
+    Ok(match ballot {
+        BallotKind::Reactions => message.reactions([YEET_YES_REACTION, YEET_NO_REACTION]),
+        BallotKind::Buttons => message.components(ballot_buttons(&BallotVotes::default())),
+    })
 }
 
 async fn meeting_message(ctx: Context, channel_id: ChannelId) -> Result<()> {
@@ -144,28 +108,130 @@ async fn meeting_message(ctx: Context, channel_id: ChannelId) -> Result<()> {
         )
         .await?;
 
-    tokio::spawn(async move {
-        sleep(Duration::from_secs(5)).await;
-        message.delete(ctx).await.trace_err_ok();
-    });
+    schedule(
+        &ctx,
+        Utc::now() + TimeDelta::seconds(5),
+        ScheduledAction::DeleteMessage {
+            channel_id,
+            message_id: message.id,
+        },
+    )
+    .await?;
 
     Ok(())
 }
 
-pub async fn can_yeet(ctx: PoiseContext<'_>, victim: &User, guild_id: GuildId) -> Result<bool> {
-    let react_role_id = ctx.data().config.read().await.ids.bot_react_role_id;
+/// Checks whether the author may yeet `victim`, spending one of their charges if so.
+pub async fn can_yeet(
+    ctx: PoiseContext<'_>,
+    victim: &User,
+    guild_id: GuildId,
+) -> Result<Option<ChargeStatus>> {
+    if let Some(refusal) = eligibility_refusal(ctx, victim, guild_id).await? {
+        ctx.say(refusal.message(victim)).await?;
+        return Ok(None);
+    }
+
+    let settings = YeetSettings::current();
+
+    match YeetCharges::try_use(
+        ctx.author().id,
+        settings.opportunities,
+        settings.refresh_charge_seconds,
+    )
+    .await?
+    {
+        ChargeUse::Used(status) => Ok(Some(status)),
+        ChargeUse::Empty(status) => {
+            ctx.say(format!(
+                "You're out of yeets! {}",
+                describe_next_charge(&status)
+            ))
+            .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// The first eligibility rule that keeps `victim` from being yeeted by the author, if any.
+async fn eligibility_refusal(
+    ctx: PoiseContext<'_>,
+    victim: &User,
+    guild_id: GuildId,
+) -> Result<Option<Refusal>> {
+    let react_role_id = RoleId::new(ctx.data().config.read().await.ids.bot_react_role_id);
+    let settings = YeetSettings::current();
+    let now = Utc::now();
 
-    if !victim.has_role(ctx, guild_id, react_role_id).await? {
-        ctx.say("You can't yeet a non reactme user!").await?;
-        return Ok(false);
+    if victim.id == ctx.author().id {
+        return Ok(Some(Refusal::SelfYeet));
     }
 
-    if !has_yeet_opportunities() {
-        ctx.say("No more yeet opportunities available").await?;
-        return Ok(false);
+    let member = guild_id.member(ctx, victim.id).await?;
+
+    if !member.roles.contains(&react_role_id) {
+        return Ok(Some(Refusal::NotReactMe));
     }
 
-    Ok(true)
+    if let Some(role_id) = YeetEligibility::exempt_roles()
+        .await?
+        .into_iter()
+        .find(|role_id| member.roles.contains(role_id))
+    {
+        return Ok(Some(Refusal::ExemptRole(role_id)));
+    }
+
+    if let Some(refusal) = check_tenure(
+        member.joined_at.map(|joined_at| *joined_at),
+        settings.min_tenure_seconds,
+        now,
+    ) {
+        return Ok(Some(refusal));
+    }
+
+    if let Some(until) = YeetPerks::immune_until(victim.id).await? {
+        return Ok(Some(Refusal::BoughtImmunity { until }));
+    }
+
+    if !settings.victim_immunity_seconds.is_zero()
+        && let Some(last_yeet) = TimeoutHistory::last(victim.id, TimeoutCause::Yeet).await?
+    {
+        let until = TimeDelta::from_std(
+            last_yeet
+                .duration
+                .saturating_add(settings.victim_immunity_seconds),
+        )
+        .ok()
+        .and_then(|protected| last_yeet.start_time.checked_add_signed(protected))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC);
+
+        if until > now {
+            return Ok(Some(Refusal::RecentlyYeeted { until }));
+        }
+    }
+
+    if settings.max_daily_targeted > 0 {
+        let targeted_at =
+            YeetEligibility::targeted_since(victim.id, now - TimeDelta::days(1)).await?;
+
+        return Ok(check_daily_limit(
+            &targeted_at,
+            settings.max_daily_targeted,
+            now,
+        ));
+    }
+
+    Ok(None)
+}
+
+fn describe_next_charge(status: &ChargeStatus) -> String {
+    match status.next_charge_at {
+        Some(next_charge_at) => format!(
+            "Your next one arrives {}.",
+            next_charge_at.discord_relative_timestamp()
+        ),
+        None => String::from("You're fully charged."),
+    }
 }
 
 /// Yeet a user if you get 6 yay votes, get yeeted yourself if they vote nay
@@ -175,18 +241,25 @@ pub async fn yeet(ctx: PoiseContext<'_>, victim: User) -> Result<()> {
     let guild_id = ctx.guild().ok_or_eyre("Couldn't get guild")?.id;
     let channel_id = ctx.channel_id();
 
-    if !can_yeet(ctx, &victim, guild_id).await? {
+    let Some(charges) = can_yeet(ctx, &victim, guild_id).await? else {
         return Ok(());
-    }
+    };
 
+    let settings = YeetSettings::current();
     let is_yeet_amongus_easter_egg = rand::rng().random_bool(0.02);
+    let deadline = TimeDelta::from_std(settings.voting_seconds)
+        .ok()
+        .and_then(|voting| Utc::now().checked_add_signed(voting))
+        .ok_or_eyre("The voting time is too long")?;
 
     let msg = create_yeet_message(
         yeeter,
         &victim,
         ctx.serenity_context(),
         channel_id,
+        deadline,
         is_yeet_amongus_easter_egg,
+        settings.ballot,
     )?;
 
     let Ok(msg) = channel_id.send_message(ctx, msg).await else {
@@ -194,40 +267,115 @@ pub async fn yeet(ctx: PoiseContext<'_>, victim: User) -> Result<()> {
         bail!("Couldn't send message announcing yeeting");
     };
 
-    YEET_MAP.lock().insert(
+    let yeet_context = match YeetVotes::start(
+        yeeter,
+        &victim,
+        guild_id,
+        channel_id,
         msg.id,
-        YeetContext::new(
-            yeeter.id,
-            victim.id,
-            guild_id,
-            channel_id,
-            msg.id,
-            is_yeet_amongus_easter_egg,
-        ),
-    );
+        deadline,
+        is_yeet_amongus_easter_egg,
+        settings.ballot,
+    )
+    .await
+    {
+        Ok(yeet_context) => yeet_context,
+        Err(error) => {
+            msg.delete(ctx).await.trace_err_ok();
+            ctx.say("Couldn't start the yeet vote").await?;
+            return Err(error);
+        }
+    };
 
-    YEET_STARBOARD_EXCLUSIONS.lock().insert(msg.id);
+    Starboard::ignore_message_permanently(msg.id)
+        .await
+        .trace_err_ok();
 
-    ctx.say("Yeeting started!").await?;
+    schedule_expiry(ctx.serenity_context().clone(), yeet_context);
 
-    sleep(Duration::from_secs(YEET_VOTING_SECONDS)).await;
+    ctx.say(format!(
+        "Yeeting started! You have {} yeet{} left. {}",
+        charges.charges,
+        if charges.charges == 1 { "" } else { "s" },
+        describe_next_charge(&charges)
+    ))
+    .await?;
 
-    if YEET_MAP.lock().remove(&msg.id).is_some() {
-        tokio::spawn({
-            let ctx = ctx.serenity_context().clone();
-            async move { msg.delete(ctx).await }
-        });
+    Ok(())
+}
 
-        if is_yeet_amongus_easter_egg {
-            easter_egg_failure(ctx.serenity_context(), channel_id)
-                .await
-                .ok();
+/// Deletes the ballot once its deadline passes, unless the vote was decided first.
+fn schedule_expiry(ctx: Context, yeet_context: ActiveYeet) {
+    tokio::spawn(async move {
+        let remaining = (yeet_context.deadline - Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        sleep(remaining).await;
+
+        expire_yeet(&ctx, &yeet_context).await.trace_err_ok();
+    });
+}
+
+async fn expire_yeet(ctx: &Context, yeet_context: &ActiveYeet) -> Result<()> {
+    if !YeetVotes::expire(&yeet_context.id).await? {
+        return Ok(());
+    }
+
+    yeet_context
+        .channel_id
+        .delete_message(ctx, yeet_context.message_id)
+        .await
+        .ok();
+
+    if yeet_context.is_yeet_amongus_easter_egg {
+        easter_egg_failure(ctx, yeet_context.channel_id).await.ok();
+    }
+
+    Ok(())
+}
+
+/// Picks up the yeet votes that were still open when the bot last stopped.
+///
+/// Overdue ballots are cleaned up, the rest are recounted in case they passed
+/// while nobody was listening and otherwise expire on their original deadline.
+pub async fn resume_pending_yeets(ctx: Context) -> Result<()> {
+    for yeet_context in YeetVotes::pending().await? {
+        if yeet_context.deadline <= Utc::now() {
+            expire_yeet(&ctx, &yeet_context).await.trace_err_ok();
+            continue;
+        }
+
+        match yeet_context
+            .channel_id
+            .message(&ctx, yeet_context.message_id)
+            .await
+        {
+            Ok(message) => {
+                schedule_expiry(ctx.clone(), yeet_context.clone());
+                recount_ballot(&ctx, yeet_context, &message)
+                    .await
+                    .trace_err_ok();
+            }
+            Err(_) => {
+                expire_yeet(&ctx, &yeet_context).await.trace_err_ok();
+            }
         }
     }
 
     Ok(())
 }
 
+/// Resolves a ballot in case it passed while the bot wasn't listening.
+async fn recount_ballot(ctx: &Context, yeet_context: ActiveYeet, message: &Message) -> Result<()> {
+    match yeet_context.ballot {
+        BallotKind::Reactions => handle_yeeting(ctx, message).await,
+        BallotKind::Buttons => {
+            let votes = YeetVotes::votes(&yeet_context.id).await?;
+            resolve_button_ballot(ctx, yeet_context, votes).await
+        }
+    }
+}
+
 async fn easter_egg_failure(ctx: &Context, channel_id: ChannelId) -> Result<()> {
     channel_id
     .send_message(
@@ -241,22 +389,9 @@ async fn easter_egg_failure(ctx: &Context, channel_id: ChannelId) -> Result<()>
     Ok(())
 }
 
-pub async fn update_interval() {
-    use futures::StreamExt;
-
-    // Every 1 hour, add a yeet opportunity up to the default, tokio interval
-    IntervalStream::new(interval(Duration::from_secs(YEET_REFRESH_CHARGE_SECONDS)))
-        .for_each(|_| async {
-            let mut yeet_opportunities = YEET_OPPORTUNITIES.lock();
-            *yeet_opportunities += 1;
-            *yeet_opportunities = yeet_opportunities.min(YEET_DEFAULT_OPPORTUNITIES);
-        })
-        .await
-}
-
 async fn get_unique_non_kingfisher_voters(
     ctx: Context,
-    yeet_context: &YeetContext,
+    yeet_context: &ActiveYeet,
     reaction: impl Into<ReactionType>,
 ) -> Result<Arc<[UserId]>> {
     let kingfisher_id = ctx.cache.current_user().id;
@@ -273,7 +408,7 @@ async fn get_unique_non_kingfisher_voters(
 
 async fn fail_to_yeet_after_vote(
     ctx: Context,
-    yeet_context: &YeetContext,
+    yeet_context: &ActiveYeet,
     shooters: &[UserId],
     target: &UserId,
 ) -> Result<()> {
@@ -298,7 +433,7 @@ async fn fail_to_yeet_after_vote(
 
 async fn successful_yeet(
     ctx: Context,
-    yeet_context: &YeetContext,
+    yeet_context: &ActiveYeet,
     shooters: &[UserId],
     target: &UserId,
     duration: Duration,
@@ -321,7 +456,7 @@ async fn successful_yeet(
         "yeeted"
     };
 
-    let mut message_handle = yeet_context
+    let announcement = yeet_context
         .channel_id
         .send_message(
             &ctx,
@@ -337,26 +472,28 @@ async fn successful_yeet(
         )
         .await?;
 
-    tokio::time::sleep(Duration::from_secs(YEET_DURATION_SECONDS - 1)).await;
-
-    message_handle
-        .edit(
-            ctx,
-            EditMessage::new().content(format!(
+    schedule(
+        &ctx,
+        timeout_end,
+        ScheduledAction::EditMessage {
+            channel_id: announcement.channel_id,
+            message_id: announcement.id,
+            content: format!(
                 "User {} was {} in {}.{} seconds\nBrought to you by: {}",
                 target.mention(),
                 verb,
                 duration.as_secs(),
                 duration.subsec_millis(),
                 shooters.mention_all()
-            )),
-        )
-        .await?;
+            ),
+        },
+    )
+    .await?;
 
     Ok(())
 }
 
-fn should_yeet_someone(message: &Message) -> Option<(Arc<YeetContext>, bool, bool)> {
+fn should_yeet_someone(message: &Message) -> Option<(bool, bool)> {
     let mut did_yay = 0;
     let mut did_nay = 0;
 
@@ -374,30 +511,29 @@ fn should_yeet_someone(message: &Message) -> Option<(Arc<YeetContext>, bool, boo
         }
     }
 
-    let did_yay = did_yay >= YEET_REQUIRED_REACTION_COUNT;
-    let did_nay = did_nay >= YEET_REQUIRED_REACTION_COUNT;
+    let required_count = YeetSettings::current().required_count;
+    let did_yay = did_yay >= required_count;
+    let did_nay = did_nay >= required_count;
 
-    (did_yay || did_nay)
-        .then(|| YEET_MAP.lock().remove(&message.id))
-        .flatten()
-        .map(|data| (data, did_yay, did_nay))
+    (did_yay || did_nay).then_some((did_yay, did_nay))
 }
 
 // Handle a reaction
 pub async fn handle_yeeting(ctx: &Context, message: &Message) -> Result<()> {
-    let Some((yeet_context, did_yay, did_nay)) = should_yeet_someone(message) else {
+    let Some((did_yay, did_nay)) = should_yeet_someone(message) else {
         return Ok(());
     };
 
-    let duration = yeet_context.start_time.elapsed();
-    let current_instant = Instant::now();
-    let parried = YEET_PARRY_MAP
-        .lock()
-        .remove(&yeet_context.victim)
-        .map(|(parry_time, attempts)| {
-            (current_instant - parry_time).as_secs() < YEET_PARRY_SECONDS && attempts == 0
-        })
-        .unwrap_or(false);
+    let Some(yeet_context) = YeetVotes::find_open(message.id).await? else {
+        return Ok(());
+    };
+
+    if yeet_context.ballot != BallotKind::Reactions || !YeetVotes::claim(&yeet_context.id).await? {
+        return Ok(());
+    }
+
+    let decided_at = Utc::now();
+    let yeet_context = Arc::new(yeet_context);
 
     // This are costly api calls.
 
@@ -427,12 +563,137 @@ pub async fn handle_yeeting(ctx: &Context, message: &Message) -> Result<()> {
         return Ok(());
     };
 
-    let parried = !yay.iter().any(|user| *user == yeet_context.victim) && parried;
+    resolve_yeet(ctx, yeet_context, did_yay, did_nay, decided_at, yay, nay).await
+}
+
+fn ballot_buttons(votes: &BallotVotes) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(YEET_BALLOT_YAY_ID)
+            .emoji(YEET_YES_REACTION)
+            .label(votes.yay.len().to_string())
+            .style(ButtonStyle::Success),
+        CreateButton::new(YEET_BALLOT_NAY_ID)
+            .emoji(YEET_NO_REACTION)
+            .label(votes.nay.len().to_string())
+            .style(ButtonStyle::Danger),
+    ])]
+}
+
+// Handle a click on a button ballot
+pub async fn handle_yeet_ballot_click(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+) -> Result<()> {
+    let yay = match interaction.data.custom_id.as_str() {
+        YEET_BALLOT_YAY_ID => true,
+        YEET_BALLOT_NAY_ID => false,
+        _ => return Ok(()),
+    };
+
+    let respond_ephemeral = |content: &str| {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .ephemeral(true),
+        )
+    };
+
+    let Some(yeet_context) = YeetVotes::find_open(interaction.message.id).await? else {
+        interaction
+            .create_response(ctx, respond_ephemeral("This vote is already over."))
+            .await?;
+        return Ok(());
+    };
+
+    let voter = interaction.user.id;
+
+    if YeetSettings::current().exclude_participants
+        && (voter == yeet_context.yeeter || voter == yeet_context.victim)
+    {
+        interaction
+            .create_response(
+                ctx,
+                respond_ephemeral("You can't vote on a yeet you're part of."),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    YeetVotes::cast(&yeet_context.id, voter, yay).await?;
+    let votes = YeetVotes::votes(&yeet_context.id).await?;
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().components(ballot_buttons(&votes)),
+            ),
+        )
+        .await?;
+
+    resolve_button_ballot(ctx, yeet_context, votes).await
+}
+
+async fn resolve_button_ballot(
+    ctx: &Context,
+    yeet_context: ActiveYeet,
+    votes: BallotVotes,
+) -> Result<()> {
+    let required_count = YeetSettings::current().required_count as usize;
+    let did_yay = votes.yay.len() >= required_count;
+    let did_nay = votes.nay.len() >= required_count;
+
+    if !(did_yay || did_nay) || !YeetVotes::claim(&yeet_context.id).await? {
+        return Ok(());
+    }
+
+    resolve_yeet(
+        ctx,
+        Arc::new(yeet_context),
+        did_yay,
+        did_nay,
+        Utc::now(),
+        votes.yay.into(),
+        votes.nay.into(),
+    )
+    .await
+}
+
+/// Times out whoever a decided vote went against, whichever kind of ballot it was.
+async fn resolve_yeet(
+    ctx: &Context,
+    yeet_context: Arc<ActiveYeet>,
+    did_yay: bool,
+    did_nay: bool,
+    decided_at: DateTime<Utc>,
+    yay: Arc<[UserId]>,
+    nay: Arc<[UserId]>,
+) -> Result<()> {
+    let settings = YeetSettings::current();
+    let duration = (Utc::now() - yeet_context.start_time)
+        .to_std()
+        .unwrap_or_default();
+
+    // Voting for your own yeet gives up the parry.
+    let parry = if did_yay && !did_nay && !yay.contains(&yeet_context.victim) {
+        YeetParries::use_on(
+            &yeet_context.id,
+            yeet_context.victim,
+            decided_at,
+            ParryTiming::from(&settings),
+        )
+        .await
+        .trace_err_ok()
+        .flatten()
+    } else {
+        None
+    };
+    let mut timeout_duration = settings.duration_seconds;
 
     // Delete the voting message
-    let channel_id = message.channel_id;
+    let channel_id = yeet_context.channel_id;
     let guild_id = yeet_context.guild_id;
-    let message_id = message.id;
+    let message_id = yeet_context.message_id;
     tokio::spawn({
         let ctx = ctx.clone();
         async move {
@@ -440,7 +701,7 @@ pub async fn handle_yeeting(ctx: &Context, message: &Message) -> Result<()> {
         }
     });
 
-    let (targets, shooters): (&[UserId], Arc<[UserId]>) = match (did_yay, did_nay, parried) {
+    let (targets, shooters): (&[UserId], Arc<[UserId]>) = match (did_yay, did_nay, parry) {
         (true, true, _) => {
             tokio::spawn({
                 let ctx = ctx.clone();
@@ -460,8 +721,41 @@ pub async fn handle_yeeting(ctx: &Context, message: &Message) -> Result<()> {
                 yay.iter().chain(nay.iter()).unique().cloned().collect(),
             )
         }
-        (true, false, false) => (&[yeet_context.victim], yay),
-        (true, false, true) => {
+        (true, false, None) => (&[yeet_context.victim], yay),
+        (true, false, Some(ParryOutcome::Partial | ParryOutcome::Failed)) => {
+            let (percent, message) = if parry == Some(ParryOutcome::Partial) {
+                (
+                    settings.partial_parry_percent,
+                    "parried a little late, so the yeet only grazed them",
+                )
+            } else {
+                (
+                    settings.failed_parry_percent,
+                    "parried too often and fumbled it, so the yeet hits harder",
+                )
+            };
+            timeout_duration = scale_timeout(timeout_duration, percent);
+
+            tokio::spawn({
+                let ctx = ctx.clone();
+                let victim = yeet_context.victim.mention();
+                async move {
+                    channel_id
+                        .send_message(
+                            ctx,
+                            CreateMessage::new().content(format!(
+                                "{victim} {message}! They're out for {}.",
+                                format_duration(timeout_duration)
+                            )),
+                        )
+                        .await
+                        .ok();
+                }
+            });
+
+            (&[yeet_context.victim], yay)
+        }
+        (true, false, Some(ParryOutcome::Reflected)) => {
             tokio::spawn({
                 let ctx = ctx.clone();
                 let victim = yeet_context.victim.mention();
@@ -494,17 +788,53 @@ pub async fn handle_yeeting(ctx: &Context, message: &Message) -> Result<()> {
         }
     };
 
+    let outcome = match (did_yay, did_nay, parry) {
+        (true, true, _) => YeetOutcome::Both,
+        (true, false, Some(ParryOutcome::Reflected)) => YeetOutcome::Parried,
+        (true, false, _) => YeetOutcome::Yeeted,
+        (false, _, _) => YeetOutcome::Backfired,
+    };
+    YeetVotes::set_outcome(&yeet_context.id, outcome)
+        .await
+        .trace_err_ok();
+
     save_to_yeet_leaderboard(targets).await.trace_err_ok();
 
+    let earnings = EarningSettings::current();
+    let earned = match outcome {
+        YeetOutcome::Yeeted => Some((yeet_context.yeeter, earnings.yeet_won, "Won a yeet vote")),
+        YeetOutcome::Backfired | YeetOutcome::Parried => Some((
+            yeet_context.victim,
+            earnings.yeet_survived,
+            "Survived a yeet vote",
+        )),
+        YeetOutcome::Both => None,
+    };
+    if let Some((user_id, amount, reason)) = earned {
+        earn(user_id, amount, reason).await.trace_err_ok();
+    }
+
     for &target in targets {
         let shooters = shooters.clone();
+        // Whoever the vote turned against was timed out because of the other side.
+        let caused_by = if target == yeet_context.victim {
+            yeet_context.yeeter
+        } else {
+            yeet_context.victim
+        };
 
         tokio::spawn({
             let ctx = ctx.clone();
             let yeet_context = yeet_context.clone();
             async move {
                 match guild_id
-                    .timeout(&ctx, &target, Duration::from_secs(YEET_DURATION_SECONDS))
+                    .timeout(
+                        &ctx,
+                        &target,
+                        timeout_duration,
+                        TimeoutCause::Yeet,
+                        caused_by,
+                    )
                     .await
                 {
                     Ok((_, timeout_end)) => successful_yeet(
@@ -551,27 +881,407 @@ pub async fn yeet_leaderboard(ctx: PoiseContext<'_>) -> Result<()> {
     Ok(())
 }
 
-/// Parry a yeet for 5 seconds.
-/// If you do it more than once a minute, it will fail :)
+fn percent(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        String::from("-")
+    } else {
+        format!("{:.0}%", part as f64 * 100.0 / whole as f64)
+    }
+}
+
+/// See who yeets whom, and how it tends to go
+#[poise::command(slash_command, rename = "yeet_stats", ephemeral = true)]
+pub async fn yeet_stats(
+    ctx: PoiseContext<'_>,
+    #[description = "Whose stats to show, everyone's by default"] user: Option<User>,
+) -> Result<()> {
+    let stats = YeetStats::load().await?;
+
+    let message_text = match user {
+        Some(user) => {
+            let as_yeeter = stats.as_yeeter(user.id);
+            let as_victim = stats.as_victim(user.id);
+
+            let mut message_text = format!(
+                "### Yeet stats for {}\n\
+                 **As yeeter:** {} started, {} landed ({}), {} turned around ({}), {} expired\n\
+                 **As victim:** targeted {} times, yeeted {} times, parried {} of {} ({})\n",
+                user.mention(),
+                as_yeeter.started,
+                as_yeeter.landed(),
+                percent(as_yeeter.landed(), as_yeeter.started),
+                as_yeeter.turned_around(),
+                percent(as_yeeter.turned_around(), as_yeeter.started),
+                as_yeeter.expired(),
+                as_victim.started,
+                as_victim.landed(),
+                as_victim.parried,
+                as_victim.voted_against_victim(),
+                percent(as_victim.parried, as_victim.voted_against_victim()),
+            );
+
+            if let Some((victim, count)) = stats.favourite_target(user.id) {
+                message_text.push_str(&format!(
+                    "**Favourite target:** {} ({count} times)\n",
+                    victim.mention()
+                ));
+            }
+
+            message_text
+        }
+        None => {
+            let total = stats.total();
+            let mut message_text = String::from("### Yeet stats\n**Top yeeters:**\n");
+
+            for (user_id, tally) in stats.top_yeeters(5) {
+                message_text.push_str(&format!(
+                    "- {}: {} ({} landed)\n",
+                    user_id.mention(),
+                    tally.started,
+                    percent(tally.landed(), tally.started)
+                ));
+            }
+
+            message_text.push_str("**Most yeeted:**\n");
+
+            for (user_id, tally) in stats.most_yeeted(5) {
+                message_text.push_str(&format!("- {}: {}\n", user_id.mention(), tally.landed()));
+            }
+
+            message_text.push_str("**Who yeets whom:**\n");
+
+            for (yeeter, victim, count) in stats.top_pairs(5) {
+                message_text.push_str(&format!(
+                    "- {} → {}: {count}\n",
+                    yeeter.mention(),
+                    victim.mention()
+                ));
+            }
+
+            message_text.push_str(&format!(
+                "**Parries:** {} of {} yeets that passed were parried ({})\n\
+                 **Backfires:** {} of {} yeets ({})",
+                total.parried,
+                total.voted_against_victim(),
+                percent(total.parried, total.voted_against_victim()),
+                total.backfired,
+                total.started,
+                percent(total.backfired, total.started),
+            ));
+
+            message_text
+        }
+    };
+
+    ctx.say(message_text).await?;
+
+    Ok(())
+}
+
+/// Parry a yeet. Time it well to reflect it.
+/// If you do it again too soon, it will fail :)
 #[poise::command(slash_command, ephemeral = true)]
 pub async fn parry(ctx: PoiseContext<'_>) -> Result<()> {
-    let user = ctx.author();
-
-    YEET_PARRY_MAP
-        .lock()
-        .entry(user.id)
-        .and_modify(|(last_time, attempts)| {
-            if last_time.elapsed() < Duration::from_secs(YEET_PARRY_COOLDOWN_SECONDS) {
-                *attempts += 1;
-            } else {
-                *last_time = Instant::now();
-                *attempts = 0;
+    let settings = YeetSettings::current();
+
+    YeetParries::attempt(ctx.author()).await?;
+
+    ctx.say(format!(
+        "You're now parrying for the next {}",
+        format_duration(settings.parry_seconds)
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Spend bank balance on yeet immunity or an extra yeet
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn yeet_buy(
+    ctx: PoiseContext<'_>,
+    #[description = "What to buy"] perk: YeetPerk,
+) -> Result<()> {
+    let settings = YeetSettings::current();
+
+    let message = match YeetPerks::buy(ctx.author().id, perk, &settings).await? {
+        Purchase::TooPoor { price, balance } => {
+            format!("That costs {price}, but you only have {balance}.")
+        }
+        Purchase::Frozen => String::from("Your account is frozen."),
+        Purchase::Bought {
+            grant: PerkGrant::ImmuneUntil(until),
+            balance,
+        } => format!(
+            "Nobody can yeet you until {}. Your new balance is {balance}.",
+            until.discord_relative_timestamp()
+        ),
+        Purchase::Bought {
+            grant: PerkGrant::Charges(status),
+            balance,
+        } => format!(
+            "You now have {} yeets. Your new balance is {balance}.",
+            status.charges
+        ),
+    };
+
+    ctx.say(message).await?;
+
+    Ok(())
+}
+
+/// View or change how yeeting works (moderator only)
+#[allow(clippy::too_many_arguments)]
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    guild_only,
+    rename = "yeet_settings",
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn yeet_settings(
+    ctx: PoiseContext<'_>,
+    #[description = "How many yeets each user can save up"] opportunities: Option<u64>,
+    #[description = "How many yays or nays decide a vote"] required_count: Option<u64>,
+    #[description = "How long the yeeted are timed out, like '5m'"] duration: Option<String>,
+    #[description = "How long until a charge comes back, like '1h'"] refresh_charge: Option<String>,
+    #[description = "How long a vote stays open, like '90s'"] voting: Option<String>,
+    #[description = "How long a parry protects, like '5s'"] parry: Option<String>,
+    #[description = "Parrying again this soon fails, like '1m'"] parry_cooldown: Option<String>,
+    #[description = "A parry this close to the vote reflects it, like '2s'"] parry_reflect: Option<
+        String,
+    >,
+    #[description = "% of the timeout a late parry leaves"] partial_parry_percent: Option<u64>,
+    #[description = "% of the timeout a fumbled parry gives"] failed_parry_percent: Option<u64>,
+    #[description = "Post-yeet protection, like '30m', or 'off'"] victim_immunity: Option<String>,
+    #[description = "How new yeets are voted on"] ballot: Option<BallotKind>,
+    #[description = "Keep the yeeter and victim from voting on buttons"]
+    exclude_participants: Option<bool>,
+    #[description = "Bank price of yeet immunity"] immunity_price: Option<u64>,
+    #[description = "How long bought immunity lasts, like '1h'"] immunity: Option<String>,
+    #[description = "Bank price of an extra yeet"] extra_charge_price: Option<u64>,
+    #[description = "How long members must be here first, like '7d', or 'off'"] min_tenure: Option<
+        String,
+    >,
+    #[description = "How often one victim can be targeted a day, 0 for no limit"]
+    max_daily_targeted: Option<u64>,
+    #[description = "Exempt this role from yeets, or lift its exemption"] exempt_role: Option<Role>,
+) -> Result<()> {
+    let mut settings = YeetSettings::current();
+    let mut changed = false;
+
+    if let Some(opportunities) = opportunities {
+        settings.opportunities = opportunities;
+        changed = true;
+    }
+
+    if let Some(required_count) = required_count {
+        if required_count == 0 {
+            ctx.say("❌ A vote needs at least one reaction.").await?;
+            return Ok(());
+        }
+
+        settings.required_count = required_count;
+        changed = true;
+    }
+
+    for (input, setting) in [
+        (duration, &mut settings.duration_seconds),
+        (refresh_charge, &mut settings.refresh_charge_seconds),
+        (voting, &mut settings.voting_seconds),
+        (parry, &mut settings.parry_seconds),
+        (parry_cooldown, &mut settings.parry_cooldown_seconds),
+        (parry_reflect, &mut settings.parry_reflect_seconds),
+        (immunity, &mut settings.immunity_seconds),
+    ] {
+        let Some(input) = input else {
+            continue;
+        };
+
+        match parse_duration(input.trim()) {
+            Ok(value) if !value.is_zero() => {
+                *setting = value;
+                changed = true;
             }
-        })
-        .or_insert((Instant::now(), 0));
+            _ => {
+                ctx.say("❌ Invalid time format! Say something like '90s' or '5m'")
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
 
-    ctx.say("You're now parrying for the next 5 seconds")
+    if settings.duration_seconds > MAX_TIMEOUT {
+        ctx.say("❌ Discord doesn't let you time out for more than 28 days.")
+            .await?;
+        return Ok(());
+    }
+
+    if partial_parry_percent.is_some_and(|percent| percent > 100)
+        || failed_parry_percent.is_some_and(|percent| percent > MAX_PARRY_PERCENT)
+    {
+        ctx.say(format!(
+            "❌ A late parry can leave at most 100% of the timeout, and a fumbled one can give \
+             at most {MAX_PARRY_PERCENT}%."
+        ))
         .await?;
+        return Ok(());
+    }
+
+    for (input, setting) in [
+        (immunity_price, &mut settings.immunity_price),
+        (extra_charge_price, &mut settings.extra_charge_price),
+        (partial_parry_percent, &mut settings.partial_parry_percent),
+        (failed_parry_percent, &mut settings.failed_parry_percent),
+        (max_daily_targeted, &mut settings.max_daily_targeted),
+    ] {
+        if let Some(input) = input {
+            *setting = input;
+            changed = true;
+        }
+    }
+
+    for (input, setting) in [
+        (victim_immunity, &mut settings.victim_immunity_seconds),
+        (min_tenure, &mut settings.min_tenure_seconds),
+    ] {
+        let Some(input) = input else {
+            continue;
+        };
+
+        match input.trim() {
+            "off" => *setting = Duration::ZERO,
+            input => {
+                let Ok(value) = parse_duration(input) else {
+                    ctx.say("❌ Invalid time format! Say something like '30m' or 'off'")
+                        .await?;
+                    return Ok(());
+                };
+
+                *setting = value;
+            }
+        }
+
+        changed = true;
+    }
+
+    if [
+        settings.refresh_charge_seconds,
+        settings.voting_seconds,
+        settings.parry_seconds,
+        settings.parry_cooldown_seconds,
+        settings.parry_reflect_seconds,
+        settings.victim_immunity_seconds,
+        settings.immunity_seconds,
+        settings.min_tenure_seconds,
+    ]
+    .into_iter()
+    .any(|setting| setting > MAX_SETTING)
+    {
+        ctx.say("❌ Yeet settings can't be longer than a year.")
+            .await?;
+        return Ok(());
+    }
+
+    if let Some(role) = exempt_role {
+        YeetEligibility::toggle_exempt_role(role.id).await?;
+        changed = true;
+    }
+
+    if let Some(ballot) = ballot {
+        settings.ballot = ballot;
+        changed = true;
+    }
+
+    if let Some(exclude_participants) = exclude_participants {
+        settings.exclude_participants = exclude_participants;
+        changed = true;
+    }
+
+    if changed {
+        settings.save().await?;
+        YeetSettings::reload().await?;
+    }
+
+    let exempt_roles = YeetEligibility::exempt_roles().await?;
+
+    ctx.say(format!(
+        "### Yeet settings{}\n\
+         Opportunities: {}\n\
+         Required votes: {}\n\
+         Timeout: {}\n\
+         Charge refresh: {}\n\
+         Voting time: {}\n\
+         Parry window: {}\n\
+         Parry cooldown: {}\n\
+         Parry reflect window: {}\n\
+         Late parry timeout: {}%\n\
+         Fumbled parry timeout: {}%\n\
+         Victim immunity: {}\n\
+         Ballot: {}{}\n\
+         Immunity: {} for {}\n\
+         Extra yeet: {}\n\
+         Minimum tenure: {}\n\
+         Max targeted per day: {}\n\
+         Exempt roles: {}",
+        if changed { " (updated)" } else { "" },
+        settings.opportunities,
+        settings.required_count,
+        format_duration(settings.duration_seconds),
+        format_duration(settings.refresh_charge_seconds),
+        format_duration(settings.voting_seconds),
+        format_duration(settings.parry_seconds),
+        format_duration(settings.parry_cooldown_seconds),
+        format_duration(settings.parry_reflect_seconds),
+        settings.partial_parry_percent,
+        settings.failed_parry_percent,
+        duration_or_off(settings.victim_immunity_seconds),
+        poise::ChoiceParameter::name(&settings.ballot),
+        if settings.exclude_participants {
+            " (yeeter and victim can't vote)"
+        } else {
+            ""
+        },
+        settings.immunity_price,
+        format_duration(settings.immunity_seconds),
+        settings.extra_charge_price,
+        duration_or_off(settings.min_tenure_seconds),
+        match settings.max_daily_targeted {
+            0 => String::from("no limit"),
+            max => max.to_string(),
+        },
+        if exempt_roles.is_empty() {
+            String::from("none")
+        } else {
+            exempt_roles
+                .iter()
+                .map(|role_id| role_id.mention())
+                .join(" ")
+        },
+    ))
+    .await?;
 
     Ok(())
 }
+
+fn duration_or_off(duration: Duration) -> String {
+    if duration.is_zero() {
+        String::from("off")
+    } else {
+        format_duration(duration).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_timeouts_stay_within_discords_limit() {
+        let five_minutes = Duration::from_secs(300);
+
+        assert_eq!(scale_timeout(five_minutes, 50), Duration::from_secs(150));
+        assert_eq!(scale_timeout(five_minutes, 150), Duration::from_secs(450));
+        assert_eq!(scale_timeout(MAX_TIMEOUT, 150), MAX_TIMEOUT);
+        assert_eq!(scale_timeout(five_minutes, u64::MAX), MAX_TIMEOUT);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::lang::ruleset_combinator::RulesetCombinator;
+use crate::shop::ShopItem;
 use crate::starboard::Starboard;
 use chrono::Duration;
 use chrono::{DateTime, TimeDelta, Utc};
@@ -29,7 +30,9 @@ pub struct Ids {
 #[derive(Deserialize)]
 pub struct RawConfig {
     pub default_text_detect_cooldown: u64,
-    pub starboards: Vec<Arc<Starboard>>,
+    /// Only read once, to seed the `starboard` table.
+    #[serde(default)]
+    pub starboards: Vec<Starboard>,
     #[serde(flatten)]
     pub ids: Ids,
     pub help_text: Option<Arc<String>>,
@@ -38,6 +41,8 @@ pub struct RawConfig {
     pub skip_hit_rate_text: String,
     pub skip_duration_text: String,
     pub class_categories: Vec<ChannelId>,
+    #[serde(default)]
+    pub shop: Vec<ShopItem>,
 }
 
 impl RawConfig {
@@ -54,8 +59,11 @@ pub struct Config {
     ///
     /// This can be overridden by the `cooldown` field in a response.
     pub default_text_detect_cooldown: TimeDelta,
-    /// The starboards that kingfisher will listen for / update.
-    pub starboards: Vec<Arc<Starboard>>,
+    /// The starboards from the config file.
+    ///
+    /// These are imported into the `starboard` table on first startup and ignored afterwards,
+    /// use `/starboard` to manage them instead.
+    pub legacy_starboards: Vec<Starboard>,
     /// Contains special ids
     pub ids: Ids,
     /// The help text for the bot. `/help`
@@ -74,6 +82,8 @@ pub struct Config {
     pub skip_duration_text: String,
     /// The list of class categories we currently support
     pub class_categories: Vec<ChannelId>,
+    /// What `/shop` sells.
+    pub shop: Vec<ShopItem>,
 }
 
 impl Config {
@@ -111,7 +121,7 @@ impl Config {
 
         Ok(Self {
             default_text_detect_cooldown: TimeDelta::seconds(default_text_detect_cooldown),
-            starboards: raw_config.starboards,
+            legacy_starboards: raw_config.starboards,
             skip_duration_text: raw_config.skip_duration_text,
             help_text: raw_config.help_text,
             responses,
@@ -119,6 +129,7 @@ impl Config {
             default_hit_rate: raw_config.default_hit_rate,
             skip_hit_rate_text: raw_config.skip_hit_rate_text,
             class_categories: raw_config.class_categories,
+            shop: raw_config.shop,
             ids: raw_config.ids,
         })
     }
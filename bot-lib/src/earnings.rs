@@ -0,0 +1,234 @@
+//! Bank rewards for taking part in the server, tuned in the single `earning_settings:1` row.
+
+use crate::{
+    bank_audit::{AuditAction, NewAuditEntry, RECORD_AUDIT},
+    data::DB,
+    economy::Bank,
+};
+use color_eyre::eyre::{Result, eyre};
+use parking_lot::{Mutex, RwLock};
+use poise::serenity_prelude::{ChannelId, Context, GuildId, Message, UserId};
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+use surrealdb::types::SurrealValue;
+
+static EARNING_SETTINGS: LazyLock<RwLock<EarningSettings>> =
+    LazyLock::new(|| RwLock::new(EarningSettings::default()));
+
+/// When each user was last paid for a class message. Forgetting this on restart only means one
+/// early payout, so it isn't persisted.
+static CLASS_MESSAGES_PAID: LazyLock<Mutex<PaidAt>> = LazyLock::new(Default::default);
+
+/// What each way of taking part pays. Zero turns that one off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, SurrealValue)]
+pub struct EarningSettings {
+    /// Paid to the author of a message that makes it onto a starboard.
+    pub starboarded: u64,
+    /// Paid to the yeeter when their yeet passes.
+    pub yeet_won: u64,
+    /// Paid to the victim when the vote turns against the yeeter.
+    pub yeet_survived: u64,
+    /// Paid for a message in a class channel.
+    pub class_message: u64,
+    /// How long after a paid class message the next one pays.
+    pub class_message_cooldown: Duration,
+}
+
+impl Default for EarningSettings {
+    /// The same values schema.surrealql seeds the row with.
+    fn default() -> Self {
+        Self {
+            starboarded: 25,
+            yeet_won: 10,
+            yeet_survived: 10,
+            class_message: 1,
+            class_message_cooldown: Duration::from_secs(300),
+        }
+    }
+}
+
+impl EarningSettings {
+    /// The settings as of the last [`EarningSettings::reload`].
+    pub fn current() -> Self {
+        *EARNING_SETTINGS.read()
+    }
+
+    pub async fn reload() -> Result<()> {
+        let settings: Option<Self> = DB
+            .query("SELECT * OMIT id FROM ONLY earning_settings:1")
+            .await?
+            .check()?
+            .take(0)?;
+
+        *EARNING_SETTINGS.write() =
+            settings.ok_or_else(|| eyre!("earning_settings:1 is missing"))?;
+
+        Ok(())
+    }
+
+    /// Saves the settings `admin` chose, with their audit row. Call [`EarningSettings::reload`]
+    /// afterwards for it to take effect.
+    pub async fn save(&self, admin: UserId) -> Result<()> {
+        let audit = NewAuditEntry::new(admin, AuditAction::Earnings, None, format!("{self:?}"))?;
+
+        DB.query(format!(
+            "BEGIN TRANSACTION;
+            UPSERT earning_settings:1 CONTENT $settings;
+            {RECORD_AUDIT};
+            COMMIT TRANSACTION;"
+        ))
+        .bind(("settings", *self))
+        .bind(("audit", audit))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+}
+
+/// Loads the earning settings seeded by schema.surrealql.
+pub async fn setup_earning_settings() -> Result<()> {
+    EarningSettings::reload().await
+}
+
+/// Pays `user_id` for taking part, unless that way of earning is turned off or their account is
+/// frozen.
+pub async fn earn(user_id: UserId, amount: u64, reason: impl Into<String>) -> Result<()> {
+    if amount == 0 || Bank::freeze(user_id).await?.is_some() {
+        return Ok(());
+    }
+
+    let amount = i64::try_from(amount).unwrap_or(i64::MAX);
+    Bank::change(user_id, amount, reason.into()).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct PaidAt(FxHashMap<UserId, Instant>);
+
+impl PaidAt {
+    /// Whether `user_id` can be paid again, and if so, starts their cooldown.
+    fn claim(&mut self, user_id: UserId, cooldown: Duration, now: Instant) -> bool {
+        let ready = self
+            .0
+            .get(&user_id)
+            .is_none_or(|&paid_at| now.duration_since(paid_at) >= cooldown);
+
+        if ready {
+            self.0.insert(user_id, now);
+        }
+
+        ready
+    }
+}
+
+/// Whether `channel_id`, or the channel a thread is in, sits in one of the class categories.
+fn is_class_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    class_categories: &[ChannelId],
+) -> bool {
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return false;
+    };
+
+    let parent_of = |channel_id: ChannelId| {
+        guild
+            .channels
+            .get(&channel_id)
+            .or_else(|| guild.threads.iter().find(|thread| thread.id == channel_id))
+            .and_then(|channel| channel.parent_id)
+    };
+
+    let Some(parent_id) = parent_of(channel_id) else {
+        return false;
+    };
+
+    class_categories.contains(&parent_id)
+        || parent_of(parent_id).is_some_and(|category| class_categories.contains(&category))
+}
+
+/// Pays for a message in a class channel, at most once per cooldown per user.
+pub async fn earn_from_class_message(
+    ctx: &Context,
+    message: &Message,
+    class_categories: &[ChannelId],
+) -> Result<()> {
+    let settings = EarningSettings::current();
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+
+    if message.author.bot
+        || settings.class_message == 0
+        || !is_class_channel(ctx, guild_id, message.channel_id, class_categories)
+    {
+        return Ok(());
+    }
+
+    let ready = CLASS_MESSAGES_PAID.lock().claim(
+        message.author.id,
+        settings.class_message_cooldown,
+        Instant::now(),
+    );
+
+    if ready {
+        earn(message.author.id, settings.class_message, "Class chat").await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn class_messages_pay_once_per_cooldown() {
+        let mut paid_at = PaidAt::default();
+        let cooldown = Duration::from_secs(300);
+        let start = Instant::now();
+        let (first, second) = (UserId::new(1), UserId::new(2));
+
+        assert!(paid_at.claim(first, cooldown, start));
+        assert!(!paid_at.claim(first, cooldown, start + Duration::from_secs(299)));
+        assert!(paid_at.claim(second, cooldown, start + Duration::from_secs(1)));
+        assert!(paid_at.claim(first, cooldown, start + Duration::from_secs(300)));
+        assert!(!paid_at.claim(first, cooldown, start + Duration::from_secs(301)));
+    }
+
+    pub(crate) async fn assert_earnings_are_seeded_and_paid() {
+        EarningSettings::reload().await.unwrap();
+        assert_eq!(EarningSettings::current(), EarningSettings::default());
+
+        let admin = UserId::new(99_802);
+
+        let settings = EarningSettings {
+            starboarded: 0,
+            ..EarningSettings::current()
+        };
+        settings.save(admin).await.unwrap();
+        EarningSettings::reload().await.unwrap();
+        assert_eq!(EarningSettings::current(), settings);
+
+        let user = UserId::new(99_801);
+        earn(user, EarningSettings::current().starboarded, "Starboarded")
+            .await
+            .unwrap();
+        assert_eq!(Bank::history_len(user).await.unwrap(), 0);
+
+        earn(user, EarningSettings::current().yeet_won, "Won a yeet vote")
+            .await
+            .unwrap();
+        assert_eq!(Bank::get(user).await.unwrap().balance, 10);
+
+        EarningSettings::default().save(admin).await.unwrap();
+        EarningSettings::reload().await.unwrap();
+    }
+}
@@ -1,19 +1,125 @@
-use crate::data::DB;
+use crate::{
+    bank_audit::{AuditAction, NewAuditEntry, RECORD_AUDIT},
+    data::DB,
+    utils::db_record_id,
+};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{Result, eyre};
 use poise::serenity_prelude::UserId;
-use serde::Deserialize;
-use surrealdb::types::{RecordId, SurrealValue};
+use serde::{Deserialize, Serialize};
+use surrealdb::types::{Array, RecordId, SurrealValue};
 
-#[derive(Clone, Debug, Deserialize, SurrealValue)]
+/// One entry in a user's bank history.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Change {
+    /// What to give `/bank_admin reverse` to undo this change.
+    pub id: String,
     pub amount: i64,
     pub reason: String,
+    /// When the change happened, if it was recorded after changes got their own table.
+    pub changed_at: Option<DateTime<Utc>>,
+    /// Who the money came from or went to, for transfers.
+    pub counterparty: Option<UserId>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ChangeRow {
+    change_id: String,
+    amount: i64,
+    reason: String,
+    changed_at: Option<DateTime<Utc>>,
+    counterparty: Option<i64>,
+}
+
+impl From<ChangeRow> for Change {
+    fn from(row: ChangeRow) -> Self {
+        Self {
+            id: row.change_id,
+            amount: row.amount,
+            reason: row.reason,
+            changed_at: row.changed_at,
+            counterparty: row.counterparty.map(|id| UserId::new(id as u64)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, SurrealValue)]
 pub struct BankAccount {
     pub balance: i64,
-    pub changes: Vec<Change>,
+}
+
+/// How a conditional [`Bank`] change went.
+#[derive(Clone, Debug)]
+pub enum Debit {
+    Done(BankAccount),
+    TooPoor {
+        balance: i64,
+    },
+    /// An admin froze the account.
+    Frozen,
+}
+
+/// How a [`Bank::transfer`] went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transfer {
+    Done {
+        from_balance: i64,
+        to_balance: i64,
+    },
+    TooPoor {
+        balance: i64,
+    },
+    /// An admin froze one of the accounts.
+    Frozen {
+        user_id: UserId,
+    },
+}
+
+/// Why and by whom an account was frozen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Freeze {
+    pub reason: String,
+    pub frozen_by: UserId,
+    pub frozen_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct FreezeRow {
+    reason: String,
+    frozen_by: i64,
+    frozen_at: DateTime<Utc>,
+}
+
+/// How a [`Bank::reverse`] went.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reversal {
+    /// `change` was undone on `user_id`'s account.
+    Done {
+        user_id: UserId,
+        change: Change,
+        balance: i64,
+    },
+    NotFound,
+    AlreadyReversed,
+}
+
+/// One row of the ledger export.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, SurrealValue)]
+pub struct LedgerEntry {
+    pub id: String,
+    pub user_id: i64,
+    pub amount: i64,
+    pub reason: String,
+    pub counterparty: Option<i64>,
+    pub gambling_net: Option<i64>,
+    pub reverses: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct TransferRow {
+    paid: Vec<i64>,
+    received: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, SurrealValue)]
@@ -28,46 +134,510 @@ struct YeetScore {
     count: u64,
 }
 
-fn record_id(table: &str, user_id: UserId) -> Result<RecordId> {
-    let user_id = i64::try_from(u64::from(user_id))
-        .map_err(|_| eyre!("Discord user ID does not fit in a SurrealDB numeric record ID"))?;
-    Ok(RecordId::new(table, user_id))
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ChangeCount {
+    count: u64,
+}
+
+/// Records a change to `$account` in `bank_change`.
+pub(crate) const RECORD_CHANGE: &str = "CREATE bank_change \
+     SET account = $account, amount = $amount, reason = $reason, \
+     gambling_net = $gambling_net, changed_at = time::now()";
+
+/// Which ranking `/bank leaderboard` shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Leaderboard {
+    /// The biggest balances, or the most gained over a window.
+    #[name = "Richest"]
+    Balance,
+    /// The most won from gambling, minus what was lost.
+    #[name = "Best gamblers"]
+    GamblingProfit,
+    /// The most won on a single bet.
+    #[name = "Biggest wins"]
+    BiggestWin,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct LeaderboardRow {
+    user_id: i64,
+    score: i64,
 }
 
+/// Selects the columns of a [`LedgerEntry`] from `bank_change`.
+const SELECT_LEDGER: &str = "SELECT <string> id AS id, record::id(account) AS user_id, amount, \
+     reason, (IF counterparty { record::id(counterparty) }) AS counterparty, gambling_net, \
+     (IF reverses { <string> reverses }) AS reverses, changed_at";
+
 pub struct Bank;
 
 impl Bank {
     pub async fn get(user_id: UserId) -> Result<BankAccount> {
         let account = DB
-            .select::<Option<BankAccount>>(record_id("bank_account", user_id)?)
+            .select::<Option<BankAccount>>(db_record_id("bank_account", user_id)?)
             .await?;
         Ok(account.unwrap_or_default())
     }
 
     pub async fn change(user_id: UserId, amount: i64, reason: String) -> Result<BankAccount> {
-        let account = record_id("bank_account", user_id)?;
+        Self::record(user_id, amount, reason, None, None).await
+    }
+
+    /// Hands `amount` to `user_id` from `admin`, for `/bank_admin give_charity`.
+    pub async fn give_charity(
+        user_id: UserId,
+        amount: i64,
+        reason: String,
+        admin: UserId,
+    ) -> Result<BankAccount> {
+        let audit = NewAuditEntry::new(
+            admin,
+            AuditAction::GiveCharity,
+            Some(user_id),
+            format!("Gave {amount}"),
+        )?;
+        Self::record(user_id, amount, reason, None, Some(audit)).await
+    }
+
+    /// `gambling_net` is what a bet won or lost, for the gambling leaderboards, and `audit` is
+    /// the admin action that made the change, if it was one.
+    async fn record(
+        user_id: UserId,
+        amount: i64,
+        reason: String,
+        gambling_net: Option<i64>,
+        audit: Option<NewAuditEntry>,
+    ) -> Result<BankAccount> {
+        let account = db_record_id("bank_account", user_id)?;
         let mut response = DB
-            .query(
-                "UPSERT ONLY $account \
-                 SET balance += $amount, changes += { amount: $amount, reason: $reason } \
-                 RETURN AFTER",
-            )
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 {RECORD_CHANGE}; \
+                 UPSERT ONLY $account SET balance += $amount RETURN AFTER; \
+                 IF $audit {{ {RECORD_AUDIT} }}; \
+                 COMMIT TRANSACTION;"
+            ))
             .bind(("account", account))
             .bind(("amount", amount))
             .bind(("reason", reason))
+            .bind(("gambling_net", gambling_net))
+            .bind(("audit", audit))
             .await?
             .check()?;
 
         response
-            .take::<Option<BankAccount>>(0)?
+            .take::<Option<BankAccount>>(2)?
             .ok_or_else(|| eyre!("bank account UPSERT returned no record"))
     }
 
-    pub async fn get_history(user_id: UserId) -> Result<Option<Vec<Change>>> {
-        Ok(DB
-            .select::<Option<BankAccount>>(record_id("bank_account", user_id)?)
+    /// Settles a bet of `stake` that won or lost `net`, if the balance still covers the stake.
+    pub async fn gamble(user_id: UserId, stake: i64, net: i64, reason: String) -> Result<Debit> {
+        Self::record_if_covered(user_id, stake, net, reason, Some(net)).await
+    }
+
+    /// Changes the balance by `amount`, but only if it's at least `stake` when the change lands.
+    ///
+    /// The check and the change are a single query, so concurrent spending can't overdraw.
+    async fn record_if_covered(
+        user_id: UserId,
+        stake: i64,
+        amount: i64,
+        reason: String,
+        gambling_net: Option<i64>,
+    ) -> Result<Debit> {
+        if stake < 0 {
+            return Err(eyre!(
+                "conditional changes can't have a negative stake of {stake}"
+            ));
+        }
+
+        // Any balance covers nothing, even one that doesn't exist yet.
+        if stake == 0 {
+            if Self::freeze(user_id).await?.is_some() {
+                return Ok(Debit::Frozen);
+            }
+
+            return Ok(Debit::Done(
+                Self::record(user_id, amount, reason, gambling_net, None).await?,
+            ));
+        }
+
+        let mut response = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $after = (UPDATE $account SET balance += $amount \
+                     WHERE balance >= $stake AND !$freeze.exists() RETURN AFTER); \
+                 IF $after {{ {RECORD_CHANGE}; }}; \
+                 RETURN $after; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+            .bind(("stake", stake))
+            .bind(("amount", amount))
+            .bind(("reason", reason))
+            .bind(("gambling_net", gambling_net))
             .await?
-            .map(|account| account.changes))
+            .check()?;
+
+        // BEGIN, the LET and the IF come first.
+        match response.take::<Vec<BankAccount>>(3)?.pop() {
+            Some(account) => Ok(Debit::Done(account)),
+            None if Self::freeze(user_id).await?.is_some() => Ok(Debit::Frozen),
+            None => Ok(Debit::TooPoor {
+                balance: Self::get(user_id).await?.balance,
+            }),
+        }
+    }
+
+    /// Moves `amount` from one account to another, unless that would leave `from` negative.
+    ///
+    /// Both sides get a change in their history, and either both happen or neither does.
+    pub async fn transfer(
+        from: UserId,
+        to: UserId,
+        amount: i64,
+        from_reason: String,
+        to_reason: String,
+    ) -> Result<Transfer> {
+        if amount <= 0 {
+            return Err(eyre!("transfers must move a positive amount, not {amount}"));
+        }
+
+        let mut response = DB
+            .query(
+                "BEGIN TRANSACTION; \
+                 LET $paid = (UPDATE $from SET balance -= $amount \
+                     WHERE balance >= $amount \
+                     AND !$from_freeze.exists() AND !$to_freeze.exists() \
+                     RETURN VALUE balance); \
+                 LET $received = IF $paid { \
+                     CREATE bank_change SET account = $from, amount = -$amount, \
+                         reason = $from_reason, counterparty = $to, changed_at = time::now(); \
+                     CREATE bank_change SET account = $to, amount = $amount, \
+                         reason = $to_reason, counterparty = $from, changed_at = time::now(); \
+                     (UPSERT ONLY $to SET balance += $amount RETURN VALUE balance) \
+                 }; \
+                 RETURN { paid: $paid, received: $received }; \
+                 COMMIT TRANSACTION;",
+            )
+            .bind(("from", db_record_id("bank_account", from)?))
+            .bind(("to", db_record_id("bank_account", to)?))
+            .bind(("from_freeze", db_record_id("bank_freeze", from)?))
+            .bind(("to_freeze", db_record_id("bank_freeze", to)?))
+            .bind(("amount", amount))
+            .bind(("from_reason", from_reason))
+            .bind(("to_reason", to_reason))
+            .await?
+            .check()?;
+
+        // BEGIN and both LETs come first.
+        let row = response
+            .take::<Option<TransferRow>>(3)?
+            .ok_or_else(|| eyre!("bank transfer returned nothing"))?;
+
+        if let (Some(&from_balance), Some(to_balance)) = (row.paid.first(), row.received) {
+            return Ok(Transfer::Done {
+                from_balance,
+                to_balance,
+            });
+        }
+
+        for user_id in [from, to] {
+            if Self::freeze(user_id).await?.is_some() {
+                return Ok(Transfer::Frozen { user_id });
+            }
+        }
+
+        Ok(Transfer::TooPoor {
+            balance: Self::get(from).await?.balance,
+        })
+    }
+
+    /// Up to `limit` of the user's changes, newest first, skipping the newest `start`.
+    pub async fn history(user_id: UserId, start: u64, limit: u64) -> Result<Vec<Change>> {
+        let rows: Vec<ChangeRow> = DB
+            .query(
+                "SELECT id, <string> id AS change_id, amount, reason, changed_at, \
+                     (IF counterparty { record::id(counterparty) }) AS counterparty \
+                 FROM bank_change WHERE account = $account \
+                 ORDER BY changed_at DESC, id DESC LIMIT $limit START $start",
+            )
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("start", start))
+            .bind(("limit", limit))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(rows.into_iter().map(Change::from).collect())
+    }
+
+    /// How many changes the user has in their history.
+    pub async fn history_len(user_id: UserId) -> Result<u64> {
+        let count: Option<ChangeCount> = DB
+            .query("SELECT count() FROM bank_change WHERE account = $account GROUP ALL")
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(count.map_or(0, |count| count.count))
+    }
+
+    /// Why the account is frozen, if it is.
+    pub async fn freeze(user_id: UserId) -> Result<Option<Freeze>> {
+        let row: Option<FreezeRow> = DB
+            .query("SELECT reason, frozen_by, frozen_at FROM ONLY $freeze")
+            .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(row.map(|row| Freeze {
+            reason: row.reason,
+            frozen_by: UserId::new(row.frozen_by as u64),
+            frozen_at: row.frozen_at,
+        }))
+    }
+
+    /// Stops the account from spending or taking part in transfers.
+    ///
+    /// Returns the existing freeze instead if the account is already frozen.
+    pub async fn set_frozen(
+        user_id: UserId,
+        frozen_by: UserId,
+        reason: String,
+    ) -> Result<Option<Freeze>> {
+        if let Some(freeze) = Self::freeze(user_id).await? {
+            return Ok(Some(freeze));
+        }
+
+        let audit = NewAuditEntry::new(frozen_by, AuditAction::Freeze, Some(user_id), &reason)?;
+        DB.query(format!(
+            "BEGIN TRANSACTION; \
+             CREATE $freeze SET reason = $reason, frozen_by = $frozen_by; \
+             {RECORD_AUDIT}; \
+             COMMIT TRANSACTION;"
+        ))
+        .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+        .bind(("reason", reason))
+        .bind(("frozen_by", frozen_by.get() as i64))
+        .bind(("audit", audit))
+        .await?
+        .check()?;
+
+        Ok(None)
+    }
+
+    /// Returns whether the account was frozen.
+    pub async fn unfreeze(user_id: UserId, admin: UserId, reason: String) -> Result<bool> {
+        let removed: Option<FreezeRow> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $removed = (DELETE ONLY $freeze RETURN BEFORE); \
+                 IF $removed {{ {RECORD_AUDIT} }}; \
+                 RETURN $removed; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+            .bind((
+                "audit",
+                NewAuditEntry::new(admin, AuditAction::Unfreeze, Some(user_id), reason)?,
+            ))
+            .await?
+            .check()?
+            // BEGIN, the LET and the IF come first.
+            .take(3)?;
+
+        Ok(removed.is_some())
+    }
+
+    /// Puts the balance at exactly `balance`, recording the difference as a change.
+    pub async fn set_balance(
+        user_id: UserId,
+        balance: i64,
+        reason: String,
+        admin: UserId,
+    ) -> Result<BankAccount> {
+        let mut response = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $before = $account.balance ?? 0; \
+                 LET $amount = $balance - $before; \
+                 {RECORD_CHANGE}; \
+                 UPSERT ONLY $account SET balance = $balance RETURN AFTER; \
+                 LET $audit = {{ \
+                     admin: $entry.admin, action: $entry.action, target: $entry.target, \
+                     details: string::concat(<string> $before, ' to ', <string> $balance, \
+                         ': ', $entry.details) \
+                 }}; \
+                 {RECORD_AUDIT}; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("balance", balance))
+            .bind(("reason", format!("Balance set: {reason}")))
+            .bind(("gambling_net", None::<i64>))
+            .bind((
+                "entry",
+                NewAuditEntry::new(admin, AuditAction::SetBalance, Some(user_id), reason)?,
+            ))
+            .await?
+            .check()?;
+
+        // BEGIN, both LETs and the CREATE come first.
+        response
+            .take::<Option<BankAccount>>(4)?
+            .ok_or_else(|| eyre!("bank account UPSERT returned no record"))
+    }
+
+    /// Undoes the change with the given ID, as shown by `/bank_admin inspect_history`.
+    ///
+    /// Only that account is touched, so the other side of a transfer is left as it is.
+    pub async fn reverse(change_id: &str, reason: String, admin: UserId) -> Result<Reversal> {
+        if !change_id.starts_with("bank_change:") {
+            return Ok(Reversal::NotFound);
+        }
+
+        let mut response = DB
+            .query(format!(
+                "LET $change = type::record($change_id); \
+                 {SELECT_LEDGER} FROM ONLY $change;"
+            ))
+            .bind(("change_id", change_id.to_owned()))
+            .await?;
+
+        // `type::record` fails on a key that doesn't parse, like `bank_change:[1,`.
+        let mut errors = response.take_errors();
+        if errors.remove(&0).is_some() {
+            return Ok(Reversal::NotFound);
+        }
+        if let Some(error) = errors.into_values().next() {
+            return Err(error.into());
+        }
+        let row: Option<LedgerEntry> = response.take(1)?;
+
+        let Some(row) = row else {
+            return Ok(Reversal::NotFound);
+        };
+        let user_id = u64::try_from(row.user_id)
+            .map(UserId::new)
+            .map_err(|_| eyre!("invalid bank account user ID {}", row.user_id))?;
+        let audit = NewAuditEntry::new(
+            admin,
+            AuditAction::Reverse,
+            Some(user_id),
+            format!("{} ({}: {}): {reason}", row.id, row.amount, row.reason),
+        )?;
+
+        let mut response = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $change = type::record($change_id); \
+                 LET $reversed = (SELECT VALUE id FROM bank_change WHERE reverses = $change LIMIT 1); \
+                 LET $balance = IF !$reversed {{ \
+                     CREATE bank_change SET account = $account, amount = -$amount, \
+                         reason = $reason, counterparty = $counterparty, \
+                         gambling_net = $gambling_net, reverses = $change, \
+                         changed_at = time::now(); \
+                     {RECORD_AUDIT}; \
+                     (UPSERT ONLY $account SET balance -= $amount RETURN VALUE balance) \
+                 }}; \
+                 RETURN $balance; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("change_id", change_id.to_owned()))
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("amount", row.amount))
+            .bind(("reason", format!("Reversed: {reason}")))
+            .bind(("audit", audit))
+            .bind((
+                "counterparty",
+                row.counterparty
+                    .map(|id| RecordId::new("bank_account", id)),
+            ))
+            .bind(("gambling_net", row.gambling_net.map(|net| -net)))
+            .await?
+            .check()?;
+
+        // BEGIN and the three LETs come first.
+        let Some(balance) = response.take::<Option<i64>>(4)? else {
+            return Ok(Reversal::AlreadyReversed);
+        };
+
+        Ok(Reversal::Done {
+            user_id,
+            change: Change {
+                id: row.id,
+                amount: row.amount,
+                reason: row.reason,
+                changed_at: row.changed_at,
+                counterparty: row.counterparty.map(|id| UserId::new(id as u64)),
+            },
+            balance,
+        })
+    }
+
+    /// Every change ever made, oldest first.
+    pub async fn ledger() -> Result<Vec<LedgerEntry>> {
+        let ledger: Vec<LedgerEntry> = DB
+            .query(format!(
+                "{SELECT_LEDGER} FROM bank_change ORDER BY changed_at, id"
+            ))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(ledger)
+    }
+
+    /// Everyone on `leaderboard`, best first, counting only changes from `since` onwards.
+    ///
+    /// Without `since`, the richest are ranked by their balance rather than their history.
+    pub async fn leaderboard(
+        leaderboard: Leaderboard,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(UserId, i64)>> {
+        let query = match (leaderboard, since) {
+            (Leaderboard::Balance, None) => {
+                "SELECT record::id(id) AS user_id, balance AS score FROM bank_account \
+                 ORDER BY score DESC, user_id"
+            }
+            (Leaderboard::Balance, Some(_)) => {
+                "SELECT record::id(account) AS user_id, math::sum(amount) AS score \
+                 FROM bank_change WHERE changed_at >= $since \
+                 GROUP BY user_id ORDER BY score DESC, user_id"
+            }
+            (Leaderboard::GamblingProfit, _) => {
+                "SELECT record::id(account) AS user_id, math::sum(gambling_net) AS score \
+                 FROM bank_change WHERE gambling_net != NONE \
+                 AND ($since = NONE OR changed_at >= $since) \
+                 GROUP BY user_id ORDER BY score DESC, user_id"
+            }
+            (Leaderboard::BiggestWin, _) => {
+                "SELECT record::id(account) AS user_id, math::max(gambling_net) AS score \
+                 FROM bank_change WHERE gambling_net > 0 \
+                 AND ($since = NONE OR changed_at >= $since) \
+                 GROUP BY user_id ORDER BY score DESC, user_id"
+            }
+        };
+
+        let rows: Vec<LeaderboardRow> = DB
+            .query(query)
+            .bind(("since", since))
+            .await?
+            .check()?
+            .take(0)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let user_id = u64::try_from(row.user_id)
+                    .map(UserId::new)
+                    .map_err(|_| eyre!("invalid bank account user ID {}", row.user_id))?;
+                Ok((user_id, row.score))
+            })
+            .collect()
     }
 
     pub async fn global_rankings() -> Result<Vec<(UserId, BankAccount)>> {
@@ -90,7 +660,6 @@ impl Bank {
                     user_id,
                     BankAccount {
                         balance: ranking.balance,
-                        changes: Vec::new(),
                     },
                 ))
             })
@@ -98,11 +667,87 @@ impl Bank {
     }
 }
 
+#[derive(Debug, Deserialize, SurrealValue)]
+struct LegacyHistory {
+    id: RecordId,
+    user_id: i64,
+    changes: Vec<LegacyChange>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct LegacyChange {
+    amount: i64,
+    reason: String,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct MigratedChange {
+    id: RecordId,
+    account: RecordId,
+    amount: i64,
+    reason: String,
+}
+
+/// Moves the `changes` arrays that used to live on `bank_account` into `bank_change`.
+///
+/// Only ever runs once. The old changes have no timestamps, so they're keyed by their position
+/// in the array to keep their order.
+pub async fn migrate_bank_history() -> Result<()> {
+    // Once migrated, no account has any changes left to find.
+    let legacy: Vec<LegacyHistory> = DB
+        .query("SELECT id, record::id(id) AS user_id, changes FROM bank_account WHERE changes")
+        .await?
+        .check()?
+        .take(0)?;
+
+    let changes: Vec<MigratedChange> = legacy
+        .into_iter()
+        .flat_map(|history| {
+            let LegacyHistory {
+                id,
+                user_id,
+                changes,
+            } = history;
+
+            changes
+                .into_iter()
+                .enumerate()
+                .map(move |(index, change)| MigratedChange {
+                    id: RecordId::new("bank_change", Array::from(vec![user_id, index as i64])),
+                    account: id.clone(),
+                    amount: change.amount,
+                    reason: change.reason,
+                })
+        })
+        .collect();
+
+    let migrated = changes.len();
+
+    DB.query(
+        "BEGIN TRANSACTION; \
+         IF !migration:bank_change_table.exists() { \
+             INSERT INTO bank_change $changes; \
+             UPDATE bank_account UNSET changes; \
+             CREATE migration:bank_change_table; \
+         }; \
+         COMMIT TRANSACTION;",
+    )
+    .bind(("changes", changes))
+    .await?
+    .check()?;
+
+    if migrated > 0 {
+        tracing::info!(migrated, "moved bank history into bank_change");
+    }
+
+    Ok(())
+}
+
 pub struct YeetLeaderboard;
 
 impl YeetLeaderboard {
     pub async fn increment(user_id: UserId) -> Result<u64> {
-        let score = record_id("yeet_score", user_id)?;
+        let score = db_record_id("yeet_score", user_id)?;
         let mut response = DB
             .query("UPSERT ONLY $score SET count += 1 RETURN AFTER")
             .bind(("score", score))
@@ -144,7 +789,14 @@ struct YeetScoreRecord {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use super::{Bank, YeetLeaderboard};
+    use super::{
+        Bank, Debit, Leaderboard, Reversal, Transfer, YeetLeaderboard, migrate_bank_history,
+    };
+    use crate::{
+        bank_audit::{AuditAction, BankAudit},
+        data::DB,
+    };
+    use chrono::{TimeDelta, Utc};
     use poise::serenity_prelude::UserId;
 
     pub(crate) async fn assert_economy_is_persisted_and_ranked() {
@@ -168,9 +820,11 @@ pub(crate) mod tests {
         );
         Bank::change(second, 8, "income".to_owned()).await.unwrap();
 
-        let history = Bank::get_history(first).await.unwrap().unwrap();
+        let history = Bank::history(first, 0, 10).await.unwrap();
         assert_eq!(history.len(), 2);
-        assert_eq!(history[1].amount, -2);
+        assert_eq!(history[0].amount, -2);
+        assert!(history[0].changed_at.is_some());
+        assert_eq!(Bank::history(first, 1, 10).await.unwrap()[0].amount, 5);
 
         let rankings = Bank::global_rankings().await.unwrap();
         assert_eq!(rankings[0].0, second);
@@ -181,4 +835,342 @@ pub(crate) mod tests {
         assert_eq!(YeetLeaderboard::increment(second).await.unwrap(), 1);
         assert_eq!(YeetLeaderboard::rankings().await.unwrap()[0], (first, 2));
     }
+
+    pub(crate) async fn assert_transfers_never_overdraw() {
+        let payer = UserId::new(99_401);
+        let payee = UserId::new(99_402);
+        let pay = |amount| {
+            Bank::transfer(
+                payer,
+                payee,
+                amount,
+                String::from("Paid"),
+                String::from("Got paid"),
+            )
+        };
+
+        assert_eq!(pay(5).await.unwrap(), Transfer::TooPoor { balance: 0 });
+        assert_eq!(Bank::history_len(payee).await.unwrap(), 0);
+        assert!(pay(0).await.is_err());
+
+        Bank::change(payer, 10, String::from("Income"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            pay(7).await.unwrap(),
+            Transfer::Done {
+                from_balance: 3,
+                to_balance: 7
+            }
+        );
+        assert_eq!(pay(4).await.unwrap(), Transfer::TooPoor { balance: 3 });
+        assert_eq!(
+            pay(3).await.unwrap(),
+            Transfer::Done {
+                from_balance: 0,
+                to_balance: 10
+            }
+        );
+
+        let payer_history = Bank::history(payer, 0, 10).await.unwrap();
+        assert_eq!(payer_history.len(), 3);
+        assert_eq!(payer_history[1].amount, -7);
+        assert_eq!(payer_history[1].reason, "Paid");
+        assert_eq!(payer_history[1].counterparty, Some(payee));
+        assert_eq!(payer_history[2].counterparty, None);
+
+        let payee_history = Bank::history(payee, 0, 10).await.unwrap();
+        assert_eq!(payee_history.len(), 2);
+        assert_eq!(payee_history[1].amount, 7);
+        assert_eq!(payee_history[1].reason, "Got paid");
+        assert_eq!(payee_history[1].counterparty, Some(payer));
+    }
+
+    pub(crate) async fn assert_concurrent_debits_never_overdraw() {
+        let spender = UserId::new(99_501);
+
+        assert!(matches!(
+            Bank::record_if_covered(spender, 1, -1, String::from("Broke"), None)
+                .await
+                .unwrap(),
+            Debit::TooPoor { balance: 0 }
+        ));
+
+        Bank::change(spender, 50, String::from("Income"))
+            .await
+            .unwrap();
+
+        let debits = futures::future::join_all(
+            (0..20).map(|n| Bank::record_if_covered(spender, 10, -10, format!("Spend {n}"), None)),
+        )
+        .await;
+
+        let done = debits
+            .into_iter()
+            .filter(|debit| matches!(debit, Ok(Debit::Done(_))))
+            .count();
+        assert_eq!(done, 5, "exactly the covered debits must go through");
+        assert_eq!(Bank::get(spender).await.unwrap().balance, 0);
+        assert_eq!(Bank::history_len(spender).await.unwrap(), 6);
+
+        assert!(matches!(
+            Bank::record_if_covered(spender, 5, 8, String::from("Gamble"), None)
+                .await
+                .unwrap(),
+            Debit::TooPoor { balance: 0 }
+        ));
+
+        Bank::change(spender, 5, String::from("Income"))
+            .await
+            .unwrap();
+
+        let Debit::Done(account) =
+            Bank::record_if_covered(spender, 5, 8, String::from("Gamble"), None)
+                .await
+                .unwrap()
+        else {
+            panic!("a covered stake must go through");
+        };
+        assert_eq!(account.balance, 13);
+    }
+
+    pub(crate) async fn assert_legacy_history_is_migrated_once() {
+        let user = UserId::new(99_601);
+
+        DB.query(
+            "UPSERT bank_account:99601 SET balance = 7, \
+             changes = [{ amount: 10, reason: 'Income' }, { amount: -3, reason: 'Gamble' }]",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+
+        migrate_bank_history().await.unwrap();
+
+        let history = Bank::history(user, 0, 10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            (history[0].amount, history[0].reason.as_str()),
+            (-3, "Gamble")
+        );
+        assert_eq!(
+            (history[1].amount, history[1].reason.as_str()),
+            (10, "Income")
+        );
+        assert_eq!(history[0].changed_at, None);
+        assert_eq!(Bank::get(user).await.unwrap().balance, 7);
+
+        Bank::change(user, 1, String::from("Income")).await.unwrap();
+        migrate_bank_history().await.unwrap();
+
+        let history = Bank::history(user, 0, 10).await.unwrap();
+        assert_eq!(history.len(), 3, "the migration must only run once");
+        assert_eq!(history[0].amount, 1);
+        assert_eq!(history[2].amount, 10);
+    }
+
+    pub(crate) async fn assert_leaderboards_rank_history() {
+        let (lucky, unlucky) = (UserId::new(99_451), UserId::new(99_452));
+        let only_ours = |rankings: Vec<(UserId, i64)>| {
+            rankings
+                .into_iter()
+                .filter(|(user_id, _)| [lucky, unlucky].contains(user_id))
+                .collect::<Vec<_>>()
+        };
+
+        for user in [lucky, unlucky] {
+            Bank::change(user, 100, String::from("Income"))
+                .await
+                .unwrap();
+        }
+
+        for (stake, net) in [(10, 40), (10, -10)] {
+            let debit = Bank::gamble(lucky, stake, net, String::from("Gamble"))
+                .await
+                .unwrap();
+            assert!(matches!(debit, Debit::Done(_)));
+        }
+        Bank::record(unlucky, 0, String::from("Slots"), Some(-20), None)
+            .await
+            .unwrap();
+        Bank::record(unlucky, 35, String::from("Roulette"), Some(25), None)
+            .await
+            .unwrap();
+
+        let profit = Bank::leaderboard(Leaderboard::GamblingProfit, None)
+            .await
+            .unwrap();
+        assert_eq!(only_ours(profit), [(lucky, 30), (unlucky, 5)]);
+
+        let biggest = Bank::leaderboard(Leaderboard::BiggestWin, None)
+            .await
+            .unwrap();
+        assert_eq!(only_ours(biggest), [(lucky, 40), (unlucky, 25)]);
+
+        let richest = Bank::leaderboard(Leaderboard::Balance, None).await.unwrap();
+        assert_eq!(only_ours(richest), [(unlucky, 135), (lucky, 130)]);
+
+        let gained =
+            Bank::leaderboard(Leaderboard::Balance, Some(Utc::now() - TimeDelta::hours(1)))
+                .await
+                .unwrap();
+        assert_eq!(only_ours(gained), [(unlucky, 135), (lucky, 130)]);
+
+        let later = Some(Utc::now() + TimeDelta::hours(1));
+        for leaderboard in [
+            Leaderboard::Balance,
+            Leaderboard::GamblingProfit,
+            Leaderboard::BiggestWin,
+        ] {
+            let ranked = Bank::leaderboard(leaderboard, later).await.unwrap();
+            assert!(
+                only_ours(ranked).is_empty(),
+                "{leaderboard:?} ignores its window"
+            );
+        }
+    }
+
+    pub(crate) async fn assert_admins_can_freeze_reverse_and_export() {
+        let (frozen, other) = (UserId::new(99_471), UserId::new(99_472));
+
+        Bank::change(frozen, 100, String::from("Income"))
+            .await
+            .unwrap();
+        Bank::change(other, 100, String::from("Income"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Bank::set_frozen(frozen, other, String::from("Alt account"))
+                .await
+                .unwrap(),
+            None
+        );
+        let freeze = Bank::freeze(frozen).await.unwrap().unwrap();
+        assert_eq!(freeze.reason, "Alt account");
+        assert_eq!(freeze.frozen_by, other);
+        assert_eq!(
+            Bank::set_frozen(frozen, other, String::from("Again"))
+                .await
+                .unwrap(),
+            Some(freeze),
+            "freezing twice keeps the first freeze"
+        );
+
+        assert!(matches!(
+            Bank::record_if_covered(frozen, 10, -10, String::from("Shop"), None)
+                .await
+                .unwrap(),
+            Debit::Frozen
+        ));
+        for (from, to) in [(frozen, other), (other, frozen)] {
+            let transfer = Bank::transfer(from, to, 10, String::new(), String::new())
+                .await
+                .unwrap();
+            assert_eq!(transfer, Transfer::Frozen { user_id: frozen });
+        }
+        assert_eq!(Bank::get(frozen).await.unwrap().balance, 100);
+
+        assert!(Bank::unfreeze(frozen, other, String::new()).await.unwrap());
+        assert!(!Bank::unfreeze(frozen, other, String::new()).await.unwrap());
+        assert!(matches!(
+            Bank::record_if_covered(frozen, 10, -10, String::from("Shop"), None)
+                .await
+                .unwrap(),
+            Debit::Done(_)
+        ));
+
+        let account = Bank::set_balance(frozen, 500, String::from("Restored after a bug"), other)
+            .await
+            .unwrap();
+        assert_eq!(account.balance, 500);
+        let history = Bank::history(frozen, 0, 1).await.unwrap();
+        assert_eq!(history[0].amount, 410);
+        assert_eq!(history[0].reason, "Balance set: Restored after a bug");
+        let audit = BankAudit::recent(1).await.unwrap().remove(0);
+        assert_eq!(
+            (audit.admin, audit.action, audit.target),
+            (other, AuditAction::SetBalance, Some(frozen))
+        );
+        assert_eq!(audit.details, "90 to 500: Restored after a bug");
+
+        let income = Bank::history(other, 0, 1).await.unwrap().remove(0);
+        assert!(matches!(
+            Bank::reverse(&income.id, String::from("Mistake"), frozen)
+                .await
+                .unwrap(),
+            Reversal::Done { user_id, balance: 0, .. } if user_id == other
+        ));
+        assert_eq!(
+            Bank::reverse(&income.id, String::from("Mistake"), frozen)
+                .await
+                .unwrap(),
+            Reversal::AlreadyReversed
+        );
+        assert_eq!(
+            Bank::reverse("bank_change:missing", String::new(), frozen)
+                .await
+                .unwrap(),
+            Reversal::NotFound
+        );
+        for malformed in ["bank_change:", "bank_change:[1,"] {
+            assert_eq!(
+                Bank::reverse(malformed, String::new(), frozen)
+                    .await
+                    .unwrap(),
+                Reversal::NotFound
+            );
+        }
+        assert_eq!(
+            Bank::reverse("yeet:1", String::new(), frozen)
+                .await
+                .unwrap(),
+            Reversal::NotFound
+        );
+
+        let audits = BankAudit::recent(1).await.unwrap();
+        assert_eq!(
+            (audits[0].admin, audits[0].action, audits[0].target),
+            (frozen, AuditAction::Reverse, Some(other))
+        );
+        assert_eq!(
+            audits[0].details,
+            format!("{} (100: Income): Mistake", income.id)
+        );
+
+        // Changes migrated from the old arrays have array keys.
+        let migrated = UserId::new(99_473);
+        DB.query(
+            "UPSERT bank_account:99473 SET balance = 5; \
+             CREATE bank_change:[99473, 0] SET account = bank_account:99473, amount = 5, \
+                 reason = 'Income', changed_at = time::now();",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        let change = Bank::history(migrated, 0, 1).await.unwrap().remove(0);
+        assert!(matches!(
+            Bank::reverse(&change.id, String::from("Mistake"), other)
+                .await
+                .unwrap(),
+            Reversal::Done { user_id, balance: 0, .. } if user_id == migrated
+        ));
+
+        let ledger = Bank::ledger().await.unwrap();
+        let reversal = ledger
+            .iter()
+            .find(|entry| entry.reverses.as_deref() == Some(income.id.as_str()))
+            .unwrap();
+        assert_eq!(reversal.user_id, 99_472);
+        assert_eq!(reversal.amount, -100);
+        assert!(
+            ledger
+                .windows(2)
+                .all(|pair| pair[0].changed_at <= pair[1].changed_at),
+            "the ledger is oldest first"
+        );
+    }
 }
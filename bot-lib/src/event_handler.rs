@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 use crate::{
-    commands::handle_yeeting,
+    commands::{handle_yeet_ballot_click, handle_yeeting},
     data::State,
+    earnings::earn_from_class_message,
     handle_starboards::handle_starboards,
+    shop::reply_to_bought_triggers,
     text_detection::{kingfisher_reply_reactions, text_detection_and_reaction},
 };
 use bot_traits::ForwardRefToTracing;
@@ -52,11 +54,22 @@ pub async fn event_handler(
                 return Ok(());
             }
 
+            crate::channel_activity::record_message(new_message);
+
             // Track message for limit enforcement
             crate::track_message_for_limit(ctx, new_message)
                 .await
                 .trace_err_ok();
 
+            let class_categories = data.config.read().await.class_categories.clone();
+            earn_from_class_message(ctx, new_message, &class_categories)
+                .await
+                .trace_err_ok();
+
+            reply_to_bought_triggers(ctx, new_message)
+                .await
+                .trace_err_ok();
+
             text_detection_and_reaction(ctx, data, new_message)
                 .await
                 .trace_err_ok();
@@ -94,11 +107,17 @@ pub async fn event_handler(
                 });
             }
 
-            handle_starboards(ctx, data, &message, reaction)
+            handle_starboards(ctx, &message, reaction)
                 .await
                 .trace_err_ok();
         }
-        serenity::FullEvent::InteractionCreate { .. } => {}
+        serenity::FullEvent::InteractionCreate { interaction } => {
+            if let Some(component) = interaction.as_message_component() {
+                handle_yeet_ballot_click(ctx, component)
+                    .await
+                    .trace_err_ok();
+            }
+        }
         serenity::FullEvent::Ratelimit { data } => {
             tracing::warn!("Ratelimited: {:?}", data);
         }
@@ -1,20 +1,23 @@
-use crate::{data::State, starboard::Starboard};
+use crate::{
+    earnings::{EarningSettings, earn},
+    starboard::{Starboard, reaction_count},
+    starboard_history::StarboardHistory,
+};
 use color_eyre::eyre::Result;
 use poise::serenity_prelude::{Context, Message, Reaction, Timestamp};
 
 pub async fn handle_starboards(
     ctx: &Context,
-    data: State,
     message: &Message,
     reaction: &Reaction,
 ) -> Result<()> {
-    if is_message_too_recent(&message.timestamp) || is_message_yeet(message) {
-        return Ok(());
-    }
+    let starboards = Starboard::all();
 
-    let config = data.config.read().await;
+    let futures = starboards.iter().map(|starboard| async {
+        if is_message_too_old(starboard, &message.timestamp) {
+            return;
+        }
 
-    let futures = config.starboards.iter().map(|starboard| async {
         match Starboard::has_recent_message(message.id).await {
             Ok(true) => return,
             Ok(false) => {}
@@ -48,6 +51,37 @@ pub async fn handle_starboards(
                 starboard_channel_id = starboard.channel_id,
                 "Failed to send a claimed message to the starboard"
             );
+            return;
+        }
+
+        if !message.author.bot
+            && let Err(error) = earn(
+                message.author.id,
+                EarningSettings::current().starboarded,
+                format!("Starboarded: {}", message.link()),
+            )
+            .await
+        {
+            tracing::error!(
+                ?error,
+                message_link = %message.link(),
+                "Failed to pay for a starboarded message"
+            );
+        }
+
+        if let Err(error) = StarboardHistory::record(
+            starboard.channel_id,
+            message,
+            &reaction.emoji,
+            reaction_count(message, &reaction.emoji),
+        )
+        .await
+        {
+            tracing::error!(
+                ?error,
+                message_link = %message.link(),
+                "Failed to record a starboard entry"
+            );
         }
     });
 
@@ -56,13 +90,14 @@ pub async fn handle_starboards(
     Ok(())
 }
 
-fn is_message_too_recent(message_timestamp: &Timestamp) -> bool {
-    message_timestamp.unix_timestamp()
-        < (chrono::Utc::now() - chrono::TimeDelta::weeks(1)).timestamp()
-}
+fn is_message_too_old(starboard: &Starboard, message_timestamp: &Timestamp) -> bool {
+    // An age reaching back past the earliest date chrono handles can't leave anything too old.
+    let Some(oldest) = chrono::TimeDelta::from_std(starboard.max_message_age())
+        .ok()
+        .and_then(|max_message_age| chrono::Utc::now().checked_sub_signed(max_message_age))
+    else {
+        return false;
+    };
 
-fn is_message_yeet(message: &Message) -> bool {
-    crate::commands::YEET_STARBOARD_EXCLUSIONS
-        .lock()
-        .contains(&message.id)
+    message_timestamp.unix_timestamp() < oldest.timestamp()
 }
@@ -0,0 +1,401 @@
+//! Per-user income cooldowns and streaks, stored in `bank_income` so a restart doesn't reset them.
+
+use crate::{
+    data::DB,
+    economy::{Bank, BankAccount, RECORD_CHANGE},
+    utils::db_record_id,
+};
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+use color_eyre::eyre::{Result, eyre};
+use poise::serenity_prelude::UserId;
+use serde::Deserialize;
+use surrealdb::types::{RecordId, SurrealValue};
+
+const INCOME_COOLDOWN: TimeDelta = TimeDelta::seconds(60);
+const INCOME_AMOUNT: i64 = 5;
+
+const DAILY_AMOUNT: i64 = 50;
+/// Added to the daily reward for every day of the streak after the first.
+const DAILY_STREAK_BONUS: i64 = 10;
+/// The streak stops adding to the daily reward after this many days.
+const DAILY_STREAK_CAP: u64 = 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, SurrealValue)]
+struct IncomeState {
+    last_income_at: Option<DateTime<Utc>>,
+    /// Grows with every income in a row, and resets when the user gambles.
+    income_bonus: i64,
+    last_daily_at: Option<DateTime<Utc>>,
+    daily_streak: u64,
+}
+
+/// The result of asking for income.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncomeClaim {
+    Paid { amount: i64, bonus: i64 },
+    TooSoon,
+}
+
+/// The result of asking for the daily reward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DailyClaim {
+    Paid { amount: i64, streak: u64 },
+    AlreadyClaimed { next_at: DateTime<Utc> },
+}
+
+impl IncomeState {
+    /// Asking again during the cooldown restarts it.
+    fn income(self, now: DateTime<Utc>) -> (Self, IncomeClaim) {
+        let too_soon = self
+            .last_income_at
+            .is_some_and(|last| now - last < INCOME_COOLDOWN);
+
+        if too_soon {
+            let state = Self {
+                last_income_at: Some(now),
+                ..self
+            };
+            return (state, IncomeClaim::TooSoon);
+        }
+
+        let state = Self {
+            last_income_at: Some(now),
+            income_bonus: self.income_bonus + 1,
+            ..self
+        };
+        let claim = IncomeClaim::Paid {
+            amount: INCOME_AMOUNT + self.income_bonus,
+            bonus: self.income_bonus,
+        };
+
+        (state, claim)
+    }
+
+    /// The daily reward can be claimed once per UTC day, and claiming it on consecutive days
+    /// builds a streak.
+    fn daily(self, now: DateTime<Utc>) -> (Self, DailyClaim) {
+        let today = now.date_naive();
+        let last_day = self.last_daily_at.map(|last| last.date_naive());
+
+        if last_day == Some(today) {
+            let next_at = (today + TimeDelta::days(1))
+                .and_time(NaiveTime::MIN)
+                .and_utc();
+            return (self, DailyClaim::AlreadyClaimed { next_at });
+        }
+
+        let streak = if last_day == today.pred_opt() {
+            self.daily_streak + 1
+        } else {
+            1
+        };
+        let state = Self {
+            last_daily_at: Some(now),
+            daily_streak: streak,
+            ..self
+        };
+        let claim = DailyClaim::Paid {
+            amount: DAILY_AMOUNT + DAILY_STREAK_BONUS * (streak.min(DAILY_STREAK_CAP) - 1) as i64,
+            streak,
+        };
+
+        (state, claim)
+    }
+}
+
+/// How many times a claim is retried when another change to the row lands first.
+const MAX_ATTEMPTS: usize = 10;
+
+pub struct Income;
+
+impl Income {
+    async fn load(income: &RecordId) -> Result<IncomeState> {
+        let state: Option<IncomeState> = DB
+            .query(
+                "INSERT IGNORE INTO bank_income { id: $income } RETURN NONE; \
+                 SELECT last_income_at, income_bonus, last_daily_at, daily_streak \
+                 FROM ONLY $income;",
+            )
+            .bind(("income", income.clone()))
+            .await?
+            .check()?
+            .take(1)?;
+
+        state.ok_or_else(|| eyre!("bank income INSERT left no record"))
+    }
+
+    /// Stores `state` and pays `amount` into `user_id`'s account in one transaction, but only if
+    /// the row still holds `stored`. Returns `None` if another change landed first.
+    async fn pay(
+        user_id: UserId,
+        stored: IncomeState,
+        state: IncomeState,
+        amount: i64,
+        reason: String,
+    ) -> Result<Option<BankAccount>> {
+        let mut response = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $claimed = (UPDATE $income CONTENT $state \
+                     WHERE last_income_at = $stored.last_income_at \
+                     AND income_bonus = $stored.income_bonus \
+                     AND last_daily_at = $stored.last_daily_at \
+                     AND daily_streak = $stored.daily_streak RETURN AFTER); \
+                 LET $paid = IF $claimed {{ \
+                     {RECORD_CHANGE}; \
+                     (UPSERT ONLY $account SET balance += $amount RETURN AFTER) \
+                 }}; \
+                 RETURN $paid; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("income", db_record_id("bank_income", user_id)?))
+            .bind(("stored", stored))
+            .bind(("state", state))
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("amount", amount))
+            .bind(("reason", reason))
+            .bind(("gambling_net", None::<i64>))
+            .await?
+            .check()?;
+
+        // BEGIN and both LETs come first.
+        Ok(response.take::<Option<BankAccount>>(3)?)
+    }
+
+    /// Pays out `user_id`'s income if it's due, and returns their account afterwards.
+    pub async fn claim(user_id: UserId) -> Result<(IncomeClaim, BankAccount)> {
+        let income = db_record_id("bank_income", user_id)?;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let now = Utc::now();
+            let stored = Self::load(&income).await?;
+
+            let (state, claim) = stored.income(now);
+            let IncomeClaim::Paid { amount, .. } = claim else {
+                DB.query("UPDATE $income SET last_income_at = $now")
+                    .bind(("income", income.clone()))
+                    .bind(("now", now))
+                    .await?
+                    .check()?;
+                return Ok((claim, Bank::get(user_id).await?));
+            };
+
+            if let Some(account) =
+                Self::pay(user_id, stored, state, amount, String::from("Income")).await?
+            {
+                return Ok((claim, account));
+            }
+        }
+
+        Err(eyre!(
+            "gave up claiming income for {user_id} after {MAX_ATTEMPTS} attempts"
+        ))
+    }
+
+    /// Pays out `user_id`'s daily reward if it's due, and returns their account afterwards.
+    pub async fn claim_daily(user_id: UserId) -> Result<(DailyClaim, BankAccount)> {
+        let income = db_record_id("bank_income", user_id)?;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let stored = Self::load(&income).await?;
+
+            let (state, claim) = stored.daily(Utc::now());
+            let DailyClaim::Paid { amount, streak } = claim else {
+                return Ok((claim, Bank::get(user_id).await?));
+            };
+
+            let reason = format!("Daily reward (day {streak})");
+            if let Some(account) = Self::pay(user_id, stored, state, amount, reason).await? {
+                return Ok((claim, account));
+            }
+        }
+
+        Err(eyre!(
+            "gave up claiming the daily reward for {user_id} after {MAX_ATTEMPTS} attempts"
+        ))
+    }
+
+    /// Gambling ends an income streak.
+    pub async fn reset_bonus(user_id: UserId) -> Result<()> {
+        DB.query("UPDATE $income SET income_bonus = 0")
+            .bind(("income", db_record_id("bank_income", user_id)?))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        // Midnight UTC.
+        DateTime::from_timestamp(1_699_920_000 + hours * 3600, 0).unwrap()
+    }
+
+    #[test]
+    fn income_bonus_grows_and_spam_restarts_the_cooldown() {
+        let (state, claim) = IncomeState::default().income(at(0));
+        assert_eq!(
+            claim,
+            IncomeClaim::Paid {
+                amount: 5,
+                bonus: 0
+            }
+        );
+
+        let (state, claim) = state.income(at(0) + TimeDelta::seconds(61));
+        assert_eq!(
+            claim,
+            IncomeClaim::Paid {
+                amount: 6,
+                bonus: 1
+            }
+        );
+
+        let spammed_at = at(0) + TimeDelta::seconds(100);
+        let (state, claim) = state.income(spammed_at);
+        assert_eq!(claim, IncomeClaim::TooSoon);
+
+        let (_, claim) = state.income(spammed_at + TimeDelta::seconds(30));
+        assert_eq!(
+            claim,
+            IncomeClaim::TooSoon,
+            "spamming restarts the cooldown"
+        );
+
+        let (_, claim) = state.income(spammed_at + TimeDelta::seconds(60));
+        assert_eq!(
+            claim,
+            IncomeClaim::Paid {
+                amount: 7,
+                bonus: 2
+            }
+        );
+    }
+
+    #[test]
+    fn daily_streaks_need_consecutive_days() {
+        let (state, claim) = IncomeState::default().daily(at(10));
+        assert_eq!(
+            claim,
+            DailyClaim::Paid {
+                amount: 50,
+                streak: 1
+            }
+        );
+
+        let (state, claim) = state.daily(at(23));
+        assert_eq!(claim, DailyClaim::AlreadyClaimed { next_at: at(24) });
+
+        let (state, claim) = state.daily(at(24 + 1));
+        assert_eq!(
+            claim,
+            DailyClaim::Paid {
+                amount: 60,
+                streak: 2
+            }
+        );
+
+        let (_, claim) = state.daily(at(3 * 24));
+        assert_eq!(
+            claim,
+            DailyClaim::Paid {
+                amount: 50,
+                streak: 1
+            }
+        );
+
+        let long_streak = IncomeState {
+            last_daily_at: Some(at(0)),
+            daily_streak: 30,
+            ..IncomeState::default()
+        };
+        let (_, claim) = long_streak.daily(at(24));
+        assert_eq!(
+            claim,
+            DailyClaim::Paid {
+                amount: 50 + 10 * (DAILY_STREAK_CAP as i64 - 1),
+                streak: 31
+            }
+        );
+    }
+
+    pub(crate) async fn assert_income_is_persisted() {
+        let user = UserId::new(99_701);
+
+        assert_eq!(
+            Income::claim(user).await.unwrap().0,
+            IncomeClaim::Paid {
+                amount: 5,
+                bonus: 0
+            }
+        );
+        assert_eq!(Income::claim(user).await.unwrap().0, IncomeClaim::TooSoon);
+
+        DB.query("UPDATE $income SET last_income_at = time::now() - 2m")
+            .bind(("income", db_record_id("bank_income", user).unwrap()))
+            .await
+            .unwrap()
+            .check()
+            .unwrap();
+        assert_eq!(
+            Income::claim(user).await.unwrap().0,
+            IncomeClaim::Paid {
+                amount: 6,
+                bonus: 1
+            }
+        );
+
+        Income::reset_bonus(user).await.unwrap();
+        DB.query("UPDATE $income SET last_income_at = time::now() - 2m")
+            .bind(("income", db_record_id("bank_income", user).unwrap()))
+            .await
+            .unwrap()
+            .check()
+            .unwrap();
+        assert_eq!(
+            Income::claim(user).await.unwrap().0,
+            IncomeClaim::Paid {
+                amount: 5,
+                bonus: 0
+            }
+        );
+
+        assert_eq!(
+            Income::claim_daily(user).await.unwrap().0,
+            DailyClaim::Paid {
+                amount: 50,
+                streak: 1
+            }
+        );
+        assert!(matches!(
+            Income::claim_daily(user).await.unwrap().0,
+            DailyClaim::AlreadyClaimed { .. }
+        ));
+        assert_eq!(Bank::get(user).await.unwrap().balance, 5 + 6 + 5 + 50);
+    }
+
+    pub(crate) async fn assert_concurrent_claims_pay_once() {
+        let user = UserId::new(99_702);
+
+        let claims = futures::future::join_all((0..10).map(|_| Income::claim_daily(user))).await;
+        let paid = claims
+            .into_iter()
+            .filter(|claim| matches!(claim, Ok((DailyClaim::Paid { .. }, _))))
+            .count();
+        assert_eq!(paid, 1, "the daily reward must only be paid once");
+
+        let claims = futures::future::join_all((0..10).map(|_| Income::claim(user))).await;
+        let paid = claims
+            .into_iter()
+            .filter(|claim| matches!(claim, Ok((IncomeClaim::Paid { .. }, _))))
+            .count();
+        assert_eq!(paid, 1, "income must only be paid once per cooldown");
+
+        assert_eq!(Bank::get(user).await.unwrap().balance, 50 + 5);
+        assert_eq!(Bank::history_len(user).await.unwrap(), 2);
+    }
+}
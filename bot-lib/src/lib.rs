@@ -3,30 +3,57 @@
 //! It's not all documented yet, but, damn it, it probably will never be.
 //! :thum:
 
+use bot_traits::ForwardRefToTracing;
 use chrono::{DateTime, Utc};
 use color_eyre::eyre::{Context, Result};
 use data::PoiseContext;
 use itertools::Itertools;
 use poise::serenity_prelude::{CacheHttp, EditMember, GuildId, Member, Mentionable, User, UserId};
 use std::time::Duration;
+use timeout_history::{TimeoutCause, TimeoutHistory};
 
 pub(crate) mod automated_replies;
+mod bank_audit;
+pub(crate) mod bets;
+mod casino_games;
+mod channel_activity;
 pub mod commands;
 pub mod config;
 pub(crate) mod courses;
 pub mod data;
+pub(crate) mod earnings;
 pub(crate) mod economy;
 pub mod event_handler;
 mod handle_starboards;
+mod income;
 mod lang;
+mod scheduled_actions;
+pub(crate) mod shop;
 mod starboard;
+mod starboard_history;
 mod text_detection;
+mod timeout_history;
 mod utils;
+mod yeet_charges;
+mod yeet_eligibility;
+mod yeet_parries;
+mod yeet_perks;
+mod yeet_settings;
+mod yeet_stats;
+mod yeet_votes;
 
+pub use bets::setup_bets;
+pub use casino_games::setup_casino_games;
 pub use commands::track_message_for_limit;
 pub use courses::update_course_list;
+pub use earnings::setup_earning_settings;
+pub use economy::migrate_bank_history;
+pub use scheduled_actions::resume_scheduled_actions;
+pub use shop::setup_shop;
 pub use starboard::debug_force_starboard;
 pub use starboard::debug_surrealdb;
+pub use starboard::setup_starboards;
+pub use yeet_settings::setup_yeet_settings;
 
 trait SayThenDelete {
     async fn say_then_delete(self, message: impl Into<String>) -> Result<()>;
@@ -48,11 +75,14 @@ impl SayThenDelete for PoiseContext<'_> {
 }
 
 trait TimeoutExt {
+    /// Times `target` out and records it in their timeout history.
     async fn timeout(
         &self,
         cache: impl CacheHttp,
         target: &UserId,
         duration: Duration,
+        cause: TimeoutCause,
+        caused_by: UserId,
     ) -> Result<(Member, DateTime<Utc>)>;
 }
 
@@ -62,17 +92,25 @@ impl TimeoutExt for GuildId {
         cache: impl CacheHttp,
         target: &UserId,
         duration: Duration,
+        cause: TimeoutCause,
+        caused_by: UserId,
     ) -> Result<(Member, DateTime<Utc>)> {
         let timeout_end = chrono::Utc::now() + duration;
 
-        self.edit_member(
-            cache,
-            target,
-            EditMember::new().disable_communication_until(timeout_end.to_rfc3339()),
-        )
-        .await
-        .wrap_err("Failed to edit member")
-        .map(|member| (member, timeout_end))
+        let member = self
+            .edit_member(
+                cache,
+                target,
+                EditMember::new().disable_communication_until(timeout_end.to_rfc3339()),
+            )
+            .await
+            .wrap_err("Failed to edit member")?;
+
+        TimeoutHistory::record(&member.user, caused_by, cause, duration)
+            .await
+            .trace_err_ok();
+
+        Ok((member, timeout_end))
     }
 }
 
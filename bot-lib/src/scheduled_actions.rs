@@ -0,0 +1,458 @@
+//! Delayed actions, stored in `scheduled_action` so they still happen if the bot restarts first.
+
+use crate::{data::DB, utils::db_id};
+use bot_traits::ForwardRefToTracing;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, eyre};
+use poise::serenity_prelude::{
+    ChannelId, Context, CreateMessage, EditMember, EditMessage, GuildId, MessageId, RoleId, UserId,
+};
+use serde::Deserialize;
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// Something the bot should do later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduledAction {
+    /// Replace the content of one of the bot's messages, like a "they will return" announcement.
+    EditMessage {
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: String,
+    },
+    DeleteMessage {
+        channel_id: ChannelId,
+        message_id: MessageId,
+    },
+    DirectMessage {
+        user_id: UserId,
+        content: String,
+    },
+    RemoveRole {
+        guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    },
+    /// An empty nickname resets it to the user's display name.
+    SetNickname {
+        guild_id: GuildId,
+        user_id: UserId,
+        nickname: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, SurrealValue)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+enum ActionKind {
+    EditMessage,
+    DeleteMessage,
+    DirectMessage,
+    RemoveRole,
+    SetNickname,
+}
+
+/// A [`ScheduledAction`] flattened into the columns of `scheduled_action`.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, SurrealValue)]
+struct ActionFields {
+    guild_id: Option<i64>,
+    channel_id: Option<i64>,
+    message_id: Option<i64>,
+    user_id: Option<i64>,
+    role_id: Option<i64>,
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ScheduledActionRow {
+    id: RecordId,
+    due_at: DateTime<Utc>,
+    kind: ActionKind,
+    guild_id: Option<i64>,
+    channel_id: Option<i64>,
+    message_id: Option<i64>,
+    user_id: Option<i64>,
+    role_id: Option<i64>,
+    content: Option<String>,
+}
+
+impl ScheduledAction {
+    fn to_row(&self) -> Result<(ActionKind, ActionFields)> {
+        Ok(match self {
+            Self::EditMessage {
+                channel_id,
+                message_id,
+                content,
+            } => (
+                ActionKind::EditMessage,
+                ActionFields {
+                    channel_id: Some(db_id(*channel_id)?),
+                    message_id: Some(db_id(*message_id)?),
+                    content: Some(content.clone()),
+                    ..ActionFields::default()
+                },
+            ),
+            Self::DeleteMessage {
+                channel_id,
+                message_id,
+            } => (
+                ActionKind::DeleteMessage,
+                ActionFields {
+                    channel_id: Some(db_id(*channel_id)?),
+                    message_id: Some(db_id(*message_id)?),
+                    ..ActionFields::default()
+                },
+            ),
+            Self::DirectMessage { user_id, content } => (
+                ActionKind::DirectMessage,
+                ActionFields {
+                    user_id: Some(db_id(*user_id)?),
+                    content: Some(content.clone()),
+                    ..ActionFields::default()
+                },
+            ),
+            Self::RemoveRole {
+                guild_id,
+                user_id,
+                role_id,
+            } => (
+                ActionKind::RemoveRole,
+                ActionFields {
+                    guild_id: Some(db_id(*guild_id)?),
+                    user_id: Some(db_id(*user_id)?),
+                    role_id: Some(db_id(*role_id)?),
+                    ..ActionFields::default()
+                },
+            ),
+            Self::SetNickname {
+                guild_id,
+                user_id,
+                nickname,
+            } => (
+                ActionKind::SetNickname,
+                ActionFields {
+                    guild_id: Some(db_id(*guild_id)?),
+                    user_id: Some(db_id(*user_id)?),
+                    content: Some(nickname.clone()),
+                    ..ActionFields::default()
+                },
+            ),
+        })
+    }
+
+    fn from_row(kind: ActionKind, fields: ActionFields) -> Result<Self> {
+        let missing = |field: &str| eyre!("{kind:?} action is missing its {field}");
+        let discord_id = |value: Option<i64>, field: &str| {
+            value
+                .filter(|&value| value > 0)
+                .map(|value| value as u64)
+                .ok_or_else(|| missing(field))
+        };
+
+        Ok(match kind {
+            ActionKind::EditMessage => Self::EditMessage {
+                channel_id: ChannelId::new(discord_id(fields.channel_id, "channel_id")?),
+                message_id: MessageId::new(discord_id(fields.message_id, "message_id")?),
+                content: fields.content.ok_or_else(|| missing("content"))?,
+            },
+            ActionKind::DeleteMessage => Self::DeleteMessage {
+                channel_id: ChannelId::new(discord_id(fields.channel_id, "channel_id")?),
+                message_id: MessageId::new(discord_id(fields.message_id, "message_id")?),
+            },
+            ActionKind::DirectMessage => Self::DirectMessage {
+                user_id: UserId::new(discord_id(fields.user_id, "user_id")?),
+                content: fields.content.ok_or_else(|| missing("content"))?,
+            },
+            ActionKind::RemoveRole => Self::RemoveRole {
+                guild_id: GuildId::new(discord_id(fields.guild_id, "guild_id")?),
+                user_id: UserId::new(discord_id(fields.user_id, "user_id")?),
+                role_id: RoleId::new(discord_id(fields.role_id, "role_id")?),
+            },
+            ActionKind::SetNickname => Self::SetNickname {
+                guild_id: GuildId::new(discord_id(fields.guild_id, "guild_id")?),
+                user_id: UserId::new(discord_id(fields.user_id, "user_id")?),
+                nickname: fields.content.ok_or_else(|| missing("content"))?,
+            },
+        })
+    }
+
+    pub(crate) async fn run(self, ctx: &Context) -> Result<()> {
+        match self {
+            Self::EditMessage {
+                channel_id,
+                message_id,
+                content,
+            } => {
+                channel_id
+                    .edit_message(ctx, message_id, EditMessage::new().content(content))
+                    .await?;
+            }
+            Self::DeleteMessage {
+                channel_id,
+                message_id,
+            } => channel_id.delete_message(ctx, message_id).await?,
+            Self::DirectMessage { user_id, content } => {
+                user_id
+                    .direct_message(ctx, CreateMessage::new().content(content))
+                    .await?;
+            }
+            Self::RemoveRole {
+                guild_id,
+                user_id,
+                role_id,
+            } => {
+                ctx.http
+                    .remove_member_role(guild_id, user_id, role_id, Some("Shop item expired"))
+                    .await?;
+            }
+            Self::SetNickname {
+                guild_id,
+                user_id,
+                nickname,
+            } => {
+                guild_id
+                    .edit_member(ctx, user_id, EditMember::new().nickname(nickname))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An action that hasn't run yet.
+#[derive(Debug, Clone)]
+pub struct PendingAction {
+    pub id: RecordId,
+    pub due_at: DateTime<Utc>,
+    pub action: ScheduledAction,
+}
+
+impl TryFrom<ScheduledActionRow> for PendingAction {
+    type Error = color_eyre::Report;
+
+    fn try_from(row: ScheduledActionRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            due_at: row.due_at,
+            action: ScheduledAction::from_row(
+                row.kind,
+                ActionFields {
+                    guild_id: row.guild_id,
+                    channel_id: row.channel_id,
+                    message_id: row.message_id,
+                    user_id: row.user_id,
+                    role_id: row.role_id,
+                    content: row.content,
+                },
+            )?,
+        })
+    }
+}
+
+pub struct ScheduledActions;
+
+impl ScheduledActions {
+    async fn create(due_at: DateTime<Utc>, action: ScheduledAction) -> Result<PendingAction> {
+        let (kind, fields) = action.to_row()?;
+
+        let id: Option<RecordId> = DB
+            .query(
+                "CREATE ONLY scheduled_action SET due_at = $due_at, kind = $kind, \
+                 guild_id = $fields.guild_id, channel_id = $fields.channel_id, \
+                 message_id = $fields.message_id, user_id = $fields.user_id, \
+                 role_id = $fields.role_id, content = $fields.content \
+                 RETURN VALUE id",
+            )
+            .bind(("due_at", due_at))
+            .bind(("kind", kind))
+            .bind(("fields", fields))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(PendingAction {
+            id: id.ok_or_else(|| eyre!("scheduled_action CREATE returned no record"))?,
+            due_at,
+            action,
+        })
+    }
+
+    /// Every action that hasn't finished, including ones interrupted by a restart.
+    async fn pending() -> Result<Vec<PendingAction>> {
+        let rows: Vec<ScheduledActionRow> = DB
+            .query(
+                "UPDATE scheduled_action SET status = 'pending' WHERE status = 'running'; \
+                 SELECT id, due_at, kind, guild_id, channel_id, message_id, user_id, role_id, \
+                 content \
+                 FROM scheduled_action WHERE status = 'pending' ORDER BY due_at;",
+            )
+            .await?
+            .check()?
+            .take(1)?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| PendingAction::try_from(row).trace_err_ok())
+            .collect())
+    }
+
+    /// Marks an action as running, returning whether it was still pending.
+    ///
+    /// Only one caller ever gets `true`, so an action never runs twice.
+    async fn claim(action: &RecordId) -> Result<bool> {
+        let claimed: Vec<RecordId> = DB
+            .query("UPDATE $action SET status = 'running' WHERE status = 'pending' RETURN VALUE id")
+            .bind(("action", action.clone()))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(!claimed.is_empty())
+    }
+
+    async fn finish(action: &RecordId, error: Option<String>) -> Result<()> {
+        DB.query(
+            "UPDATE $action SET status = IF $error = NONE { 'done' } ELSE { 'failed' }, \
+             error = $error, finished_at = time::now()",
+        )
+        .bind(("action", action.clone()))
+        .bind(("error", error))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+}
+
+fn spawn(ctx: Context, pending: PendingAction) {
+    tokio::spawn(async move {
+        let remaining = (pending.due_at - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(remaining).await;
+
+        if !ScheduledActions::claim(&pending.id)
+            .await
+            .trace_err_ok()
+            .unwrap_or(false)
+        {
+            return;
+        }
+
+        let error = pending
+            .action
+            .run(&ctx)
+            .await
+            .err()
+            .map(|error| error.to_string());
+
+        if let Some(error) = &error {
+            tracing::warn!("Scheduled action {:?} failed: {error}", pending.id);
+        }
+
+        ScheduledActions::finish(&pending.id, error)
+            .await
+            .trace_err_ok();
+    });
+}
+
+/// Runs `action` at `due_at`, or as soon as the bot is back if it's down by then.
+pub async fn schedule(ctx: &Context, due_at: DateTime<Utc>, action: ScheduledAction) -> Result<()> {
+    let pending = ScheduledActions::create(due_at, action).await?;
+    spawn(ctx.clone(), pending);
+
+    Ok(())
+}
+
+/// Picks up the actions that were still waiting when the bot last stopped.
+pub async fn resume_scheduled_actions(ctx: Context) -> Result<()> {
+    for pending in ScheduledActions::pending().await? {
+        spawn(ctx.clone(), pending);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_survive_the_round_trip_through_their_row() {
+        for action in [
+            ScheduledAction::EditMessage {
+                channel_id: ChannelId::new(1),
+                message_id: MessageId::new(2),
+                content: String::from("They have since returned."),
+            },
+            ScheduledAction::DeleteMessage {
+                channel_id: ChannelId::new(1),
+                message_id: MessageId::new(2),
+            },
+            ScheduledAction::DirectMessage {
+                user_id: UserId::new(4),
+                content: String::from("Welcome back"),
+            },
+            ScheduledAction::RemoveRole {
+                guild_id: GuildId::new(3),
+                user_id: UserId::new(4),
+                role_id: RoleId::new(5),
+            },
+            ScheduledAction::SetNickname {
+                guild_id: GuildId::new(3),
+                user_id: UserId::new(4),
+                nickname: String::new(),
+            },
+        ] {
+            let (kind, fields) = action.to_row().unwrap();
+            assert_eq!(ScheduledAction::from_row(kind, fields).unwrap(), action);
+        }
+
+        assert!(
+            ScheduledAction::from_row(ActionKind::RemoveRole, ActionFields::default()).is_err()
+        );
+    }
+
+    pub(crate) async fn assert_scheduled_actions_run_once() {
+        let action = ScheduledAction::DirectMessage {
+            user_id: UserId::new(99_201),
+            content: String::from("Welcome back"),
+        };
+        let due_at = Utc::now();
+        let created = ScheduledActions::create(due_at, action.clone())
+            .await
+            .unwrap();
+
+        let pending = ScheduledActions::pending().await.unwrap();
+        let resumed = pending
+            .iter()
+            .find(|pending| pending.id == created.id)
+            .unwrap();
+        assert_eq!(resumed.action, action);
+        assert_eq!(resumed.due_at.timestamp(), due_at.timestamp());
+
+        assert!(ScheduledActions::claim(&created.id).await.unwrap());
+        assert!(
+            !ScheduledActions::claim(&created.id).await.unwrap(),
+            "an action must only run once"
+        );
+
+        // A restart while it was running puts it back in the queue.
+        let pending = ScheduledActions::pending().await.unwrap();
+        assert!(pending.iter().any(|pending| pending.id == created.id));
+        assert!(ScheduledActions::claim(&created.id).await.unwrap());
+
+        ScheduledActions::finish(
+            &created.id,
+            Some(String::from("Cannot send messages to this user")),
+        )
+        .await
+        .unwrap();
+        assert!(
+            ScheduledActions::pending()
+                .await
+                .unwrap()
+                .iter()
+                .all(|pending| pending.id != created.id)
+        );
+        assert!(!ScheduledActions::claim(&created.id).await.unwrap());
+    }
+}
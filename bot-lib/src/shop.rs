@@ -0,0 +1,801 @@
+//! Things to spend bank balance on, listed in the `[[shop]]` section of the config.
+//!
+//! What each user bought is kept in `inventory`, so time-limited items still run out if the bot
+//! restarts in between.
+
+use crate::{
+    data::DB,
+    economy::{Bank, BankAccount, RECORD_CHANGE},
+    scheduled_actions::{ScheduledAction, schedule},
+    utils::{db_id, db_record_id},
+};
+use bot_traits::ForwardRefToTracing;
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::{Result, eyre};
+use parking_lot::Mutex;
+use poise::serenity_prelude::{
+    Context, CreateAllowedMentions, CreateMessage, EditMember, GuildId, Message, RoleId, UserId,
+};
+use serde::Deserialize;
+use serde_with::{DurationSeconds, serde_as};
+use std::{sync::LazyLock, time::Duration};
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// How long a bought trigger stays quiet after it replies.
+const AUTO_REPLY_COOLDOWN: TimeDelta = TimeDelta::seconds(60);
+pub const TRIGGER_LENGTH: std::ops::RangeInclusive<usize> = 3..=32;
+pub const MAX_REPLY_LENGTH: usize = 200;
+/// Discord's own limit.
+pub const MAX_NICKNAME_LENGTH: usize = 32;
+
+static BOUGHT_REPLIES: LazyLock<Mutex<BoughtReplies>> = LazyLock::new(Default::default);
+
+const fn one_day() -> Duration {
+    Duration::from_secs(24 * 60 * 60)
+}
+
+/// Something listed in the shop.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ShopItem {
+    /// What users pick in `/shop buy`, so it should be unique.
+    pub name: String,
+    pub description: String,
+    pub price: u64,
+    #[serde(flatten)]
+    pub kind: ShopItemKind,
+}
+
+#[serde_as]
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShopItemKind {
+    /// A cosmetic role, kept for good unless it has a duration.
+    Role {
+        role_id: RoleId,
+        #[serde_as(as = "Option<DurationSeconds<u64>>")]
+        #[serde(default, rename = "duration_seconds")]
+        duration: Option<Duration>,
+    },
+    /// A nickname of the buyer's choosing. Their old one comes back afterwards.
+    Nickname {
+        #[serde_as(as = "DurationSeconds<u64>")]
+        #[serde(default = "one_day", rename = "duration_seconds")]
+        duration: Duration,
+    },
+    /// The bot replies to a phrase of the buyer's choosing, in any channel.
+    AutoReply {
+        #[serde_as(as = "DurationSeconds<u64>")]
+        #[serde(default = "one_day", rename = "duration_seconds")]
+        duration: Duration,
+    },
+    /// Uses of `/clip_that` that skip its cooldown.
+    ClipThatUses { uses: u64 },
+}
+
+impl ShopItemKind {
+    fn duration(self) -> Option<Duration> {
+        match self {
+            Self::Role { duration, .. } => duration,
+            Self::Nickname { duration } | Self::AutoReply { duration } => Some(duration),
+            Self::ClipThatUses { .. } => None,
+        }
+    }
+
+    /// Whether buying it again stacks, rather than waiting for the last one to run out.
+    fn stacks(self) -> bool {
+        matches!(self, Self::ClipThatUses { .. })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, SurrealValue)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+enum InventoryKind {
+    Role,
+    Nickname,
+    AutoReply,
+    ClipThatUses,
+}
+
+impl From<ShopItemKind> for InventoryKind {
+    fn from(kind: ShopItemKind) -> Self {
+        match kind {
+            ShopItemKind::Role { .. } => Self::Role,
+            ShopItemKind::Nickname { .. } => Self::Nickname,
+            ShopItemKind::AutoReply { .. } => Self::AutoReply,
+            ShopItemKind::ClipThatUses { .. } => Self::ClipThatUses,
+        }
+    }
+}
+
+/// What the buyer chose for the items that need it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Order {
+    Plain,
+    Nickname(String),
+    AutoReply { trigger: String, reply: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Purchase {
+    Bought {
+        expires_at: Option<DateTime<Utc>>,
+        balance: i64,
+    },
+    TooPoor {
+        price: i64,
+        balance: i64,
+    },
+    /// The last one hasn't run out yet. `None` if it never does.
+    AlreadyOwned {
+        expires_at: Option<DateTime<Utc>>,
+    },
+    TriggerTaken,
+    Frozen,
+}
+
+/// A row of `inventory`, as shown to its owner.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, SurrealValue)]
+pub struct InventoryItem {
+    pub item: String,
+    pub bought_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub uses_left: Option<u64>,
+    pub trigger: Option<String>,
+}
+
+#[derive(Debug, Clone, SurrealValue)]
+struct NewInventoryRow {
+    user_id: i64,
+    item: String,
+    kind: InventoryKind,
+    price: i64,
+    expires_at: Option<DateTime<Utc>>,
+    uses_left: Option<u64>,
+    trigger: Option<String>,
+    reply: Option<String>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ChargeRow {
+    /// When the buyer's unexpired copies of the item run out.
+    owned: Vec<Option<DateTime<Utc>>>,
+    trigger_taken: bool,
+    bought: Option<RecordId>,
+    balance: Option<i64>,
+}
+
+/// How [`Shop::charge`] went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Charge {
+    /// Paid for, and added to `inventory` as `bought`.
+    Done {
+        bought: RecordId,
+        balance: i64,
+    },
+    Refused(Purchase),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, SurrealValue)]
+struct BoughtReplyRow {
+    trigger: String,
+    reply: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BoughtReply {
+    trigger: String,
+    reply: String,
+    expires_at: DateTime<Utc>,
+    last_sent_at: Option<DateTime<Utc>>,
+}
+
+impl From<BoughtReplyRow> for BoughtReply {
+    fn from(row: BoughtReplyRow) -> Self {
+        Self {
+            trigger: row.trigger,
+            reply: row.reply,
+            expires_at: row.expires_at,
+            last_sent_at: None,
+        }
+    }
+}
+
+/// The bought triggers that haven't run out, kept in memory since every message is checked.
+#[derive(Debug, Default)]
+struct BoughtReplies(Vec<BoughtReply>);
+
+impl BoughtReplies {
+    /// The reply to `content`, if it contains a trigger that isn't cooling down.
+    fn reply_to(&mut self, content: &str, now: DateTime<Utc>) -> Option<String> {
+        self.0.retain(|bought| bought.expires_at > now);
+
+        let content = content.to_lowercase();
+        let bought = self.0.iter_mut().find(|bought| {
+            content.contains(&bought.trigger)
+                && bought
+                    .last_sent_at
+                    .is_none_or(|sent_at| now - sent_at >= AUTO_REPLY_COOLDOWN)
+        })?;
+
+        bought.last_sent_at = Some(now);
+        Some(bought.reply.clone())
+    }
+}
+
+/// Triggers are matched case-insensitively.
+pub fn normalize_trigger(trigger: &str) -> String {
+    trigger.trim().to_lowercase()
+}
+
+pub struct Shop;
+
+impl Shop {
+    /// Loads the bought triggers that haven't run out yet.
+    pub async fn load_replies() -> Result<()> {
+        let replies: Vec<BoughtReplyRow> = DB
+            .query(
+                "SELECT trigger, reply, expires_at FROM inventory \
+                 WHERE kind = 'auto_reply' AND expires_at > time::now()",
+            )
+            .await?
+            .check()?
+            .take(0)?;
+
+        BOUGHT_REPLIES.lock().0 = replies.into_iter().map(BoughtReply::from).collect();
+
+        Ok(())
+    }
+
+    /// Everything `owner` bought that hasn't run out or been used up, newest first.
+    pub async fn inventory(owner: UserId) -> Result<Vec<InventoryItem>> {
+        let items: Vec<InventoryItem> = DB
+            .query(
+                "SELECT item, bought_at, expires_at, uses_left, trigger FROM inventory \
+                 WHERE user_id = $user_id \
+                 AND (expires_at = NONE OR expires_at > time::now()) \
+                 AND (uses_left = NONE OR uses_left > 0) \
+                 ORDER BY bought_at DESC",
+            )
+            .bind(("user_id", db_id(owner)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(items)
+    }
+
+    /// Spends one of `owner`'s bought `/clip_that` uses, returning whether they had one.
+    pub async fn use_clip(owner: UserId) -> Result<bool> {
+        let used: Option<RecordId> = DB
+            .query(
+                "BEGIN TRANSACTION; \
+                 LET $row = (SELECT VALUE id FROM inventory \
+                     WHERE user_id = $user_id AND kind = 'clip_that_uses' AND uses_left > 0 \
+                     LIMIT 1)[0]; \
+                 IF $row { UPDATE $row SET uses_left -= 1 }; \
+                 RETURN $row; \
+                 COMMIT TRANSACTION;",
+            )
+            .bind(("user_id", db_id(owner)?))
+            .await?
+            .check()?
+            .take(3)?;
+
+        Ok(used.is_some())
+    }
+
+    /// Charges `buyer` for `item` and hands it over, refunding them if that fails.
+    pub async fn buy(
+        ctx: &Context,
+        guild_id: GuildId,
+        buyer: UserId,
+        item: &ShopItem,
+        order: Order,
+    ) -> Result<Purchase> {
+        let expires_at = item
+            .kind
+            .duration()
+            .map(|duration| Utc::now() + TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX));
+
+        let mut row = NewInventoryRow {
+            user_id: db_id(buyer)?,
+            item: item.name.clone(),
+            kind: item.kind.into(),
+            price: i64::try_from(item.price).unwrap_or(i64::MAX),
+            expires_at,
+            uses_left: None,
+            trigger: None,
+            reply: None,
+        };
+
+        match (item.kind, &order) {
+            (ShopItemKind::Role { .. }, _) => {}
+            (ShopItemKind::Nickname { .. }, Order::Nickname(_)) => {}
+            (ShopItemKind::AutoReply { .. }, Order::AutoReply { trigger, reply }) => {
+                row.trigger = Some(trigger.clone());
+                row.reply = Some(reply.clone());
+            }
+            (ShopItemKind::ClipThatUses { uses }, _) => row.uses_left = Some(uses),
+            (kind, order) => return Err(eyre!("{order:?} doesn't fit a {kind:?} item")),
+        }
+
+        let reason = format!("Bought {} from the shop", item.name);
+        let (bought, balance) =
+            match Self::charge(buyer, &row, !item.kind.stacks(), &reason).await? {
+                Charge::Done { bought, balance } => (bought, balance),
+                Charge::Refused(purchase) => return Ok(purchase),
+            };
+
+        if let Err(error) = Self::grant(ctx, guild_id, buyer, item.kind, order, expires_at).await {
+            Self::refund(buyer, bought, &row, format!("Refund: {reason}"))
+                .await
+                .trace_err_ok();
+            return Err(error);
+        }
+
+        if let (Some(trigger), Some(reply), Some(expires_at)) =
+            (row.trigger, row.reply, row.expires_at)
+        {
+            BOUGHT_REPLIES.lock().0.push(BoughtReply {
+                trigger,
+                reply,
+                expires_at,
+                last_sent_at: None,
+            });
+        }
+
+        Ok(Purchase::Bought {
+            expires_at,
+            balance,
+        })
+    }
+
+    /// Takes the price of `row` from `buyer` and adds it to their inventory.
+    ///
+    /// The checks, the debit and the insert are one transaction, so concurrent buys can't both
+    /// get an `exclusive` item, and a trigger is held in `auto_reply_trigger` by one buyer at a
+    /// time.
+    async fn charge(
+        buyer: UserId,
+        row: &NewInventoryRow,
+        exclusive: bool,
+        reason: &str,
+    ) -> Result<Charge> {
+        let charged: Option<ChargeRow> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $owned = SELECT VALUE expires_at FROM inventory \
+                     WHERE $exclusive AND user_id = $row.user_id AND item = $row.item \
+                     AND (expires_at = NONE OR expires_at > time::now()); \
+                 LET $held = IF $row.trigger {{ type::record('auto_reply_trigger', $row.trigger) }}; \
+                 LET $taken = $held.expires_at > time::now(); \
+                 LET $after = IF !$owned AND !$taken {{ \
+                     (UPDATE $account SET balance += $amount \
+                         WHERE balance >= -$amount AND !$freeze.exists() RETURN AFTER)[0] \
+                 }}; \
+                 LET $bought = IF $after {{ \
+                     IF $held {{ UPSERT $held SET expires_at = $row.expires_at }}; \
+                     {RECORD_CHANGE}; \
+                     (CREATE ONLY inventory SET user_id = $row.user_id, item = $row.item, \
+                         kind = $row.kind, price = $row.price, expires_at = $row.expires_at, \
+                         uses_left = $row.uses_left, trigger = $row.trigger, reply = $row.reply \
+                         RETURN VALUE id) \
+                 }}; \
+                 RETURN {{ \
+                     owned: $owned, trigger_taken: $taken, bought: $bought, balance: $after.balance \
+                 }}; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("row", row.clone()))
+            .bind(("exclusive", exclusive))
+            .bind(("account", db_record_id("bank_account", buyer)?))
+            .bind(("freeze", db_record_id("bank_freeze", buyer)?))
+            .bind(("amount", -row.price))
+            .bind(("reason", reason.to_owned()))
+            .bind(("gambling_net", None::<i64>))
+            .await?
+            .check()?
+            // BEGIN and the five LETs come first.
+            .take(6)?;
+        let charged = charged.ok_or_else(|| eyre!("shop charge returned nothing"))?;
+
+        if let Some(expires_at) = charged.owned.into_iter().max() {
+            return Ok(Charge::Refused(Purchase::AlreadyOwned { expires_at }));
+        }
+        if charged.trigger_taken {
+            return Ok(Charge::Refused(Purchase::TriggerTaken));
+        }
+        if let (Some(bought), Some(balance)) = (charged.bought, charged.balance) {
+            return Ok(Charge::Done { bought, balance });
+        }
+        if Bank::freeze(buyer).await?.is_some() {
+            return Ok(Charge::Refused(Purchase::Frozen));
+        }
+
+        Ok(Charge::Refused(Purchase::TooPoor {
+            price: row.price,
+            balance: Bank::get(buyer).await?.balance,
+        }))
+    }
+
+    /// Undoes [`Shop::charge`], for when the item couldn't be handed over.
+    async fn refund(
+        buyer: UserId,
+        bought: RecordId,
+        row: &NewInventoryRow,
+        reason: String,
+    ) -> Result<BankAccount> {
+        let mut response = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 DELETE $bought; \
+                 IF $trigger {{ DELETE type::record('auto_reply_trigger', $trigger) }}; \
+                 {RECORD_CHANGE}; \
+                 UPSERT ONLY $account SET balance += $amount RETURN AFTER; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("bought", bought))
+            .bind(("trigger", row.trigger.clone()))
+            .bind(("account", db_record_id("bank_account", buyer)?))
+            .bind(("amount", row.price))
+            .bind(("reason", reason))
+            .bind(("gambling_net", None::<i64>))
+            .await?
+            .check()?;
+
+        response
+            .take::<Option<BankAccount>>(4)?
+            .ok_or_else(|| eyre!("bank account UPSERT returned no record"))
+    }
+
+    /// Applies a role or nickname on Discord, and schedules putting it back once it runs out.
+    ///
+    /// If that can't be scheduled, it's put back straight away.
+    async fn grant(
+        ctx: &Context,
+        guild_id: GuildId,
+        buyer: UserId,
+        kind: ShopItemKind,
+        order: Order,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let undo = match (kind, order) {
+            (ShopItemKind::Role { role_id, .. }, _) => {
+                ctx.http
+                    .add_member_role(guild_id, buyer, role_id, Some("Bought from the shop"))
+                    .await?;
+
+                ScheduledAction::RemoveRole {
+                    guild_id,
+                    user_id: buyer,
+                    role_id,
+                }
+            }
+            (ShopItemKind::Nickname { .. }, Order::Nickname(nickname)) => {
+                let previous = guild_id.member(ctx, buyer).await?.nick.unwrap_or_default();
+
+                guild_id
+                    .edit_member(ctx, buyer, EditMember::new().nickname(nickname))
+                    .await?;
+
+                ScheduledAction::SetNickname {
+                    guild_id,
+                    user_id: buyer,
+                    nickname: previous,
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        let Some(expires_at) = expires_at else {
+            return Ok(());
+        };
+
+        if let Err(error) = schedule(ctx, expires_at, undo.clone()).await {
+            undo.run(ctx).await.trace_err_ok();
+            return Err(error);
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads what's needed to act on shop items bought before the last restart.
+pub async fn setup_shop() -> Result<()> {
+    Shop::load_replies().await
+}
+
+/// Replies to a message that contains someone's bought trigger.
+pub async fn reply_to_bought_triggers(ctx: &Context, message: &Message) -> Result<()> {
+    if message.author.bot || message.guild_id.is_none() {
+        return Ok(());
+    }
+
+    let Some(reply) = BOUGHT_REPLIES.lock().reply_to(&message.content, Utc::now()) else {
+        return Ok(());
+    };
+
+    message
+        .channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(reply)
+                .reference_message(message)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Listing {
+        shop: Vec<ShopItem>,
+    }
+
+    #[test]
+    fn shop_items_are_read_from_the_config() {
+        let Listing { shop } = toml::from_str(
+            r#"
+            [[shop]]
+            name = "Purple"
+            description = "A purple name for a week"
+            price = 500
+            kind = "role"
+            role_id = 1234
+            duration_seconds = 604800
+
+            [[shop]]
+            name = "Nickname"
+            description = "Pick your own nickname for a day"
+            price = 100
+            kind = "nickname"
+
+            [[shop]]
+            name = "Clips"
+            description = "Three clips that skip the cooldown"
+            price = 50
+            kind = "clip_that_uses"
+            uses = 3
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            shop.iter().map(|item| item.kind).collect::<Vec<_>>(),
+            [
+                ShopItemKind::Role {
+                    role_id: RoleId::new(1234),
+                    duration: Some(Duration::from_secs(604_800)),
+                },
+                ShopItemKind::Nickname {
+                    duration: one_day()
+                },
+                ShopItemKind::ClipThatUses { uses: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn bought_triggers_reply_once_per_cooldown_until_they_expire() {
+        let now = Utc::now();
+        let mut replies = BoughtReplies(vec![BoughtReply {
+            trigger: normalize_trigger(" Skill Issue "),
+            reply: String::from("certified"),
+            expires_at: now + TimeDelta::hours(1),
+            last_sent_at: None,
+        }]);
+
+        assert_eq!(replies.reply_to("hello", now), None);
+        assert_eq!(
+            replies.reply_to("that's a SKILL ISSUE", now).as_deref(),
+            Some("certified")
+        );
+        assert_eq!(replies.reply_to("skill issue", now), None);
+
+        let later = now + AUTO_REPLY_COOLDOWN;
+        assert_eq!(
+            replies.reply_to("skill issue", later).as_deref(),
+            Some("certified")
+        );
+
+        let expired = now + TimeDelta::hours(2);
+        assert_eq!(replies.reply_to("skill issue", expired), None);
+        assert!(replies.0.is_empty());
+    }
+
+    async fn charge(buyer: UserId, row: &NewInventoryRow) -> Charge {
+        let exclusive = row.kind != InventoryKind::ClipThatUses;
+        Shop::charge(buyer, row, exclusive, "Test purchase")
+            .await
+            .unwrap()
+    }
+
+    fn trigger_row(buyer: UserId, trigger: &str) -> NewInventoryRow {
+        NewInventoryRow {
+            user_id: db_id(buyer).unwrap(),
+            item: String::from("Trigger"),
+            kind: InventoryKind::AutoReply,
+            price: 100,
+            expires_at: Some(Utc::now() + TimeDelta::hours(1)),
+            uses_left: None,
+            trigger: Some(String::from(trigger)),
+            reply: Some(String::from("hi")),
+        }
+    }
+
+    pub(crate) async fn assert_inventory_is_persisted() {
+        let owner = UserId::new(99_901);
+        let expires_at = Utc::now() + TimeDelta::hours(1);
+
+        Bank::change(owner, 1000, String::from("Shop test funds"))
+            .await
+            .unwrap();
+
+        for row in [
+            NewInventoryRow {
+                user_id: db_id(owner).unwrap(),
+                item: String::from("Clips"),
+                kind: InventoryKind::ClipThatUses,
+                price: 50,
+                expires_at: None,
+                uses_left: Some(1),
+                trigger: None,
+                reply: None,
+            },
+            NewInventoryRow {
+                user_id: db_id(owner).unwrap(),
+                item: String::from("Trigger"),
+                kind: InventoryKind::AutoReply,
+                price: 100,
+                expires_at: Some(expires_at),
+                uses_left: None,
+                trigger: Some(String::from("kingfisher test")),
+                reply: Some(String::from("hi")),
+            },
+            NewInventoryRow {
+                user_id: db_id(owner).unwrap(),
+                item: String::from("Old trigger"),
+                kind: InventoryKind::AutoReply,
+                price: 100,
+                expires_at: Some(Utc::now() - TimeDelta::hours(1)),
+                uses_left: None,
+                trigger: Some(String::from("kingfisher old")),
+                reply: Some(String::from("bye")),
+            },
+        ] {
+            assert!(matches!(charge(owner, &row).await, Charge::Done { .. }));
+        }
+        assert_eq!(Bank::get(owner).await.unwrap().balance, 1000 - 250);
+
+        let Charge::Refused(Purchase::AlreadyOwned {
+            expires_at: Some(owned_until),
+        }) = charge(owner, &trigger_row(owner, "kingfisher other")).await
+        else {
+            panic!("an unexpired item can't be bought twice");
+        };
+        assert_eq!(owned_until.timestamp(), expires_at.timestamp());
+
+        Shop::load_replies().await.unwrap();
+        let now = Utc::now();
+        assert_eq!(
+            BOUGHT_REPLIES
+                .lock()
+                .reply_to("kingfisher test", now)
+                .as_deref(),
+            Some("hi")
+        );
+        assert_eq!(BOUGHT_REPLIES.lock().reply_to("kingfisher old", now), None);
+
+        let items = Shop::inventory(owner).await.unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.item.as_str())
+                .collect::<Vec<_>>(),
+            ["Trigger", "Clips"]
+        );
+
+        assert!(Shop::use_clip(owner).await.unwrap());
+        assert!(!Shop::use_clip(owner).await.unwrap());
+
+        let items = Shop::inventory(owner).await.unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.item.as_str())
+                .collect::<Vec<_>>(),
+            ["Trigger"],
+            "used up items leave the inventory"
+        );
+
+        let other = UserId::new(99_902);
+        Bank::change(other, 1000, String::from("Shop test funds"))
+            .await
+            .unwrap();
+        assert_eq!(
+            charge(other, &trigger_row(other, "kingfisher test")).await,
+            Charge::Refused(Purchase::TriggerTaken)
+        );
+        assert!(matches!(
+            charge(other, &trigger_row(other, "kingfisher old")).await,
+            Charge::Done { .. }
+        ));
+    }
+
+    pub(crate) async fn assert_concurrent_buys_charge_once() {
+        let buyer = UserId::new(99_903);
+        Bank::change(buyer, 1000, String::from("Shop test funds"))
+            .await
+            .unwrap();
+
+        // A buy that loses the race can also fail to commit, which charges nothing either.
+        let row = trigger_row(buyer, "kingfisher race");
+        let charges = futures::future::join_all(
+            (0..5).map(|_| Shop::charge(buyer, &row, true, "Test purchase")),
+        )
+        .await;
+        let bought = charges
+            .iter()
+            .filter(|charge| matches!(charge, Ok(Charge::Done { .. })))
+            .count();
+        assert_eq!(bought, 1, "an item can't be bought twice at once");
+        assert_eq!(Bank::get(buyer).await.unwrap().balance, 900);
+
+        let buyers = (99_904..99_909).map(UserId::new).collect::<Vec<_>>();
+        for &buyer in &buyers {
+            Bank::change(buyer, 1000, String::from("Shop test funds"))
+                .await
+                .unwrap();
+        }
+
+        let rows = buyers
+            .iter()
+            .map(|&buyer| trigger_row(buyer, "kingfisher shared"))
+            .collect::<Vec<_>>();
+        let charges = futures::future::join_all(
+            buyers
+                .iter()
+                .zip(&rows)
+                .map(|(&buyer, row)| Shop::charge(buyer, row, true, "Test purchase")),
+        )
+        .await;
+        let winners = buyers
+            .iter()
+            .zip(&charges)
+            .filter_map(|(buyer, charge)| match charge {
+                Ok(Charge::Done { bought, .. }) => Some((*buyer, bought.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let [(winner, bought)] = winners.as_slice() else {
+            panic!("a trigger can't go to two buyers, got {charges:?}");
+        };
+
+        let row = trigger_row(*winner, "kingfisher shared");
+        let refunded = Shop::refund(*winner, bought.clone(), &row, String::from("Test refund"))
+            .await
+            .unwrap();
+        assert_eq!(refunded.balance, 1000);
+        assert!(
+            Shop::inventory(*winner).await.unwrap().is_empty(),
+            "a refund takes the item back"
+        );
+
+        let loser = buyers.iter().find(|&buyer| buyer != winner).unwrap();
+        assert!(
+            matches!(
+                charge(*loser, &trigger_row(*loser, "kingfisher shared")).await,
+                Charge::Done { .. }
+            ),
+            "a refund frees the trigger"
+        );
+    }
+}
@@ -1,17 +1,34 @@
 use crate::{
+    channel_activity::{ActivitySummary, channel_activity},
     commands::is_stefan,
     data::{DB, PoiseContext},
     utils::SendReplyEphemeral,
+    utils::db_record_id,
 };
 use color_eyre::eyre::Result;
+use parking_lot::RwLock;
 use poise::serenity_prelude::{
-    ChannelId, Context, CreateAllowedMentions, CreateAttachment, CreateMessage, Mentionable,
-    Message, MessageId, MessageReference, MessageReferenceKind, Reaction, ReactionType,
+    ChannelId, Context, CreateAllowedMentions, CreateAttachment, CreateEmbed, CreateEmbedAuthor,
+    CreateMessage, Mentionable, Message, MessageId, MessageReference, MessageReferenceKind,
+    Reaction, ReactionType,
 };
 use serde::Deserialize;
-use surrealdb::types::RecordId;
+use serde_with::{DurationSeconds, serde_as};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use surrealdb::types::{RecordId, SurrealValue};
 use tokio::sync::Mutex;
 
+/// The starboards loaded from the `starboard` table.
+static STARBOARDS: LazyLock<RwLock<Vec<Arc<Starboard>>>> =
+    LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// How old a message can be before it can't be starboarded, unless a starboard says otherwise.
+pub const DEFAULT_MAX_MESSAGE_AGE: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+#[serde_as]
 #[derive(Deserialize)]
 pub struct Starboard {
     pub reaction_count: u64,
@@ -19,11 +36,233 @@ pub struct Starboard {
     pub banned_reactions: Option<Vec<String>>,
     pub channel_id: u64,
     pub ignored_channel_ids: Option<Vec<u64>>,
+    /// Scales `reaction_count` with how busy the source channel has been.
+    pub activity_threshold: Option<ActivityThreshold>,
+    /// Fixed thresholds for specific source channels.
+    ///
+    /// These win over both `reaction_count` and `activity_threshold`.
+    pub channel_overrides: Option<Vec<ChannelThresholdOverride>>,
+    /// How starboarded messages are posted.
+    #[serde(default)]
+    pub format: StarboardFormat,
+    /// Messages older than this are ignored. Defaults to [`DEFAULT_MAX_MESSAGE_AGE`].
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    #[serde(default)]
+    pub max_message_age: Option<Duration>,
     #[serde(skip)]
     sequential_message_lock: Mutex<()>,
 }
 
+/// How a starboard is stored in the `starboard` table.
+#[derive(Clone, Debug, Deserialize, SurrealValue)]
+pub struct StarboardRecord {
+    pub sending_channel_id: u64,
+    pub reaction_count: u64,
+    pub banned_reactions: Option<Vec<String>>,
+    pub ignored_channel_ids: Option<Vec<u64>>,
+    pub activity_threshold: Option<ActivityThreshold>,
+    pub channel_overrides: Option<Vec<ChannelThresholdOverride>>,
+    pub format: StarboardFormat,
+    pub max_message_age: Option<Duration>,
+}
+
+impl StarboardRecord {
+    fn record_id(sending_channel_id: u64) -> Result<RecordId> {
+        db_record_id("starboard", sending_channel_id)
+    }
+
+    /// Creates or replaces the starboard sending to `sending_channel_id`.
+    ///
+    /// Call [`Starboard::reload`] afterwards for it to take effect.
+    pub async fn save(&self) -> Result<()> {
+        DB.query("UPSERT $starboard CONTENT $content")
+            .bind(("starboard", Self::record_id(self.sending_channel_id)?))
+            .bind(("content", self.clone()))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+}
+
+impl From<StarboardRecord> for Starboard {
+    fn from(record: StarboardRecord) -> Self {
+        Self {
+            reaction_count: record.reaction_count,
+            banned_reactions: record.banned_reactions,
+            channel_id: record.sending_channel_id,
+            ignored_channel_ids: record.ignored_channel_ids,
+            activity_threshold: record.activity_threshold,
+            channel_overrides: record.channel_overrides,
+            format: record.format,
+            max_message_age: record.max_message_age,
+            sequential_message_lock: Mutex::new(()),
+        }
+    }
+}
+
+/// How a starboard posts the messages it picks up.
+#[derive(
+    Deserialize, SurrealValue, poise::ChoiceParameter, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum StarboardFormat {
+    /// A mention header, a Discord forward of the message and the emoji on its own.
+    #[default]
+    Forward,
+    /// A single embed with the author, source, content, first image and count.
+    Embed,
+}
+
+/// What counts as activity for [`ActivityThreshold`].
+#[derive(
+    Deserialize, SurrealValue, poise::ChoiceParameter, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum ActivityMetric {
+    /// Messages sent in the channel.
+    #[default]
+    Messages,
+    /// Distinct people who sent messages in the channel.
+    Authors,
+}
+
+fn default_activity_window_hours() -> u64 {
+    24
+}
+
+/// Makes the threshold follow the source channel's recent activity.
+///
+/// A channel exactly at `baseline` needs `reaction_count` reactions. Busier
+/// channels need more and quieter ones fewer, growing with the square root of
+/// the ratio so the memes channel doesn't become impossible.
+#[derive(Deserialize, SurrealValue, Clone, Copy, Debug)]
+pub struct ActivityThreshold {
+    #[serde(default)]
+    pub metric: ActivityMetric,
+    #[serde(default = "default_activity_window_hours")]
+    pub window_hours: u64,
+    pub baseline: u64,
+    pub min_reaction_count: u64,
+    pub max_reaction_count: u64,
+}
+
+impl ActivityThreshold {
+    fn scale(&self, reaction_count: u64, activity: ActivitySummary) -> u64 {
+        let activity = match self.metric {
+            ActivityMetric::Messages => activity.messages,
+            ActivityMetric::Authors => activity.unique_authors,
+        };
+
+        let ratio = (activity as f64 / self.baseline.max(1) as f64).sqrt();
+        let scaled = (reaction_count as f64 * ratio).round() as u64;
+
+        scaled
+            .max(self.min_reaction_count)
+            .min(self.max_reaction_count)
+    }
+}
+
+#[derive(Deserialize, SurrealValue, Clone, Copy, Debug)]
+pub struct ChannelThresholdOverride {
+    pub channel_id: u64,
+    pub reaction_count: u64,
+}
+
 impl Starboard {
+    /// All currently configured starboards.
+    pub fn all() -> Vec<Arc<Starboard>> {
+        STARBOARDS.read().clone()
+    }
+
+    /// The starboard sending to `sending_channel_id`, if there is one.
+    pub fn find(sending_channel_id: ChannelId) -> Option<Arc<Starboard>> {
+        STARBOARDS
+            .read()
+            .iter()
+            .find(|starboard| starboard.channel_id == sending_channel_id.get())
+            .cloned()
+    }
+
+    /// Re-reads every starboard from the `starboard` table.
+    pub async fn reload() -> Result<()> {
+        let records: Vec<StarboardRecord> = DB
+            .query("SELECT * OMIT id FROM starboard ORDER BY sending_channel_id")
+            .await?
+            .check()?
+            .take(0)?;
+
+        *STARBOARDS.write() = records
+            .into_iter()
+            .map(|record| Arc::new(record.into()))
+            .collect();
+
+        Ok(())
+    }
+
+    /// Deletes the starboard sending to `sending_channel_id`, returning whether one existed.
+    ///
+    /// Call [`Starboard::reload`] afterwards for it to take effect.
+    pub async fn delete(sending_channel_id: ChannelId) -> Result<bool> {
+        let deleted: Option<StarboardRecord> = DB
+            .query("DELETE ONLY $starboard RETURN BEFORE")
+            .bind((
+                "starboard",
+                StarboardRecord::record_id(sending_channel_id.get())?,
+            ))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(deleted.is_some())
+    }
+
+    /// Copies the starboards from the config file into the `starboard` table.
+    ///
+    /// Only ever runs once, so starboards deleted with `/starboard delete` stay deleted.
+    pub async fn import_from_config(starboards: &[Starboard]) -> Result<()> {
+        let records: Vec<StarboardRecord> = starboards.iter().map(Starboard::to_record).collect();
+
+        for record in &records {
+            StarboardRecord::record_id(record.sending_channel_id)?;
+        }
+
+        DB.query(
+            "BEGIN TRANSACTION; \
+             IF !migration:starboard_config_import.exists() { \
+                 FOR $record IN $records { \
+                     UPSERT type::record('starboard', $record.sending_channel_id) CONTENT $record; \
+                 }; \
+                 CREATE migration:starboard_config_import; \
+             }; \
+             COMMIT TRANSACTION;",
+        )
+        .bind(("records", records))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    pub fn to_record(&self) -> StarboardRecord {
+        StarboardRecord {
+            sending_channel_id: self.channel_id,
+            reaction_count: self.reaction_count,
+            banned_reactions: self.banned_reactions.clone(),
+            ignored_channel_ids: self.ignored_channel_ids.clone(),
+            activity_threshold: self.activity_threshold,
+            channel_overrides: self.channel_overrides.clone(),
+            format: self.format,
+            max_message_age: self.max_message_age,
+        }
+    }
+
+    pub fn max_message_age(&self) -> Duration {
+        self.max_message_age.unwrap_or(DEFAULT_MAX_MESSAGE_AGE)
+    }
+
     #[tracing::instrument(level = "trace", skip(self, message), fields(message_link = %message.link()))]
     /// Checks the reaction threshold and configured channel/reaction exclusions.
     pub fn does_starboard_apply(&self, message: &Message, reaction: &Reaction) -> bool {
@@ -33,14 +272,27 @@ impl Starboard {
     }
 
     fn enough_reactions(&self, message: &Message, reaction: &Reaction) -> bool {
-        let reaction_type = &reaction.emoji;
-        let reaction_count = message
-            .reactions
+        reaction_count(message, &reaction.emoji) >= self.reaction_threshold(message.channel_id)
+    }
+
+    /// How many of one reaction a message in `channel_id` needs.
+    pub fn reaction_threshold(&self, channel_id: ChannelId) -> u64 {
+        if let Some(channel_override) = self
+            .channel_overrides
             .iter()
-            .find(|reaction| reaction.reaction_type == *reaction_type)
-            .map_or(0, |reaction| reaction.count);
+            .flatten()
+            .find(|channel_override| channel_override.channel_id == channel_id.get())
+        {
+            return channel_override.reaction_count;
+        }
 
-        reaction_count >= self.reaction_count
+        match &self.activity_threshold {
+            Some(activity_threshold) => activity_threshold.scale(
+                self.reaction_count,
+                channel_activity(channel_id, activity_threshold.window_hours),
+            ),
+            None => self.reaction_count,
+        }
     }
 
     fn is_allowed_reaction(&self, reaction: &Reaction) -> bool {
@@ -84,9 +336,10 @@ impl Starboard {
             .unwrap_or(false))
     }
 
+    /// Keeps a yeet ballot off every starboard, even across restarts.
     pub async fn ignore_message_permanently(message_id: MessageId) -> Result<()> {
         let message_id = i64::from(message_id);
-        DB.query("create $message")
+        DB.query("create $message set kind = 'yeet'")
             .bind((
                 "message",
                 RecordId::new("starboard_recent_message", message_id),
@@ -109,6 +362,65 @@ impl Starboard {
         ctx: &Context,
         message: &Message,
         reaction: &ReactionType,
+    ) -> Result<()> {
+        match self.format {
+            StarboardFormat::Forward => self.reply_forward(ctx, message, reaction).await,
+            StarboardFormat::Embed => self.reply_embed(ctx, message, reaction).await,
+        }
+    }
+
+    async fn reply_embed(
+        &self,
+        ctx: &Context,
+        message: &Message,
+        reaction: &ReactionType,
+    ) -> Result<()> {
+        let author =
+            CreateEmbedAuthor::new(message.author.display_name()).icon_url(message.author.face());
+
+        let mut embed = CreateEmbed::new()
+            .author(author)
+            .field(
+                "Source",
+                format!(
+                    "{} · [Jump to message]({})",
+                    message.channel_id.mention(),
+                    message.link()
+                ),
+                true,
+            )
+            .field(
+                "Reactions",
+                format!("{} {}", reaction, reaction_count(message, reaction)),
+                true,
+            )
+            .timestamp(message.timestamp);
+
+        if !message.content.is_empty() {
+            embed = embed.description(truncate_chars(&message.content, EMBED_DESCRIPTION_LIMIT));
+        }
+
+        if let Some(image) = message.attachments.iter().find(|attachment| {
+            attachment
+                .content_type
+                .as_deref()
+                .is_some_and(|content_type| content_type.starts_with("image/"))
+        }) {
+            embed = embed.image(&image.url);
+        }
+
+        ChannelId::new(self.channel_id)
+            .send_message(ctx, CreateMessage::new().embed(embed))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reply_forward(
+        &self,
+        ctx: &Context,
+        message: &Message,
+        reaction: &ReactionType,
     ) -> Result<()> {
         // Ensure that these two messages are back to back
         let _lock = self.sequential_message_lock.lock().await;
@@ -172,14 +484,43 @@ impl Starboard {
     }
 }
 
+/// Seeds the `starboard` table from the config file (only the first time) and loads it.
+pub async fn setup_starboards(legacy_starboards: &[Starboard]) -> Result<()> {
+    Starboard::import_from_config(legacy_starboards).await?;
+    Starboard::reload().await
+}
+
+const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+
+fn truncate_chars(text: &str, limit: usize) -> String {
+    if text.chars().nth(limit).is_none() {
+        return text.to_owned();
+    }
+
+    let end = text
+        .char_indices()
+        .nth(limit - 1)
+        .map_or(text.len(), |(end, _)| end);
+
+    format!("{}…", &text[..end])
+}
+
+/// How many of `reaction` the message currently has.
+pub(crate) fn reaction_count(message: &Message, reaction: &ReactionType) -> u64 {
+    message
+        .reactions
+        .iter()
+        .find(|message_reaction| message_reaction.reaction_type == *reaction)
+        .map_or(0, |message_reaction| message_reaction.count)
+}
+
 #[poise::command(
     prefix_command,
     check = is_stefan
 )]
 pub async fn debug_force_starboard(ctx: PoiseContext<'_>, message: Message) -> Result<()> {
     let emoji = ReactionType::Unicode("🧪".into());
-    let config = ctx.data().config.read().await;
-    for starboard in &config.starboards {
+    for starboard in Starboard::all() {
         starboard
             .reply(ctx.serenity_context(), &message, &emoji)
             .await?;
@@ -199,6 +540,44 @@ pub async fn debug_surrealdb(ctx: PoiseContext<'_>, query: Vec<String>) -> Resul
     Ok(())
 }
 
+#[cfg(test)]
+#[test]
+fn embed_description_is_truncated_on_char_boundaries() {
+    assert_eq!(truncate_chars("short", 10), "short");
+    assert_eq!(truncate_chars("ééééé", 3), "éé…");
+    assert_eq!(truncate_chars("abc", 3), "abc");
+    assert_eq!(truncate_chars("abcd", 3), "ab…");
+}
+
+#[cfg(test)]
+#[test]
+fn activity_threshold_scales_and_clamps() {
+    use crate::starboard::{ActivityMetric, ActivityThreshold};
+
+    let threshold = ActivityThreshold {
+        metric: ActivityMetric::Messages,
+        window_hours: 24,
+        baseline: 100,
+        min_reaction_count: 3,
+        max_reaction_count: 15,
+    };
+    let activity = |messages| ActivitySummary {
+        messages,
+        unique_authors: 0,
+    };
+
+    assert_eq!(threshold.scale(8, activity(100)), 8);
+    assert_eq!(threshold.scale(8, activity(400)), 15);
+    assert_eq!(threshold.scale(8, activity(25)), 4);
+    assert_eq!(threshold.scale(8, activity(0)), 3);
+
+    let by_authors = ActivityThreshold {
+        metric: ActivityMetric::Authors,
+        ..threshold
+    };
+    assert_eq!(by_authors.scale(8, activity(400)), 3);
+}
+
 #[cfg(test)]
 #[tokio::test]
 async fn test_db_setup() {
@@ -232,5 +611,117 @@ async fn test_db_setup() {
             .unwrap()
     );
 
+    Starboard::ignore_message_permanently(MessageId::from(3))
+        .await
+        .unwrap();
+    assert!(
+        Starboard::has_recent_message(MessageId::from(3))
+            .await
+            .unwrap(),
+        "yeet ballots must be excluded from every starboard"
+    );
+
     crate::economy::tests::assert_economy_is_persisted_and_ranked().await;
+    crate::economy::tests::assert_transfers_never_overdraw().await;
+    crate::economy::tests::assert_concurrent_debits_never_overdraw().await;
+    crate::economy::tests::assert_legacy_history_is_migrated_once().await;
+    crate::economy::tests::assert_leaderboards_rank_history().await;
+    crate::economy::tests::assert_admins_can_freeze_reverse_and_export().await;
+    crate::bank_audit::tests::assert_admin_actions_are_audited().await;
+    crate::bets::tests::assert_pools_pay_out_parimutuel().await;
+    crate::casino_games::tests::assert_held_wagers_survive_restarts().await;
+    crate::income::tests::assert_income_is_persisted().await;
+    crate::income::tests::assert_concurrent_claims_pay_once().await;
+    crate::earnings::tests::assert_earnings_are_seeded_and_paid().await;
+    crate::shop::tests::assert_inventory_is_persisted().await;
+    crate::shop::tests::assert_concurrent_buys_charge_once().await;
+    crate::starboard_history::tests::assert_starboard_history_is_queryable().await;
+    crate::yeet_settings::tests::assert_yeet_settings_are_seeded_and_editable().await;
+    crate::yeet_votes::tests::assert_yeet_votes_survive_and_resolve_once().await;
+    crate::yeet_stats::tests::assert_yeet_stats_read_decided_votes().await;
+    crate::timeout_history::tests::assert_timeouts_are_recorded_per_cause().await;
+    crate::yeet_charges::tests::assert_yeet_charges_are_per_user().await;
+    crate::yeet_parries::tests::assert_parries_are_used_once().await;
+    crate::yeet_eligibility::tests::assert_eligibility_is_read_from_the_db().await;
+    crate::yeet_perks::tests::assert_perks_cost_bank_balance().await;
+    crate::yeet_perks::tests::assert_concurrent_buys_charge_once().await;
+    crate::scheduled_actions::tests::assert_scheduled_actions_run_once().await;
+    assert_starboards_are_imported_once_and_editable().await;
+}
+
+#[cfg(test)]
+async fn assert_starboards_are_imported_once_and_editable() {
+    use crate::starboard::{ActivityMetric, ActivityThreshold, StarboardFormat};
+    use poise::serenity_prelude::ChannelId;
+
+    let from_config: Vec<Starboard> = toml::from_str::<toml::Table>(
+        "[[starboards]]\n\
+         channel_id = 95001\n\
+         reaction_count = 8\n\
+         banned_reactions = [\"🫂\"]\n\
+         ignored_channel_ids = [95002]\n\
+         [starboards.activity_threshold]\n\
+         baseline = 100\n\
+         min_reaction_count = 3\n\
+         max_reaction_count = 15\n\
+         [[starboards]]\n\
+         channel_id = 95003\n\
+         reaction_count = 4\n\
+         format = \"embed\"\n",
+    )
+    .unwrap()["starboards"]
+        .clone()
+        .try_into()
+        .unwrap();
+
+    Starboard::import_from_config(&from_config).await.unwrap();
+    Starboard::reload().await.unwrap();
+    assert_eq!(Starboard::all().len(), 2);
+
+    let imported = Starboard::find(ChannelId::new(95001)).unwrap();
+    assert_eq!(imported.reaction_count, 8);
+    assert_eq!(imported.ignored_channel_ids, Some(vec![95002]));
+    assert_eq!(
+        imported.activity_threshold.unwrap().metric,
+        ActivityMetric::Messages
+    );
+    assert_eq!(
+        Starboard::find(ChannelId::new(95003)).unwrap().format,
+        StarboardFormat::Embed
+    );
+
+    assert!(Starboard::delete(ChannelId::new(95003)).await.unwrap());
+    assert!(!Starboard::delete(ChannelId::new(95003)).await.unwrap());
+
+    Starboard::import_from_config(&from_config).await.unwrap();
+    Starboard::reload().await.unwrap();
+    assert_eq!(Starboard::all().len(), 1, "the import must only run once");
+
+    assert_eq!(
+        imported.max_message_age(),
+        crate::starboard::DEFAULT_MAX_MESSAGE_AGE
+    );
+
+    let mut record = imported.to_record();
+    record.reaction_count = 5;
+    record.max_message_age = Some(std::time::Duration::from_secs(60 * 60 * 24 * 30));
+    record.format = StarboardFormat::Embed;
+    record.activity_threshold = Some(ActivityThreshold {
+        metric: ActivityMetric::Authors,
+        ..record.activity_threshold.unwrap()
+    });
+    record.save().await.unwrap();
+    Starboard::reload().await.unwrap();
+
+    let edited = Starboard::find(ChannelId::new(95001)).unwrap();
+    assert_eq!(edited.reaction_count, 5);
+    assert_eq!(edited.format, StarboardFormat::Embed);
+    assert_eq!(
+        edited.max_message_age(),
+        std::time::Duration::from_secs(60 * 60 * 24 * 30)
+    );
+    assert_eq!(
+        edited.activity_threshold.unwrap().metric,
+        ActivityMetric::Authors
+    );
 }
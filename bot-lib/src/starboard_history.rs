@@ -0,0 +1,203 @@
+use crate::{
+    data::DB,
+    utils::{db_id, db_record_id},
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use poise::serenity_prelude::{ChannelId, Message, MessageId, ReactionType, UserId};
+use serde::Deserialize;
+use std::time::Duration;
+use surrealdb::types::SurrealValue;
+
+/// A message that made it onto a starboard.
+#[derive(Debug, Deserialize, SurrealValue)]
+pub struct StarboardEntry {
+    pub message_id: i64,
+    pub channel_id: i64,
+    pub author_id: i64,
+    pub reaction: Option<String>,
+    pub reaction_count: Option<i64>,
+    pub time: DateTime<Utc>,
+}
+
+impl StarboardEntry {
+    pub fn message_id(&self) -> MessageId {
+        MessageId::new(self.message_id as u64)
+    }
+
+    pub fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    pub fn author_id(&self) -> UserId {
+        UserId::new(self.author_id as u64)
+    }
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct AuthorCount {
+    author_id: i64,
+    entries: u64,
+}
+
+#[derive(Debug, Default, Deserialize, SurrealValue)]
+pub struct AuthorStats {
+    pub entries: u64,
+    pub reactions: u64,
+}
+
+pub struct StarboardHistory;
+
+impl StarboardHistory {
+    /// Remember that `message` was posted to the starboard sending to `starboard_channel_id`.
+    pub async fn record(
+        starboard_channel_id: u64,
+        message: &Message,
+        reaction: &ReactionType,
+        reaction_count: u64,
+    ) -> Result<()> {
+        DB.query(
+            "UPSERT $message SET author_id = $author_id, channel_id = $channel_id; \
+             RELATE $starboard->starboarded->$message \
+             SET reaction = $reaction, reaction_count = $reaction_count;",
+        )
+        .bind(("message", db_record_id("message", message.id)?))
+        .bind((
+            "starboard",
+            db_record_id("starboard", starboard_channel_id)?,
+        ))
+        .bind(("author_id", db_id(message.author.id)?))
+        .bind(("channel_id", db_id(message.channel_id)?))
+        .bind(("reaction", reaction.to_string()))
+        .bind(("reaction_count", reaction_count))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// The most starboarded authors, optionally only counting the last `window`.
+    pub async fn top_authors(window: Option<Duration>, limit: u64) -> Result<Vec<(UserId, u64)>> {
+        let counts: Vec<AuthorCount> = DB
+            .query(
+                "SELECT out.author_id AS author_id, count() AS entries FROM starboarded \
+                 WHERE kind = 'starboard' AND out.author_id != NONE \
+                 AND ($window = NONE OR time > time::now() - $window) \
+                 GROUP BY author_id ORDER BY entries DESC LIMIT $limit",
+            )
+            .bind(("window", window))
+            .bind(("limit", limit))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(counts
+            .into_iter()
+            .map(|count| (UserId::new(count.author_id as u64), count.entries))
+            .collect())
+    }
+
+    /// A random past entry, for resurfacing.
+    pub async fn random_entry() -> Result<Option<StarboardEntry>> {
+        Ok(DB
+            .query(
+                "SELECT record::id(out) AS message_id, out.channel_id AS channel_id, \
+                 out.author_id AS author_id, reaction, reaction_count, time FROM starboarded \
+                 WHERE kind = 'starboard' AND out.author_id != NONE \
+                 ORDER BY rand() LIMIT 1",
+            )
+            .await?
+            .check()?
+            .take(0)?)
+    }
+
+    pub async fn author_stats(author_id: UserId) -> Result<AuthorStats> {
+        let stats: Option<AuthorStats> = DB
+            .query(
+                "SELECT count() AS entries, math::sum(reaction_count ?? 0) AS reactions \
+                 FROM starboarded \
+                 WHERE kind = 'starboard' AND out.author_id = $author_id GROUP ALL",
+            )
+            .bind(("author_id", db_id(author_id)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(stats.unwrap_or_default())
+    }
+
+    /// The author's entries with the most reactions, best first.
+    pub async fn best_entries(author_id: UserId, limit: u64) -> Result<Vec<StarboardEntry>> {
+        Ok(DB
+            .query(
+                "SELECT record::id(out) AS message_id, out.channel_id AS channel_id, \
+                 out.author_id AS author_id, reaction, reaction_count, time FROM starboarded \
+                 WHERE kind = 'starboard' AND out.author_id = $author_id \
+                 ORDER BY reaction_count DESC LIMIT $limit",
+            )
+            .bind(("author_id", db_id(author_id)?))
+            .bind(("limit", limit))
+            .await?
+            .check()?
+            .take(0)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::StarboardHistory;
+    use poise::serenity_prelude::{Message, ReactionType, UserId};
+    use std::time::Duration;
+
+    fn message(id: u64, author_id: u64) -> Message {
+        let mut message = Message::default();
+        message.id = id.into();
+        message.channel_id = 93_000.into();
+        message.author.id = author_id.into();
+        message
+    }
+
+    pub(crate) async fn assert_starboard_history_is_queryable() {
+        let star = ReactionType::Unicode("⭐".to_owned());
+        let prolific = UserId::new(92_001);
+        let occasional = UserId::new(92_002);
+
+        for (message_id, author_id, count) in [
+            (92_101, prolific, 8),
+            (92_102, prolific, 12),
+            (92_103, occasional, 9),
+        ] {
+            StarboardHistory::record(94_000, &message(message_id, author_id.get()), &star, count)
+                .await
+                .unwrap();
+        }
+
+        let top = StarboardHistory::top_authors(None, 10).await.unwrap();
+        assert_eq!(top[0], (prolific, 2));
+        assert_eq!(top[1], (occasional, 1));
+
+        let recent = StarboardHistory::top_authors(Some(Duration::from_secs(3600)), 10)
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 2);
+
+        let stats = StarboardHistory::author_stats(prolific).await.unwrap();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.reactions, 20);
+
+        let best = StarboardHistory::best_entries(prolific, 1).await.unwrap();
+        assert_eq!(best[0].message_id, 92_102);
+        assert_eq!(best[0].reaction.as_deref(), Some("⭐"));
+
+        let random = StarboardHistory::random_entry().await.unwrap().unwrap();
+        assert_eq!(random.channel_id, 93_000);
+
+        assert_eq!(
+            StarboardHistory::author_stats(UserId::new(92_999))
+                .await
+                .unwrap()
+                .entries,
+            0
+        );
+    }
+}
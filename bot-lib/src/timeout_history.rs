@@ -0,0 +1,232 @@
+//! Every timeout the bot hands out, kept in the `timeout_event` table.
+
+use crate::{data::DB, yeet_votes::user_record_id};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, eyre};
+use poise::serenity_prelude::{User, UserId};
+use serde::Deserialize;
+use std::time::Duration;
+use surrealdb::types::SurrealValue;
+
+/// Why someone was timed out. Stored as the `cause` string of a `timeout_event`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutCause {
+    Yeet,
+    ModAbuse,
+    SelfTimeout,
+    MessageLimit,
+}
+
+impl TimeoutCause {
+    pub const ALL: [Self; 4] = [
+        Self::Yeet,
+        Self::ModAbuse,
+        Self::SelfTimeout,
+        Self::MessageLimit,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Yeet => "yeet",
+            Self::ModAbuse => "mod abuse",
+            Self::SelfTimeout => "self timeout",
+            Self::MessageLimit => "message limit",
+        }
+    }
+
+    fn from_str(cause: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == cause)
+    }
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct TimeoutEventRow {
+    start_time: DateTime<Utc>,
+    duration: Duration,
+    caused_by: i64,
+    cause: String,
+}
+
+/// One past timeout.
+#[derive(Debug, Clone)]
+pub struct TimeoutEvent {
+    pub start_time: DateTime<Utc>,
+    pub duration: Duration,
+    pub caused_by: UserId,
+    pub cause: TimeoutCause,
+}
+
+impl TryFrom<TimeoutEventRow> for TimeoutEvent {
+    type Error = color_eyre::Report;
+
+    fn try_from(row: TimeoutEventRow) -> Result<Self> {
+        Ok(Self {
+            start_time: row.start_time,
+            duration: row.duration,
+            caused_by: UserId::new(row.caused_by as u64),
+            cause: TimeoutCause::from_str(&row.cause)
+                .ok_or_else(|| eyre!("Unknown timeout cause {:?}", row.cause))?,
+        })
+    }
+}
+
+/// How often and for how long someone was timed out for one cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CauseTotal {
+    pub cause: TimeoutCause,
+    pub count: u64,
+    pub duration: Duration,
+}
+
+pub struct TimeoutHistory;
+
+impl TimeoutHistory {
+    /// Remember that `user` was just timed out for `duration`, because of `caused_by`.
+    pub async fn record(
+        user: &User,
+        caused_by: UserId,
+        cause: TimeoutCause,
+        duration: Duration,
+    ) -> Result<()> {
+        DB.query(
+            "LET $event = (CREATE ONLY timeout_event SET \
+                 user_id = $user, start_time = time::now(), duration = $duration, \
+                 caused_by = $caused_by, cause = $cause); \
+             UPSERT $user SET name = $name, \
+                 timeout_history = array::append(timeout_history ?? [], $event.id);",
+        )
+        .bind(("user", user_record_id(user.id)?))
+        .bind(("name", user.name.clone()))
+        .bind(("caused_by", user_record_id(caused_by)?))
+        .bind(("cause", cause.as_str()))
+        .bind(("duration", duration))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// The user's most recent timeouts, newest first.
+    pub async fn recent(user_id: UserId, limit: u64) -> Result<Vec<TimeoutEvent>> {
+        let rows: Vec<TimeoutEventRow> = DB
+            .query(
+                "SELECT start_time, duration, record::id(caused_by) AS caused_by, cause \
+                 FROM timeout_event WHERE user_id = $user \
+                 ORDER BY start_time DESC LIMIT $limit",
+            )
+            .bind(("user", user_record_id(user_id)?))
+            .bind(("limit", limit))
+            .await?
+            .check()?
+            .take(0)?;
+
+        rows.into_iter().map(TimeoutEvent::try_from).collect()
+    }
+
+    /// The user's latest timeout for `cause`.
+    pub async fn last(user_id: UserId, cause: TimeoutCause) -> Result<Option<TimeoutEvent>> {
+        let row: Option<TimeoutEventRow> = DB
+            .query(
+                "SELECT start_time, duration, record::id(caused_by) AS caused_by, cause \
+                 FROM timeout_event WHERE user_id = $user AND cause = $cause \
+                 ORDER BY start_time DESC LIMIT 1",
+            )
+            .bind(("user", user_record_id(user_id)?))
+            .bind(("cause", cause.as_str()))
+            .await?
+            .check()?
+            .take(0)?;
+
+        row.map(TimeoutEvent::try_from).transpose()
+    }
+
+    /// How many timeouts the user got per cause, and how long they added up to.
+    pub async fn totals(user_id: UserId) -> Result<Vec<CauseTotal>> {
+        let rows: Vec<TimeoutEventRow> = DB
+            .query(
+                "SELECT start_time, duration, record::id(caused_by) AS caused_by, cause \
+                 FROM timeout_event WHERE user_id = $user",
+            )
+            .bind(("user", user_record_id(user_id)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        let mut totals: Vec<CauseTotal> = Vec::new();
+
+        for event in rows {
+            let event = TimeoutEvent::try_from(event)?;
+
+            match totals.iter_mut().find(|total| total.cause == event.cause) {
+                Some(total) => {
+                    total.count += 1;
+                    total.duration += event.duration;
+                }
+                None => totals.push(CauseTotal {
+                    cause: event.cause,
+                    count: 1,
+                    duration: event.duration,
+                }),
+            }
+        }
+
+        totals.sort_by_key(|total| std::cmp::Reverse(total.count));
+
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{TimeoutCause, TimeoutHistory};
+    use crate::yeet_votes::tests::user;
+    use poise::serenity_prelude::UserId;
+    use std::time::Duration;
+
+    pub(crate) async fn assert_timeouts_are_recorded_per_cause() {
+        let victim = user(97_001);
+        let moderator = UserId::new(97_002);
+
+        for (caused_by, cause, seconds) in [
+            (moderator, TimeoutCause::ModAbuse, 60),
+            (moderator, TimeoutCause::ModAbuse, 120),
+            (victim.id, TimeoutCause::SelfTimeout, 30),
+            (UserId::new(97_003), TimeoutCause::Yeet, 300),
+        ] {
+            TimeoutHistory::record(&victim, caused_by, cause, Duration::from_secs(seconds))
+                .await
+                .unwrap();
+        }
+
+        let last_yeet = TimeoutHistory::last(victim.id, TimeoutCause::Yeet)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(last_yeet.caused_by, UserId::new(97_003));
+
+        let recent = TimeoutHistory::recent(victim.id, 2).await.unwrap();
+        assert_eq!(recent.len(), 2);
+        assert!(recent[0].start_time >= recent[1].start_time);
+
+        let totals = TimeoutHistory::totals(victim.id).await.unwrap();
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals[0].cause, TimeoutCause::ModAbuse);
+        assert_eq!(totals[0].count, 2);
+        assert_eq!(totals[0].duration, Duration::from_secs(180));
+
+        let history_len: Option<u64> = crate::data::DB
+            .query("RETURN array::len(user:97001.timeout_history)")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap();
+        assert_eq!(history_len, Some(4));
+
+        assert!(
+            TimeoutHistory::totals(UserId::new(97_999))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
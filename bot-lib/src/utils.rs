@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, eyre};
 use poise::CreateReply;
+use surrealdb::types::RecordId;
 
 use crate::data::PoiseContext;
 
@@ -30,3 +31,14 @@ impl SendReplyEphemeral for PoiseContext<'_> {
         Ok(())
     }
 }
+
+/// Discord IDs are `u64`s, but SurrealDB ints are `i64`s.
+pub fn db_id(id: impl Into<u64>) -> Result<i64> {
+    let id = id.into();
+    i64::try_from(id).map_err(|_| eyre!("Discord ID {id} does not fit in a SurrealDB int"))
+}
+
+/// The record in `table` keyed by a Discord ID.
+pub fn db_record_id(table: &str, id: impl Into<u64>) -> Result<RecordId> {
+    Ok(RecordId::new(table, db_id(id)?))
+}
@@ -0,0 +1,410 @@
+//! Per-user yeet charges, stored in `yeet_charge` and refilled lazily one at a time.
+
+use crate::{data::DB, economy::RECORD_CHANGE, utils::db_record_id};
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::{Result, eyre};
+use poise::serenity_prelude::UserId;
+use serde::Deserialize;
+use std::time::Duration;
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// A user's charges as of `refreshed_at`, the last time one was added (or they were full).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, SurrealValue)]
+struct ChargeState {
+    charges: u64,
+    refreshed_at: DateTime<Utc>,
+}
+
+/// What a user has to yeet with right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChargeStatus {
+    pub charges: u64,
+    /// `None` while the user is full.
+    pub next_charge_at: Option<DateTime<Utc>>,
+}
+
+/// What an extra charge cost, from [`YeetCharges::buy_extra`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoughtCharge {
+    pub status: ChargeStatus,
+    pub balance: i64,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct BuyRow {
+    unchanged: bool,
+    balance: Option<i64>,
+}
+
+/// The result of trying to spend a charge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeUse {
+    Used(ChargeStatus),
+    Empty(ChargeStatus),
+}
+
+impl ChargeState {
+    fn full(max: u64, now: DateTime<Utc>) -> Self {
+        Self {
+            charges: max,
+            refreshed_at: now,
+        }
+    }
+
+    /// Adds every charge that came back since `refreshed_at`.
+    ///
+    /// Extra charges can take a user past `max`, and are kept until spent.
+    fn refilled(self, max: u64, refresh: Duration, now: DateTime<Utc>) -> Self {
+        let refresh = TimeDelta::from_std(refresh).unwrap_or(TimeDelta::MAX);
+
+        if self.charges >= max || refresh <= TimeDelta::zero() {
+            return Self::full(self.charges.max(max), now);
+        }
+
+        let elapsed = (now - self.refreshed_at).max(TimeDelta::zero());
+        let gained = (elapsed.num_milliseconds() / refresh.num_milliseconds().max(1)) as u64;
+        let charges = self.charges.saturating_add(gained);
+
+        if charges >= max {
+            return Self::full(max, now);
+        }
+
+        // Too many refreshes to add up only happens with a tiny refresh, so the part of one that
+        // would be lost by starting over from `now` doesn't matter.
+        let refreshed_at = i32::try_from(gained)
+            .ok()
+            .and_then(|gained| refresh.checked_mul(gained))
+            .and_then(|refilled| self.refreshed_at.checked_add_signed(refilled))
+            .unwrap_or(now);
+
+        Self {
+            charges,
+            refreshed_at,
+        }
+    }
+
+    fn status(self, max: u64, refresh: Duration) -> ChargeStatus {
+        ChargeStatus {
+            charges: self.charges,
+            next_charge_at: (self.charges < max).then(|| {
+                TimeDelta::from_std(refresh)
+                    .ok()
+                    .and_then(|refresh| self.refreshed_at.checked_add_signed(refresh))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC)
+            }),
+        }
+    }
+
+    /// Adds one charge, even past `max`.
+    fn with_extra(self) -> Self {
+        Self {
+            charges: self.charges.saturating_add(1),
+            ..self
+        }
+    }
+
+    /// Spends one charge, if there is one.
+    fn spent(self, max: u64, now: DateTime<Utc>) -> Option<Self> {
+        let charges = self.charges.checked_sub(1)?;
+
+        Some(Self {
+            charges,
+            // A full user wasn't waiting on a charge, so the clock starts now.
+            refreshed_at: if self.charges >= max {
+                now
+            } else {
+                self.refreshed_at
+            },
+        })
+    }
+}
+
+/// How many times a charge update is retried when another one lands first.
+const MAX_ATTEMPTS: usize = 10;
+
+pub struct YeetCharges;
+
+impl YeetCharges {
+    /// Applies `change` to `user_id`'s refilled charges and stores what it returns, if anything.
+    ///
+    /// The write only lands if nothing else changed the row since it was read, so concurrent
+    /// yeets can't spend the same charge and concurrent buys can't drop each other's charges.
+    async fn update<T>(
+        user_id: UserId,
+        max: u64,
+        refresh: Duration,
+        change: impl Fn(ChargeState, DateTime<Utc>) -> (Option<ChargeState>, T),
+    ) -> Result<T> {
+        let charge = db_record_id("yeet_charge", user_id)?;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let now = Utc::now();
+            let stored = Self::load(&charge, max, now).await?;
+
+            let (changed, result) = change(stored.refilled(max, refresh, now), now);
+            let Some(changed) = changed else {
+                return Ok(result);
+            };
+
+            let updated: Vec<ChargeState> = DB
+                .query(
+                    "UPDATE $charge SET charges = $changed.charges, \
+                         refreshed_at = $changed.refreshed_at \
+                     WHERE charges = $stored.charges AND refreshed_at = $stored.refreshed_at \
+                     RETURN AFTER",
+                )
+                .bind(("charge", charge.clone()))
+                .bind(("changed", changed))
+                .bind(("stored", stored))
+                .await?
+                .check()?
+                .take(0)?;
+
+            if !updated.is_empty() {
+                return Ok(result);
+            }
+        }
+
+        Err(eyre!(
+            "gave up updating yeet charges for {user_id} after {MAX_ATTEMPTS} attempts"
+        ))
+    }
+
+    /// The charges stored for `charge`, starting a new user off full.
+    async fn load(charge: &RecordId, max: u64, now: DateTime<Utc>) -> Result<ChargeState> {
+        let stored: Option<ChargeState> = DB
+            .query(
+                "INSERT IGNORE INTO yeet_charge \
+                     { id: $charge, charges: $max, refreshed_at: $now } RETURN NONE; \
+                 SELECT charges, refreshed_at FROM ONLY $charge;",
+            )
+            .bind(("charge", charge.clone()))
+            .bind(("max", max))
+            .bind(("now", now))
+            .await?
+            .check()?
+            .take(1)?;
+
+        stored.ok_or_else(|| eyre!("yeet charge INSERT left no record"))
+    }
+
+    /// Spends one of `user_id`'s charges if they have any left.
+    ///
+    /// Users hold at most `max` charges and get one back every `refresh`.
+    pub async fn try_use(user_id: UserId, max: u64, refresh: Duration) -> Result<ChargeUse> {
+        Self::update(user_id, max, refresh, |state, now| {
+            match state.spent(max, now) {
+                Some(spent) => (Some(spent), ChargeUse::Used(spent.status(max, refresh))),
+                None => (None, ChargeUse::Empty(state.status(max, refresh))),
+            }
+        })
+        .await
+    }
+
+    /// Takes `price` from `user_id`'s bank balance and gives them one charge on top of whatever
+    /// they have, even past `max`.
+    ///
+    /// The debit and the new charge are one transaction. Returns `None` if the balance doesn't
+    /// cover `price` or the account is frozen.
+    pub async fn buy_extra(
+        user_id: UserId,
+        max: u64,
+        refresh: Duration,
+        price: i64,
+        reason: String,
+    ) -> Result<Option<BoughtCharge>> {
+        let charge = db_record_id("yeet_charge", user_id)?;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let now = Utc::now();
+            let stored = Self::load(&charge, max, now).await?;
+            let extra = stored.refilled(max, refresh, now).with_extra();
+
+            let bought: Option<BuyRow> = DB
+                .query(format!(
+                    "BEGIN TRANSACTION; \
+                     LET $unchanged = $charge.charges = $stored.charges \
+                         AND $charge.refreshed_at = $stored.refreshed_at; \
+                     LET $after = IF $unchanged {{ \
+                         (UPDATE $account SET balance += $amount \
+                             WHERE balance >= -$amount AND !$freeze.exists() RETURN AFTER)[0] \
+                     }}; \
+                     IF $after {{ \
+                         {RECORD_CHANGE}; \
+                         UPDATE $charge SET charges = $extra.charges, \
+                             refreshed_at = $extra.refreshed_at; \
+                     }}; \
+                     RETURN {{ unchanged: $unchanged, balance: $after.balance }}; \
+                     COMMIT TRANSACTION;"
+                ))
+                .bind(("charge", charge.clone()))
+                .bind(("stored", stored))
+                .bind(("extra", extra))
+                .bind(("account", db_record_id("bank_account", user_id)?))
+                .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+                .bind(("amount", -price))
+                .bind(("reason", reason.clone()))
+                .bind(("gambling_net", None::<i64>))
+                .await?
+                .check()?
+                // BEGIN, both LETs and the IF come first.
+                .take(4)?;
+            let bought = bought.ok_or_else(|| eyre!("yeet charge purchase returned nothing"))?;
+
+            if bought.unchanged {
+                return Ok(bought.balance.map(|balance| BoughtCharge {
+                    status: extra.status(max, refresh),
+                    balance,
+                }));
+            }
+        }
+
+        Err(eyre!(
+            "gave up buying a yeet charge for {user_id} after {MAX_ATTEMPTS} attempts"
+        ))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::economy::Bank;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn charges_come_back_one_refresh_at_a_time() {
+        let empty = ChargeState {
+            charges: 0,
+            refreshed_at: at(0),
+        };
+
+        let later = empty.refilled(3, HOUR, at(3600 + 1800));
+        assert_eq!(later.charges, 1);
+        assert_eq!(later.refreshed_at, at(3600));
+        assert_eq!(later.status(3, HOUR).next_charge_at, Some(at(7200)));
+
+        let full = empty.refilled(3, HOUR, at(3600 * 10));
+        assert_eq!(full, ChargeState::full(3, at(3600 * 10)));
+        assert_eq!(full.status(3, HOUR).next_charge_at, None);
+    }
+
+    #[test]
+    fn spending_from_full_starts_the_clock() {
+        let full = ChargeState::full(3, at(0));
+
+        let spent = full.spent(3, at(500)).unwrap();
+        assert_eq!(spent.charges, 2);
+        assert_eq!(spent.refreshed_at, at(500));
+
+        let spent_again = spent.spent(3, at(900)).unwrap();
+        assert_eq!(spent_again.refreshed_at, at(500));
+
+        let empty = ChargeState {
+            charges: 0,
+            refreshed_at: at(0),
+        };
+        assert_eq!(empty.spent(3, at(1)), None);
+    }
+
+    #[test]
+    fn extra_charges_survive_refills() {
+        let topped_up = ChargeState {
+            charges: 4,
+            refreshed_at: at(0),
+        };
+
+        assert_eq!(topped_up.refilled(3, HOUR, at(3600 * 5)).charges, 4);
+
+        let spent = topped_up.spent(3, at(10)).unwrap();
+        assert_eq!(spent.charges, 3);
+        assert_eq!(spent.status(3, HOUR).next_charge_at, None);
+    }
+
+    #[test]
+    fn tiny_refreshes_dont_overflow() {
+        let empty = ChargeState {
+            charges: 0,
+            refreshed_at: at(0),
+        };
+
+        let refilled = empty.refilled(u64::MAX, Duration::from_millis(1), at(10_000_000));
+        assert_eq!(refilled.charges, 10_000_000_000);
+        assert_eq!(refilled.refreshed_at, at(10_000_000));
+    }
+
+    #[test]
+    fn huge_refreshes_dont_overflow() {
+        let empty = ChargeState {
+            charges: 0,
+            refreshed_at: at(0),
+        };
+
+        assert_eq!(
+            empty.status(3, Duration::MAX).next_charge_at,
+            Some(DateTime::<Utc>::MAX_UTC)
+        );
+    }
+
+    pub(crate) async fn assert_yeet_charges_are_per_user() {
+        let yeeter = UserId::new(98_001);
+        let bystander = UserId::new(98_002);
+
+        for remaining in [1, 0] {
+            let ChargeUse::Used(status) = YeetCharges::try_use(yeeter, 2, HOUR).await.unwrap()
+            else {
+                panic!("a fresh user must have charges");
+            };
+            assert_eq!(status.charges, remaining);
+            assert!(status.next_charge_at.is_some());
+        }
+
+        let ChargeUse::Empty(status) = YeetCharges::try_use(yeeter, 2, HOUR).await.unwrap() else {
+            panic!("charges must run out");
+        };
+        assert_eq!(status.charges, 0);
+        assert!(status.next_charge_at.unwrap() > Utc::now());
+
+        assert_eq!(
+            YeetCharges::buy_extra(yeeter, 2, HOUR, 10, String::from("Extra"))
+                .await
+                .unwrap(),
+            None,
+            "charges can't be bought without the balance for them"
+        );
+        Bank::change(yeeter, 10, String::from("Income"))
+            .await
+            .unwrap();
+        let bought = YeetCharges::buy_extra(yeeter, 2, HOUR, 10, String::from("Extra"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(bought.status.charges, 1);
+        assert_eq!(bought.balance, 0);
+        assert!(matches!(
+            YeetCharges::try_use(yeeter, 2, HOUR).await.unwrap(),
+            ChargeUse::Used(ChargeStatus { charges: 0, .. })
+        ));
+
+        let ChargeUse::Used(status) = YeetCharges::try_use(bystander, 2, HOUR).await.unwrap()
+        else {
+            panic!("one user's yeets must not use up another's");
+        };
+        assert_eq!(status.charges, 1);
+
+        let racer = UserId::new(98_003);
+        let uses =
+            futures::future::join_all((0..10).map(|_| YeetCharges::try_use(racer, 3, HOUR))).await;
+        assert_eq!(
+            uses.iter()
+                .filter(|used| matches!(used, Ok(ChargeUse::Used(_))))
+                .count(),
+            3,
+            "concurrent yeets must not spend the same charge"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Who can be yeeted, and why not.
+
+use crate::{
+    data::DB, utils::GetRelativeTimestamp, utils::db_record_id, yeet_votes::user_record_id,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::Result;
+use poise::serenity_prelude::{Mentionable, RoleId, User, UserId};
+use std::time::Duration;
+use surrealdb::types::RecordId;
+
+/// Why a yeet was refused, shown to the yeeter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    SelfYeet,
+    NotReactMe,
+    ExemptRole(RoleId),
+    TooNew { eligible_at: DateTime<Utc> },
+    BoughtImmunity { until: DateTime<Utc> },
+    RecentlyYeeted { until: DateTime<Utc> },
+    TargetedTooOften { count: u64, next_at: DateTime<Utc> },
+}
+
+impl Refusal {
+    pub fn message(&self, victim: &User) -> String {
+        let victim = victim.mention();
+
+        match self {
+            Self::SelfYeet => String::from("You can't yeet yourself! Try `/timeout` instead."),
+            Self::NotReactMe => String::from("You can't yeet a non reactme user!"),
+            Self::ExemptRole(role_id) => {
+                format!(
+                    "{victim} has the {} role and can't be yeeted.",
+                    role_id.mention()
+                )
+            }
+            Self::TooNew { eligible_at } => format!(
+                "{victim} only just joined. They can be yeeted {}.",
+                eligible_at.discord_relative_timestamp()
+            ),
+            Self::BoughtImmunity { until } => format!(
+                "{victim} paid for protection and can't be yeeted until {}",
+                until.discord_relative_timestamp()
+            ),
+            Self::RecentlyYeeted { until } => format!(
+                "{victim} was yeeted recently and can't be yeeted again until {}",
+                until.discord_relative_timestamp()
+            ),
+            Self::TargetedTooOften { count, next_at } => format!(
+                "{victim} has already been targeted {count} time{} today. Try again {}.",
+                if *count == 1 { "" } else { "s" },
+                next_at.discord_relative_timestamp()
+            ),
+        }
+    }
+}
+
+/// Refuses a victim who joined less than `min_tenure` ago.
+pub fn check_tenure(
+    joined_at: Option<DateTime<Utc>>,
+    min_tenure: Duration,
+    now: DateTime<Utc>,
+) -> Option<Refusal> {
+    let joined_at = joined_at?;
+    let eligible_at = TimeDelta::from_std(min_tenure)
+        .ok()
+        .and_then(|min_tenure| joined_at.checked_add_signed(min_tenure))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC);
+
+    (eligible_at > now).then_some(Refusal::TooNew { eligible_at })
+}
+
+/// Refuses a victim who was already targeted `max` times in the last day.
+///
+/// `targeted_at` holds the start of each of those yeets, oldest first. A `max` of zero means no limit.
+pub fn check_daily_limit(
+    targeted_at: &[DateTime<Utc>],
+    max: u64,
+    now: DateTime<Utc>,
+) -> Option<Refusal> {
+    let window_start = now - TimeDelta::days(1);
+    let recent: Vec<_> = targeted_at
+        .iter()
+        .filter(|&&start| start > window_start)
+        .collect();
+    let count = recent.len() as u64;
+
+    if max == 0 || count < max {
+        return None;
+    }
+
+    // Once enough of them are more than a day old, the victim is fair game again.
+    let next_at = *recent[(count - max) as usize] + TimeDelta::days(1);
+
+    Some(Refusal::TargetedTooOften { count, next_at })
+}
+
+pub struct YeetEligibility;
+
+impl YeetEligibility {
+    /// Roles whose members can't be yeeted.
+    pub async fn exempt_roles() -> Result<Vec<RoleId>> {
+        let ids: Vec<i64> = DB
+            .query("SELECT VALUE record::id(id) FROM yeet_exempt_role")
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(ids.into_iter().map(|id| RoleId::new(id as u64)).collect())
+    }
+
+    /// Exempts `role_id` from yeets, or lifts its exemption. Returns whether it's now exempt.
+    pub async fn toggle_exempt_role(role_id: RoleId) -> Result<bool> {
+        let role = db_record_id("yeet_exempt_role", role_id)?;
+        let exempt: Option<RecordId> = DB
+            .query("SELECT VALUE id FROM ONLY $role")
+            .bind(("role", role.clone()))
+            .await?
+            .check()?
+            .take(0)?;
+
+        DB.query(if exempt.is_some() {
+            "DELETE $role"
+        } else {
+            "CREATE $role"
+        })
+        .bind(("role", role))
+        .await?
+        .check()?;
+
+        Ok(exempt.is_none())
+    }
+
+    /// When each yeet against `victim` since `since` started, oldest first.
+    pub async fn targeted_since(
+        victim: UserId,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>> {
+        Ok(DB
+            .query(
+                "SELECT VALUE start_time FROM yeet \
+                 WHERE out = $victim AND start_time > $since ORDER BY start_time",
+            )
+            .bind(("victim", user_record_id(victim)?))
+            .bind(("since", since))
+            .await?
+            .check()?
+            .take(0)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + hours * 3600, 0).unwrap()
+    }
+
+    #[test]
+    fn new_members_are_protected_for_their_tenure() {
+        let week = Duration::from_secs(7 * 24 * 3600);
+
+        assert_eq!(
+            check_tenure(Some(at(0)), week, at(24)),
+            Some(Refusal::TooNew {
+                eligible_at: at(7 * 24)
+            })
+        );
+        assert_eq!(check_tenure(Some(at(0)), week, at(7 * 24)), None);
+        assert_eq!(check_tenure(Some(at(0)), Duration::ZERO, at(0)), None);
+        assert_eq!(check_tenure(None, week, at(0)), None);
+        assert_eq!(
+            check_tenure(Some(at(0)), Duration::MAX, at(0)),
+            Some(Refusal::TooNew {
+                eligible_at: DateTime::<Utc>::MAX_UTC
+            }),
+            "a tenure past the last date chrono handles is never reached"
+        );
+    }
+
+    #[test]
+    fn victims_can_only_be_targeted_so_often_a_day() {
+        let targeted_at = [at(-30), at(-20), at(-10), at(-1)];
+
+        assert_eq!(check_daily_limit(&targeted_at, 0, at(0)), None);
+        assert_eq!(check_daily_limit(&targeted_at, 4, at(0)), None);
+        assert_eq!(
+            check_daily_limit(&targeted_at, 2, at(0)),
+            Some(Refusal::TargetedTooOften {
+                count: 3,
+                next_at: at(14)
+            })
+        );
+        assert_eq!(
+            check_daily_limit(&targeted_at, 3, at(0)),
+            Some(Refusal::TargetedTooOften {
+                count: 3,
+                next_at: at(4)
+            })
+        );
+    }
+
+    /// Relies on the votes left behind by the yeet vote tests.
+    pub(crate) async fn assert_eligibility_is_read_from_the_db() {
+        let targeted =
+            YeetEligibility::targeted_since(UserId::new(96_002), Utc::now() - TimeDelta::days(1))
+                .await
+                .unwrap();
+        assert_eq!(targeted.len(), 2);
+        assert!(targeted[0] <= targeted[1]);
+
+        let role = RoleId::new(99_301);
+        assert!(YeetEligibility::toggle_exempt_role(role).await.unwrap());
+        assert_eq!(YeetEligibility::exempt_roles().await.unwrap(), vec![role]);
+        assert!(!YeetEligibility::toggle_exempt_role(role).await.unwrap());
+        assert!(YeetEligibility::exempt_roles().await.unwrap().is_empty());
+    }
+}
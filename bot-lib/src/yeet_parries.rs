@@ -0,0 +1,240 @@
+//! Parry attempts, stored in `parry_attempt` so a restart doesn't forget them.
+
+use crate::{data::DB, yeet_settings::YeetSettings, yeet_votes::user_record_id};
+use chrono::{DateTime, TimeDelta, Utc};
+use color_eyre::eyre::Result;
+use poise::serenity_prelude::{User, UserId};
+use serde::Deserialize;
+use std::time::Duration;
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// What a parry did to a yeet that passed against its victim.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, SurrealValue)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum ParryOutcome {
+    /// Timed just right, so the yeeter gets yeeted instead.
+    Reflected,
+    /// Still inside the window, but late. The victim gets a shorter timeout.
+    Partial,
+    /// Parried again during the cooldown. The victim gets a longer timeout.
+    Failed,
+}
+
+/// The parry windows, taken from [`YeetSettings`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParryTiming {
+    /// How long before a vote passes a parry still counts.
+    pub window: Duration,
+    /// How long before a vote passes a parry reflects it.
+    pub reflect: Duration,
+    /// How long after one parry another one fails.
+    pub cooldown: Duration,
+}
+
+impl From<&YeetSettings> for ParryTiming {
+    fn from(settings: &YeetSettings) -> Self {
+        Self {
+            window: settings.parry_seconds,
+            reflect: settings.parry_reflect_seconds,
+            cooldown: settings.parry_cooldown_seconds,
+        }
+    }
+}
+
+impl ParryTiming {
+    /// Judges the latest of `attempts`, sorted oldest first, against a vote that passed at `decided_at`.
+    fn judge(&self, attempts: &[DateTime<Utc>], decided_at: DateTime<Utc>) -> Option<ParryOutcome> {
+        let (&latest, earlier) = attempts.split_last()?;
+        let age = (decided_at - latest).to_std().ok()?;
+
+        if age > self.window {
+            return None;
+        }
+
+        let spammed = earlier.last().is_some_and(|&previous| {
+            (latest - previous).to_std().unwrap_or_default() < self.cooldown
+        });
+
+        Some(if spammed {
+            ParryOutcome::Failed
+        } else if age <= self.reflect {
+            ParryOutcome::Reflected
+        } else {
+            ParryOutcome::Partial
+        })
+    }
+
+    /// How far back an attempt can still change the outcome.
+    fn lookback(&self) -> TimeDelta {
+        TimeDelta::from_std(self.window + self.cooldown).unwrap_or(TimeDelta::MAX)
+    }
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ParryAttemptRow {
+    id: RecordId,
+    attempted_at: DateTime<Utc>,
+    used: bool,
+}
+
+pub struct YeetParries;
+
+impl YeetParries {
+    pub async fn attempt(user: &User) -> Result<()> {
+        DB.query(
+            "UPSERT $user SET name = $name; \
+             CREATE parry_attempt SET user_id = $user, attempted_at = time::now();",
+        )
+        .bind(("user", user_record_id(user.id)?))
+        .bind(("name", user.name.clone()))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    /// Spends `victim`'s latest parry on `yeet`, which passed against them at `decided_at`.
+    ///
+    /// Returns `None` if there was no parry to spend. A parry is only ever spent once.
+    pub async fn use_on(
+        yeet: &RecordId,
+        victim: UserId,
+        decided_at: DateTime<Utc>,
+        timing: ParryTiming,
+    ) -> Result<Option<ParryOutcome>> {
+        let attempts: Vec<ParryAttemptRow> = DB
+            .query(
+                "SELECT id, attempted_at, used_on != NONE AS used FROM parry_attempt \
+                 WHERE user_id = $user AND attempted_at > $since AND attempted_at <= $decided_at \
+                 ORDER BY attempted_at",
+            )
+            .bind(("user", user_record_id(victim)?))
+            .bind(("since", decided_at - timing.lookback()))
+            .bind(("decided_at", decided_at))
+            .await?
+            .check()?
+            .take(0)?;
+
+        let Some(latest) = attempts.last().filter(|latest| !latest.used) else {
+            return Ok(None);
+        };
+
+        let times: Vec<_> = attempts
+            .iter()
+            .map(|attempt| attempt.attempted_at)
+            .collect();
+        let Some(outcome) = timing.judge(&times, decided_at) else {
+            return Ok(None);
+        };
+
+        let used: Vec<RecordId> = DB
+            .query(
+                "UPDATE $attempt SET used_on = $yeet, outcome = $outcome \
+                 WHERE used_on = NONE RETURN VALUE id",
+            )
+            .bind(("attempt", latest.id.clone()))
+            .bind(("yeet", yeet.clone()))
+            .bind(("outcome", outcome))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok((!used.is_empty()).then_some(outcome))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::yeet_votes::tests::user;
+
+    const TIMING: ParryTiming = ParryTiming {
+        window: Duration::from_secs(5),
+        reflect: Duration::from_secs(2),
+        cooldown: Duration::from_secs(60),
+    };
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    #[test]
+    fn parries_are_judged_by_how_close_they_were() {
+        assert_eq!(TIMING.judge(&[], at(0)), None);
+        assert_eq!(
+            TIMING.judge(&[at(0)], at(1_500)),
+            Some(ParryOutcome::Reflected)
+        );
+        assert_eq!(
+            TIMING.judge(&[at(0)], at(2_000)),
+            Some(ParryOutcome::Reflected)
+        );
+        assert_eq!(
+            TIMING.judge(&[at(0)], at(4_000)),
+            Some(ParryOutcome::Partial)
+        );
+        assert_eq!(TIMING.judge(&[at(0)], at(5_001)), None, "too early");
+        assert_eq!(TIMING.judge(&[at(1_000)], at(0)), None, "after the vote");
+    }
+
+    #[test]
+    fn parrying_during_the_cooldown_fails() {
+        let spammed = [at(-30_000), at(0)];
+        assert_eq!(
+            TIMING.judge(&spammed, at(1_000)),
+            Some(ParryOutcome::Failed)
+        );
+        assert_eq!(
+            TIMING.judge(&spammed, at(4_000)),
+            Some(ParryOutcome::Failed)
+        );
+        assert_eq!(
+            TIMING.judge(&spammed, at(6_000)),
+            None,
+            "a stale spam is harmless"
+        );
+
+        let rested = [at(-60_000), at(0)];
+        assert_eq!(
+            TIMING.judge(&rested, at(1_000)),
+            Some(ParryOutcome::Reflected)
+        );
+    }
+
+    pub(crate) async fn assert_parries_are_used_once() {
+        let victim = user(99_001);
+        let yeet = RecordId::new("yeet", "parry_test");
+
+        assert_eq!(
+            YeetParries::use_on(&yeet, victim.id, Utc::now(), TIMING)
+                .await
+                .unwrap(),
+            None
+        );
+
+        YeetParries::attempt(&victim).await.unwrap();
+        let decided_at = Utc::now() + TimeDelta::milliseconds(500);
+        assert_eq!(
+            YeetParries::use_on(&yeet, victim.id, decided_at, TIMING)
+                .await
+                .unwrap(),
+            Some(ParryOutcome::Reflected)
+        );
+        assert_eq!(
+            YeetParries::use_on(&yeet, victim.id, decided_at, TIMING)
+                .await
+                .unwrap(),
+            None,
+            "a parry must only be used once"
+        );
+
+        YeetParries::attempt(&victim).await.unwrap();
+        assert_eq!(
+            YeetParries::use_on(&yeet, victim.id, Utc::now() + TimeDelta::seconds(1), TIMING)
+                .await
+                .unwrap(),
+            Some(ParryOutcome::Failed)
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Yeet perks bought with bank balance.
+
+use crate::{
+    data::DB,
+    economy::{Bank, RECORD_CHANGE},
+    utils::db_record_id,
+    yeet_charges::{ChargeStatus, YeetCharges},
+    yeet_settings::YeetSettings,
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, eyre};
+use humantime::format_duration;
+use poise::serenity_prelude::UserId;
+use serde::Deserialize;
+use surrealdb::types::SurrealValue;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum YeetPerk {
+    /// Nobody can start a yeet against you for a while.
+    #[name = "Immunity"]
+    Immunity,
+    /// One more yeet, on top of your usual charges.
+    #[name = "Extra yeet charge"]
+    ExtraCharge,
+}
+
+impl YeetPerk {
+    pub fn price(self, settings: &YeetSettings) -> i64 {
+        let price = match self {
+            Self::Immunity => settings.immunity_price,
+            Self::ExtraCharge => settings.extra_charge_price,
+        };
+
+        i64::try_from(price).unwrap_or(i64::MAX)
+    }
+
+    /// The reason the purchase shows up with in the bank history.
+    fn reason(self, settings: &YeetSettings) -> String {
+        match self {
+            Self::Immunity => format!(
+                "Bought {} of yeet immunity",
+                format_duration(settings.immunity_seconds)
+            ),
+            Self::ExtraCharge => String::from("Bought an extra yeet charge"),
+        }
+    }
+}
+
+/// What a user got for their money.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerkGrant {
+    ImmuneUntil(DateTime<Utc>),
+    Charges(ChargeStatus),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purchase {
+    Bought { grant: PerkGrant, balance: i64 },
+    TooPoor { price: i64, balance: i64 },
+    Frozen,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ImmunityRow {
+    until: Option<DateTime<Utc>>,
+    balance: Option<i64>,
+}
+
+pub struct YeetPerks;
+
+impl YeetPerks {
+    /// When the user's bought immunity runs out, if they have any left.
+    pub async fn immune_until(user_id: UserId) -> Result<Option<DateTime<Utc>>> {
+        let until: Option<DateTime<Utc>> = DB
+            .query("SELECT VALUE until FROM ONLY $immunity")
+            .bind(("immunity", db_record_id("yeet_immunity", user_id)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(until.filter(|&until| until > Utc::now()))
+    }
+
+    /// Charges `user_id` for `perk` and hands it over, in one transaction.
+    pub async fn buy(user_id: UserId, perk: YeetPerk, settings: &YeetSettings) -> Result<Purchase> {
+        let price = perk.price(settings);
+        let reason = perk.reason(settings);
+
+        let bought = match perk {
+            YeetPerk::Immunity => Self::buy_immunity(user_id, price, reason, settings)
+                .await?
+                .map(|(until, balance)| (PerkGrant::ImmuneUntil(until), balance)),
+            YeetPerk::ExtraCharge => YeetCharges::buy_extra(
+                user_id,
+                settings.opportunities,
+                settings.refresh_charge_seconds,
+                price,
+                reason,
+            )
+            .await?
+            .map(|bought| (PerkGrant::Charges(bought.status), bought.balance)),
+        };
+
+        if let Some((grant, balance)) = bought {
+            return Ok(Purchase::Bought { grant, balance });
+        }
+        if Bank::freeze(user_id).await?.is_some() {
+            return Ok(Purchase::Frozen);
+        }
+
+        Ok(Purchase::TooPoor {
+            price,
+            balance: Bank::get(user_id).await?.balance,
+        })
+    }
+
+    /// Returns when the immunity now runs out and the balance left, or `None` if the balance
+    /// doesn't cover `price` or the account is frozen.
+    async fn buy_immunity(
+        user_id: UserId,
+        price: i64,
+        reason: String,
+        settings: &YeetSettings,
+    ) -> Result<Option<(DateTime<Utc>, i64)>> {
+        // Buying more while immune stacks on top of what's left.
+        let bought: Option<ImmunityRow> = DB
+            .query(format!(
+                "BEGIN TRANSACTION; \
+                 LET $after = (UPDATE $account SET balance += $amount \
+                     WHERE balance >= -$amount AND !$freeze.exists() RETURN AFTER)[0]; \
+                 LET $until = IF $after {{ \
+                     {RECORD_CHANGE}; \
+                     (UPSERT ONLY $immunity \
+                         SET until = (IF until > time::now() {{ until }} ELSE {{ time::now() }}) \
+                             + $duration \
+                         RETURN VALUE until) \
+                 }}; \
+                 RETURN {{ until: $until, balance: $after.balance }}; \
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("immunity", db_record_id("yeet_immunity", user_id)?))
+            .bind(("duration", settings.immunity_seconds))
+            .bind(("account", db_record_id("bank_account", user_id)?))
+            .bind(("freeze", db_record_id("bank_freeze", user_id)?))
+            .bind(("amount", -price))
+            .bind(("reason", reason))
+            .bind(("gambling_net", None::<i64>))
+            .await?
+            .check()?
+            // BEGIN and both LETs come first.
+            .take(3)?;
+        let bought = bought.ok_or_else(|| eyre!("yeet immunity purchase returned nothing"))?;
+
+        Ok(bought.until.zip(bought.balance))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::time::Duration;
+
+    pub(crate) async fn assert_perks_cost_bank_balance() {
+        let buyer = UserId::new(99_101);
+        let settings = YeetSettings {
+            immunity_price: 30,
+            immunity_seconds: Duration::from_secs(3600),
+            extra_charge_price: 20,
+            ..YeetSettings::default()
+        };
+
+        assert_eq!(
+            YeetPerks::buy(buyer, YeetPerk::Immunity, &settings)
+                .await
+                .unwrap(),
+            Purchase::TooPoor {
+                price: 30,
+                balance: 0
+            }
+        );
+        assert_eq!(YeetPerks::immune_until(buyer).await.unwrap(), None);
+
+        Bank::change(buyer, 100, String::from("Income"))
+            .await
+            .unwrap();
+
+        let Purchase::Bought {
+            grant: PerkGrant::ImmuneUntil(first_until),
+            balance: 70,
+        } = YeetPerks::buy(buyer, YeetPerk::Immunity, &settings)
+            .await
+            .unwrap()
+        else {
+            panic!("immunity must be bought once affordable");
+        };
+        assert_eq!(
+            YeetPerks::immune_until(buyer).await.unwrap(),
+            Some(first_until)
+        );
+
+        let Purchase::Bought {
+            grant: PerkGrant::ImmuneUntil(second_until),
+            ..
+        } = YeetPerks::buy(buyer, YeetPerk::Immunity, &settings)
+            .await
+            .unwrap()
+        else {
+            panic!("immunity must stack");
+        };
+        assert_eq!(second_until - first_until, TimeDelta::hours(1));
+
+        let Purchase::Bought {
+            grant: PerkGrant::Charges(status),
+            balance: 20,
+        } = YeetPerks::buy(buyer, YeetPerk::ExtraCharge, &settings)
+            .await
+            .unwrap()
+        else {
+            panic!("an extra charge must be bought once affordable");
+        };
+        assert_eq!(status.charges, settings.opportunities + 1);
+
+        let history = Bank::history(buyer, 0, 10).await.unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].amount, -20);
+        assert_eq!(history[0].reason, "Bought an extra yeet charge");
+        assert_eq!(history[2].reason, "Bought 1h of yeet immunity");
+    }
+
+    pub(crate) async fn assert_concurrent_buys_charge_once() {
+        let buyer = UserId::new(99_102);
+        let settings = YeetSettings {
+            immunity_price: 30,
+            immunity_seconds: Duration::from_secs(3600),
+            extra_charge_price: 20,
+            ..YeetSettings::default()
+        };
+        Bank::change(buyer, 1000, String::from("Income"))
+            .await
+            .unwrap();
+
+        // A buy that loses the race can also fail to commit, which charges nothing either.
+        let buys = futures::future::join_all(
+            (0..5).map(|_| YeetPerks::buy(buyer, YeetPerk::Immunity, &settings)),
+        )
+        .await;
+        let bought = buys
+            .iter()
+            .filter(|buy| matches!(buy, Ok(Purchase::Bought { .. })))
+            .count() as i64;
+        assert!(bought > 0);
+        assert_eq!(Bank::get(buyer).await.unwrap().balance, 1000 - 30 * bought);
+        let until = YeetPerks::immune_until(buyer).await.unwrap().unwrap();
+        let paid_for = until - Utc::now();
+        assert!(
+            paid_for > TimeDelta::hours(bought) - TimeDelta::minutes(1)
+                && paid_for <= TimeDelta::hours(bought),
+            "every immunity paid for must stack, got {paid_for} for {bought}"
+        );
+
+        let balance = Bank::get(buyer).await.unwrap().balance;
+        let buys = futures::future::join_all(
+            (0..5).map(|_| YeetPerks::buy(buyer, YeetPerk::ExtraCharge, &settings)),
+        )
+        .await;
+        let bought = buys
+            .iter()
+            .filter(|buy| matches!(buy, Ok(Purchase::Bought { .. })))
+            .count();
+        assert!(bought > 0);
+        assert_eq!(
+            Bank::get(buyer).await.unwrap().balance,
+            balance - 20 * bought as i64
+        );
+        let Ok(Purchase::Bought {
+            grant: PerkGrant::Charges(status),
+            ..
+        }) = YeetPerks::buy(buyer, YeetPerk::ExtraCharge, &settings).await
+        else {
+            panic!("an extra charge must be bought once affordable");
+        };
+        assert_eq!(
+            status.charges,
+            settings.opportunities + bought as u64 + 1,
+            "every charge paid for must land"
+        );
+    }
+}
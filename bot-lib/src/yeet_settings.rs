@@ -0,0 +1,160 @@
+//! Yeet tuning, stored in the single `yeet_settings:1` row.
+
+use crate::data::DB;
+use color_eyre::eyre::{Result, eyre};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::{sync::LazyLock, time::Duration};
+use surrealdb::types::SurrealValue;
+
+static YEET_SETTINGS: LazyLock<RwLock<YeetSettings>> =
+    LazyLock::new(|| RwLock::new(YeetSettings::default()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, SurrealValue)]
+pub struct YeetSettings {
+    /// How many yeet charges each user holds when fully refilled.
+    pub opportunities: u64,
+    /// How many yays or nays decide a vote.
+    pub required_count: u64,
+    /// How long a yeeted user is timed out for.
+    pub duration_seconds: Duration,
+    /// How long it takes for one of a user's charges to come back.
+    pub refresh_charge_seconds: Duration,
+    /// How long a vote stays open.
+    pub voting_seconds: Duration,
+    /// How long a parry protects for.
+    pub parry_seconds: Duration,
+    /// Parrying again within this long makes the parry fail.
+    pub parry_cooldown_seconds: Duration,
+    /// A parry made this close to the vote passing sends the yeet back at the yeeter.
+    pub parry_reflect_seconds: Duration,
+    /// How much of the timeout a late parry leaves the victim with, in percent.
+    pub partial_parry_percent: u64,
+    /// How much of the timeout a parry made during the cooldown gives the victim, in percent.
+    pub failed_parry_percent: u64,
+    /// How long after a yeet's timeout ends its target can't be yeeted again. Zero disables it.
+    pub victim_immunity_seconds: Duration,
+    /// How new yeets are voted on.
+    pub ballot: BallotKind,
+    /// Whether the yeeter and victim are kept from voting on button ballots.
+    pub exclude_participants: bool,
+    /// What a stretch of yeet immunity costs in the bank.
+    pub immunity_price: u64,
+    /// How long one purchase of yeet immunity lasts.
+    pub immunity_seconds: Duration,
+    /// What an extra yeet charge costs in the bank.
+    pub extra_charge_price: u64,
+    /// How long someone has to be in the server before they can be yeeted. Zero disables it.
+    pub min_tenure_seconds: Duration,
+    /// How many yeets can target one victim in a day. Zero means no limit.
+    pub max_daily_targeted: u64,
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, SurrealValue, poise::ChoiceParameter,
+)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum BallotKind {
+    /// Count ✅ and ❌ reactions.
+    #[default]
+    Reactions,
+    /// Buttons with a live tally and one changeable vote per user.
+    Buttons,
+}
+
+impl Default for YeetSettings {
+    /// The same values schema.surrealql seeds the row with.
+    fn default() -> Self {
+        Self {
+            opportunities: 3,
+            required_count: 6,
+            duration_seconds: Duration::from_secs(300),
+            refresh_charge_seconds: Duration::from_secs(3600),
+            voting_seconds: Duration::from_secs(90),
+            parry_seconds: Duration::from_secs(5),
+            parry_cooldown_seconds: Duration::from_secs(60),
+            parry_reflect_seconds: Duration::from_secs(2),
+            partial_parry_percent: 50,
+            failed_parry_percent: 150,
+            victim_immunity_seconds: Duration::ZERO,
+            ballot: BallotKind::Reactions,
+            exclude_participants: false,
+            immunity_price: 100,
+            immunity_seconds: Duration::from_secs(3600),
+            extra_charge_price: 50,
+            min_tenure_seconds: Duration::ZERO,
+            max_daily_targeted: 0,
+        }
+    }
+}
+
+impl YeetSettings {
+    /// The settings as of the last [`YeetSettings::reload`].
+    pub fn current() -> Self {
+        *YEET_SETTINGS.read()
+    }
+
+    pub async fn reload() -> Result<()> {
+        let settings: Option<Self> = DB
+            .query("SELECT * OMIT id FROM ONLY yeet_settings:1")
+            .await?
+            .check()?
+            .take(0)?;
+
+        *YEET_SETTINGS.write() = settings.ok_or_else(|| eyre!("yeet_settings:1 is missing"))?;
+
+        Ok(())
+    }
+
+    /// Call [`YeetSettings::reload`] afterwards for it to take effect.
+    pub async fn save(&self) -> Result<()> {
+        DB.query("UPSERT yeet_settings:1 CONTENT $settings")
+            .bind(("settings", *self))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+}
+
+/// Loads the yeet settings seeded by schema.surrealql.
+pub async fn setup_yeet_settings() -> Result<()> {
+    YeetSettings::reload().await
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::YeetSettings;
+    use crate::data::DB;
+    use std::time::Duration;
+
+    pub(crate) async fn assert_yeet_settings_are_seeded_and_editable() {
+        YeetSettings::reload().await.unwrap();
+        assert_eq!(YeetSettings::current(), YeetSettings::default());
+
+        let settings = YeetSettings {
+            required_count: 4,
+            voting_seconds: Duration::from_secs(120),
+            ..YeetSettings::current()
+        };
+        settings.save().await.unwrap();
+        YeetSettings::reload().await.unwrap();
+        assert_eq!(YeetSettings::current(), settings);
+
+        // What a row seeded before these fields existed looks like once the schema runs again.
+        DB.query(
+            "UPDATE yeet_settings:1 SET min_tenure_seconds = NONE, max_daily_targeted = NONE; \
+             UPDATE yeet_settings:1;",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        YeetSettings::reload().await.unwrap();
+        assert_eq!(YeetSettings::current(), settings);
+
+        YeetSettings::default().save().await.unwrap();
+        YeetSettings::reload().await.unwrap();
+    }
+}
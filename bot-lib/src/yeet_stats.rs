@@ -0,0 +1,242 @@
+//! Yeet statistics, computed from the decided rows of the `yeet` relation.
+
+use crate::{data::DB, yeet_votes::YeetOutcome};
+use color_eyre::eyre::Result;
+use poise::serenity_prelude::UserId;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use surrealdb::types::SurrealValue;
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct DecidedYeetRow {
+    yeeter: i64,
+    victim: i64,
+    outcome: Option<YeetOutcome>,
+}
+
+/// How a set of yeets turned out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct YeetTally {
+    pub started: u64,
+    pub yeeted: u64,
+    pub backfired: u64,
+    pub both: u64,
+    pub parried: u64,
+}
+
+impl YeetTally {
+    fn add(&mut self, outcome: Option<YeetOutcome>) {
+        self.started += 1;
+
+        match outcome {
+            Some(YeetOutcome::Yeeted) => self.yeeted += 1,
+            Some(YeetOutcome::Backfired) => self.backfired += 1,
+            Some(YeetOutcome::Both) => self.both += 1,
+            Some(YeetOutcome::Parried) => self.parried += 1,
+            None => {}
+        }
+    }
+
+    /// Votes that ended without a decision, usually by running out of time.
+    pub fn expired(&self) -> u64 {
+        self.started - self.yeeted - self.backfired - self.both - self.parried
+    }
+
+    /// Yeets that timed out their victim.
+    pub fn landed(&self) -> u64 {
+        self.yeeted + self.both
+    }
+
+    /// Yeets that timed out their yeeter instead.
+    pub fn turned_around(&self) -> u64 {
+        self.backfired + self.parried
+    }
+
+    /// Votes that went against the victim, parried or not.
+    pub fn voted_against_victim(&self) -> u64 {
+        self.yeeted + self.both + self.parried
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct YeetStats {
+    by_yeeter: FxHashMap<UserId, YeetTally>,
+    by_victim: FxHashMap<UserId, YeetTally>,
+    pairs: FxHashMap<(UserId, UserId), u64>,
+    total: YeetTally,
+}
+
+impl YeetStats {
+    /// Reads every yeet vote that has finished, one way or another.
+    pub async fn load() -> Result<Self> {
+        let rows: Vec<DecidedYeetRow> = DB
+            .query(
+                "SELECT record::id(in) AS yeeter, record::id(out) AS victim, outcome \
+                 FROM yeet WHERE status != 'voting'",
+            )
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(Self::from_rows(rows.into_iter().map(|row| {
+            (
+                UserId::new(row.yeeter as u64),
+                UserId::new(row.victim as u64),
+                row.outcome,
+            )
+        })))
+    }
+
+    fn from_rows(rows: impl IntoIterator<Item = (UserId, UserId, Option<YeetOutcome>)>) -> Self {
+        let mut stats = Self::default();
+
+        for (yeeter, victim, outcome) in rows {
+            stats.by_yeeter.entry(yeeter).or_default().add(outcome);
+            stats.by_victim.entry(victim).or_default().add(outcome);
+            *stats.pairs.entry((yeeter, victim)).or_default() += 1;
+            stats.total.add(outcome);
+        }
+
+        stats
+    }
+
+    pub fn total(&self) -> YeetTally {
+        self.total
+    }
+
+    pub fn as_yeeter(&self, user_id: UserId) -> YeetTally {
+        self.by_yeeter.get(&user_id).copied().unwrap_or_default()
+    }
+
+    pub fn as_victim(&self, user_id: UserId) -> YeetTally {
+        self.by_victim.get(&user_id).copied().unwrap_or_default()
+    }
+
+    /// Whoever started the most yeets.
+    pub fn top_yeeters(&self, limit: usize) -> Vec<(UserId, YeetTally)> {
+        top_by(&self.by_yeeter, limit, |tally| tally.started)
+    }
+
+    /// Whoever was actually yeeted the most.
+    pub fn most_yeeted(&self, limit: usize) -> Vec<(UserId, YeetTally)> {
+        top_by(&self.by_victim, limit, |tally| tally.landed())
+            .into_iter()
+            .filter(|(_, tally)| tally.landed() > 0)
+            .collect()
+    }
+
+    /// Who yeets whom the most, as `(yeeter, victim, count)`.
+    pub fn top_pairs(&self, limit: usize) -> Vec<(UserId, UserId, u64)> {
+        let mut pairs: Vec<_> = self
+            .pairs
+            .iter()
+            .map(|(&(yeeter, victim), &count)| (yeeter, victim, count))
+            .collect();
+        pairs.sort_by_key(|&(yeeter, victim, count)| (std::cmp::Reverse(count), yeeter, victim));
+        pairs.truncate(limit);
+        pairs
+    }
+
+    /// The victim `user_id` tried to yeet most often.
+    pub fn favourite_target(&self, user_id: UserId) -> Option<(UserId, u64)> {
+        self.top_pairs(usize::MAX)
+            .into_iter()
+            .find(|&(yeeter, _, _)| yeeter == user_id)
+            .map(|(_, victim, count)| (victim, count))
+    }
+}
+
+fn top_by(
+    tallies: &FxHashMap<UserId, YeetTally>,
+    limit: usize,
+    key: impl Fn(&YeetTally) -> u64,
+) -> Vec<(UserId, YeetTally)> {
+    let mut tallies: Vec<_> = tallies
+        .iter()
+        .map(|(&user_id, &tally)| (user_id, tally))
+        .collect();
+    tallies.sort_by_key(|(user_id, tally)| (std::cmp::Reverse(key(tally)), *user_id));
+    tallies.truncate(limit);
+    tallies
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_are_tallied_per_side() {
+        let (alice, bob, carol) = (UserId::new(1), UserId::new(2), UserId::new(3));
+
+        let stats = YeetStats::from_rows([
+            (alice, bob, Some(YeetOutcome::Yeeted)),
+            (alice, bob, Some(YeetOutcome::Parried)),
+            (alice, bob, None),
+            (alice, carol, Some(YeetOutcome::Backfired)),
+            (carol, bob, Some(YeetOutcome::Both)),
+        ]);
+
+        let alice_stats = stats.as_yeeter(alice);
+        assert_eq!(alice_stats.started, 4);
+        assert_eq!(alice_stats.landed(), 1);
+        assert_eq!(alice_stats.turned_around(), 2);
+        assert_eq!(alice_stats.expired(), 1);
+
+        let bob_stats = stats.as_victim(bob);
+        assert_eq!(bob_stats.landed(), 2);
+        assert_eq!(bob_stats.parried, 1);
+        assert_eq!(bob_stats.voted_against_victim(), 3);
+
+        assert_eq!(stats.top_yeeters(1)[0].0, alice);
+        assert_eq!(stats.most_yeeted(5).len(), 1);
+        assert_eq!(stats.top_pairs(1), vec![(alice, bob, 3)]);
+        assert_eq!(stats.favourite_target(carol), Some((bob, 1)));
+        assert_eq!(stats.favourite_target(bob), None);
+        assert_eq!(stats.total().started, 5);
+    }
+
+    pub(crate) async fn assert_yeet_stats_read_decided_votes() {
+        use crate::{yeet_settings::BallotKind, yeet_votes::YeetVotes, yeet_votes::tests::user};
+        use chrono::Utc;
+        use poise::serenity_prelude::{ChannelId, GuildId, MessageId};
+
+        let (yeeter, victim) = (user(96_501), user(96_502));
+
+        for (message_id, outcome) in [
+            (96_510, Some(YeetOutcome::Yeeted)),
+            (96_511, Some(YeetOutcome::Parried)),
+            (96_512, None),
+        ] {
+            let started = YeetVotes::start(
+                &yeeter,
+                &victim,
+                GuildId::new(96_500),
+                ChannelId::new(96_500),
+                MessageId::new(message_id),
+                Utc::now(),
+                false,
+                BallotKind::Reactions,
+            )
+            .await
+            .unwrap();
+
+            match outcome {
+                Some(outcome) => {
+                    assert!(YeetVotes::claim(&started.id).await.unwrap());
+                    YeetVotes::set_outcome(&started.id, outcome).await.unwrap();
+                }
+                None => assert!(YeetVotes::expire(&started.id).await.unwrap()),
+            }
+        }
+
+        let stats = YeetStats::load().await.unwrap();
+        let as_yeeter = stats.as_yeeter(yeeter.id);
+
+        assert_eq!(as_yeeter.started, 3);
+        assert_eq!(as_yeeter.landed(), 1);
+        assert_eq!(as_yeeter.parried, 1);
+        assert_eq!(as_yeeter.expired(), 1);
+        assert_eq!(stats.as_victim(victim.id).parried, 1);
+        assert_eq!(stats.favourite_target(yeeter.id), Some((victim.id, 3)));
+    }
+}
@@ -0,0 +1,341 @@
+//! Yeet votes, stored in the `yeet` relation so a restart doesn't orphan them.
+
+use crate::{
+    data::DB,
+    utils::{db_id, db_record_id},
+    yeet_settings::BallotKind,
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, eyre};
+use poise::serenity_prelude::{ChannelId, GuildId, MessageId, User, UserId};
+use serde::Deserialize;
+use surrealdb::types::{RecordId, SurrealValue};
+
+/// How a yeet vote ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, SurrealValue)]
+#[serde(rename_all = "snake_case")]
+#[surreal(untagged, rename_all = "snake_case")]
+pub enum YeetOutcome {
+    /// The victim was yeeted.
+    Yeeted,
+    /// The yeeter was yeeted instead.
+    Backfired,
+    /// Both hit the threshold at once, so both were yeeted.
+    Both,
+    /// The victim parried, so the yeeter was yeeted.
+    Parried,
+}
+
+/// A yeet vote that was running when it was read.
+#[derive(Clone, Debug)]
+pub struct ActiveYeet {
+    pub id: RecordId,
+    pub yeeter: UserId,
+    pub victim: UserId,
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub start_time: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+    pub is_yeet_amongus_easter_egg: bool,
+    pub ballot: BallotKind,
+}
+
+/// Who voted which way on a button ballot.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BallotVotes {
+    pub yay: Vec<UserId>,
+    pub nay: Vec<UserId>,
+}
+
+#[derive(Debug, Deserialize, SurrealValue)]
+struct ActiveYeetRow {
+    id: RecordId,
+    yeeter: i64,
+    victim: i64,
+    guild_id: i64,
+    channel_id: i64,
+    message_id: i64,
+    start_time: DateTime<Utc>,
+    deadline: DateTime<Utc>,
+    is_yeet_amongus_easter_egg: bool,
+    ballot: BallotKind,
+}
+
+impl From<ActiveYeetRow> for ActiveYeet {
+    fn from(row: ActiveYeetRow) -> Self {
+        Self {
+            id: row.id,
+            yeeter: UserId::new(row.yeeter as u64),
+            victim: UserId::new(row.victim as u64),
+            guild_id: GuildId::new(row.guild_id as u64),
+            channel_id: ChannelId::new(row.channel_id as u64),
+            message_id: MessageId::new(row.message_id as u64),
+            start_time: row.start_time,
+            deadline: row.deadline,
+            is_yeet_amongus_easter_egg: row.is_yeet_amongus_easter_egg,
+            ballot: row.ballot,
+        }
+    }
+}
+
+const ACTIVE_YEET_FIELDS: &str = "id, record::id(in) AS yeeter, record::id(out) AS victim, \
+     guild_id, channel_id, message_id, start_time, deadline, is_yeet_amongus_easter_egg, ballot";
+
+pub(crate) fn user_record_id(user_id: UserId) -> Result<RecordId> {
+    db_record_id("user", user_id)
+}
+
+pub struct YeetVotes;
+
+impl YeetVotes {
+    /// Records a new vote on the ballot `message_id`, open until `deadline`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start(
+        yeeter: &User,
+        victim: &User,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        deadline: DateTime<Utc>,
+        is_yeet_amongus_easter_egg: bool,
+        ballot: BallotKind,
+    ) -> Result<ActiveYeet> {
+        let row: Option<ActiveYeetRow> = DB
+            .query(
+                "UPSERT $yeeter SET name = $yeeter_name; \
+                 UPSERT $victim SET name = $victim_name; \
+                 LET $yeet = (RELATE ONLY $yeeter->yeet->$victim SET \
+                     guild_id = $guild_id, channel_id = $channel_id, message_id = $message_id, \
+                     deadline = $deadline, is_yeet_amongus_easter_egg = $easter_egg, \
+                     ballot = $ballot); \
+                 SELECT "
+                    .to_owned()
+                    + ACTIVE_YEET_FIELDS
+                    + " FROM ONLY $yeet.id;",
+            )
+            .bind(("yeeter", user_record_id(yeeter.id)?))
+            .bind(("yeeter_name", yeeter.name.clone()))
+            .bind(("victim", user_record_id(victim.id)?))
+            .bind(("victim_name", victim.name.clone()))
+            .bind(("guild_id", db_id(guild_id)?))
+            .bind(("channel_id", db_id(channel_id)?))
+            .bind(("message_id", db_id(message_id)?))
+            .bind(("deadline", deadline))
+            .bind(("easter_egg", is_yeet_amongus_easter_egg))
+            .bind(("ballot", ballot))
+            .await?
+            .check()?
+            .take(3)?;
+
+        row.map(ActiveYeet::from)
+            .ok_or_else(|| eyre!("yeet RELATE returned no record"))
+    }
+
+    /// The vote still running on the ballot `message_id`, if any.
+    pub async fn find_open(message_id: MessageId) -> Result<Option<ActiveYeet>> {
+        let row: Option<ActiveYeetRow> = DB
+            .query(
+                "SELECT ".to_owned()
+                    + ACTIVE_YEET_FIELDS
+                    + " FROM yeet WHERE message_id = $message_id AND status = 'voting' LIMIT 1",
+            )
+            .bind(("message_id", db_id(message_id)?))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(row.map(ActiveYeet::from))
+    }
+
+    /// Closes a vote for resolution, returning whether it was still open.
+    ///
+    /// Only one caller ever gets `true`, so a ballot can't be resolved twice.
+    pub async fn claim(yeet: &RecordId) -> Result<bool> {
+        let claimed: Vec<RecordId> = DB
+            .query(
+                "UPDATE $yeet SET status = 'resolved', resolved_at = time::now() \
+                 WHERE status = 'voting' RETURN VALUE id",
+            )
+            .bind(("yeet", yeet.clone()))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(!claimed.is_empty())
+    }
+
+    /// Casts or changes `voter`'s vote on a button ballot.
+    pub async fn cast(yeet: &RecordId, voter: UserId, yay: bool) -> Result<()> {
+        DB.query(
+            "UPSERT type::record('yeet_vote', [$yeet, $voter]) \
+             SET yeet = $yeet, voter = $voter, yay = $yay",
+        )
+        .bind(("yeet", yeet.clone()))
+        .bind(("voter", db_id(voter)?))
+        .bind(("yay", yay))
+        .await?
+        .check()?;
+
+        Ok(())
+    }
+
+    pub async fn votes(yeet: &RecordId) -> Result<BallotVotes> {
+        let mut response = DB
+            .query(
+                "SELECT VALUE voter FROM yeet_vote WHERE yeet = $yeet AND yay = true; \
+                 SELECT VALUE voter FROM yeet_vote WHERE yeet = $yeet AND yay = false;",
+            )
+            .bind(("yeet", yeet.clone()))
+            .await?
+            .check()?;
+
+        let yay: Vec<i64> = response.take(0)?;
+        let nay: Vec<i64> = response.take(1)?;
+        let to_user_ids =
+            |ids: Vec<i64>| ids.into_iter().map(|id| UserId::new(id as u64)).collect();
+
+        Ok(BallotVotes {
+            yay: to_user_ids(yay),
+            nay: to_user_ids(nay),
+        })
+    }
+
+    /// Marks a vote that ran out of time, returning whether it was still open.
+    pub async fn expire(yeet: &RecordId) -> Result<bool> {
+        let expired: Vec<RecordId> = DB
+            .query(
+                "UPDATE $yeet SET status = 'expired', resolved_at = time::now() \
+                 WHERE status = 'voting' RETURN VALUE id",
+            )
+            .bind(("yeet", yeet.clone()))
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(!expired.is_empty())
+    }
+
+    pub async fn set_outcome(yeet: &RecordId, outcome: YeetOutcome) -> Result<()> {
+        DB.query("UPDATE $yeet SET outcome = $outcome")
+            .bind(("yeet", yeet.clone()))
+            .bind(("outcome", outcome))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Every vote that is still open.
+    pub async fn pending() -> Result<Vec<ActiveYeet>> {
+        let rows: Vec<ActiveYeetRow> = DB
+            .query(
+                "SELECT ".to_owned()
+                    + ACTIVE_YEET_FIELDS
+                    + " FROM yeet WHERE status = 'voting' AND message_id != NONE",
+            )
+            .await?
+            .check()?
+            .take(0)?;
+
+        Ok(rows.into_iter().map(ActiveYeet::from).collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{BallotVotes, YeetOutcome, YeetVotes};
+    use crate::yeet_settings::BallotKind;
+    use chrono::{TimeDelta, Utc};
+    use poise::serenity_prelude::{ChannelId, GuildId, MessageId, User, UserId};
+
+    pub(crate) fn user(id: u64) -> User {
+        let mut user = User::default();
+        user.id = UserId::new(id);
+        user.name = format!("user{id}");
+        user
+    }
+
+    pub(crate) async fn assert_yeet_votes_survive_and_resolve_once() {
+        let deadline = Utc::now() + TimeDelta::seconds(90);
+        let started = YeetVotes::start(
+            &user(96_001),
+            &user(96_002),
+            GuildId::new(96_100),
+            ChannelId::new(96_200),
+            MessageId::new(96_300),
+            deadline,
+            false,
+            BallotKind::Buttons,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(started.yeeter, UserId::new(96_001));
+        assert_eq!(started.victim, UserId::new(96_002));
+        assert_eq!(started.message_id, MessageId::new(96_300));
+        assert_eq!(started.deadline.timestamp(), deadline.timestamp());
+
+        let pending = YeetVotes::pending().await.unwrap();
+        assert!(pending.iter().any(|yeet| yeet.id == started.id));
+
+        let open = YeetVotes::find_open(MessageId::new(96_300))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(open.id, started.id);
+        assert_eq!(open.ballot, BallotKind::Buttons);
+
+        let (alice, bob) = (UserId::new(96_010), UserId::new(96_011));
+        YeetVotes::cast(&started.id, alice, true).await.unwrap();
+        YeetVotes::cast(&started.id, bob, true).await.unwrap();
+        YeetVotes::cast(&started.id, alice, false).await.unwrap();
+        assert_eq!(
+            YeetVotes::votes(&started.id).await.unwrap(),
+            BallotVotes {
+                yay: vec![bob],
+                nay: vec![alice],
+            },
+            "changing a vote must replace it"
+        );
+
+        assert!(YeetVotes::claim(&started.id).await.unwrap());
+        assert!(
+            !YeetVotes::claim(&started.id).await.unwrap(),
+            "a ballot must only resolve once"
+        );
+        assert!(
+            YeetVotes::find_open(MessageId::new(96_300))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(!YeetVotes::expire(&started.id).await.unwrap());
+        YeetVotes::set_outcome(&started.id, YeetOutcome::Parried)
+            .await
+            .unwrap();
+
+        let expiring = YeetVotes::start(
+            &user(96_001),
+            &user(96_002),
+            GuildId::new(96_100),
+            ChannelId::new(96_200),
+            MessageId::new(96_301),
+            Utc::now(),
+            true,
+            BallotKind::Reactions,
+        )
+        .await
+        .unwrap();
+
+        assert!(YeetVotes::expire(&expiring.id).await.unwrap());
+        assert!(!YeetVotes::expire(&expiring.id).await.unwrap());
+        assert!(
+            YeetVotes::pending()
+                .await
+                .unwrap()
+                .iter()
+                .all(|yeet| yeet.id != started.id && yeet.id != expiring.id)
+        );
+    }
+}
@@ -6,6 +6,8 @@ use bot_lib::{
     data::{RawAppState, State, setup_db},
     debug_force_starboard, debug_surrealdb,
     event_handler::event_handler,
+    migrate_bank_history, resume_scheduled_actions, setup_bets, setup_casino_games,
+    setup_earning_settings, setup_shop, setup_starboards, setup_yeet_settings,
 };
 use clap::Parser;
 use color_eyre::eyre::{Result, WrapErr, eyre};
@@ -67,6 +69,25 @@ async fn main() -> Result<()> {
     ))?;
 
     setup_db().await;
+    migrate_bank_history()
+        .await
+        .wrap_err("Failed to migrate bank history")?;
+    setup_starboards(&config.legacy_starboards)
+        .await
+        .wrap_err("Failed to load starboards")?;
+    setup_yeet_settings()
+        .await
+        .wrap_err("Failed to load yeet settings")?;
+    setup_earning_settings()
+        .await
+        .wrap_err("Failed to load earning settings")?;
+    setup_shop().await.wrap_err("Failed to load shop items")?;
+    setup_bets()
+        .await
+        .wrap_err("Failed to pay out ended bets")?;
+    setup_casino_games()
+        .await
+        .wrap_err("Failed to refund unfinished casino games")?;
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -78,6 +99,7 @@ async fn main() -> Result<()> {
                 aur_search(),
                 bank_admin(),
                 bank(),
+                bet(),
                 catalog(),
                 clip_that(),
                 coinflip(),
@@ -105,8 +127,14 @@ async fn main() -> Result<()> {
                 sathya(),
                 search_catalog(),
                 send_feedback(),
+                shop(),
+                starboard(),
                 timeout(),
+                timeout_history(),
                 yeet_leaderboard(),
+                yeet_buy(),
+                yeet_settings(),
+                yeet_stats(),
                 yeet(),
                 track_flight(),
                 plane_details(),
@@ -141,7 +169,22 @@ async fn main() -> Result<()> {
             ..Default::default()
         })
         .setup(move |ctx, _ready, framework| {
-            tokio::spawn(async { update_interval().await });
+            tokio::spawn({
+                let ctx = ctx.clone();
+                async move {
+                    if let Err(error) = resume_pending_yeets(ctx).await {
+                        tracing::error!("Couldn't resume pending yeets: {error}");
+                    }
+                }
+            });
+            tokio::spawn({
+                let ctx = ctx.clone();
+                async move {
+                    if let Err(error) = resume_scheduled_actions(ctx).await {
+                        tracing::error!("Couldn't resume scheduled actions: {error}");
+                    }
+                }
+            });
             let http = Arc::clone(&ctx.http);
 
             Box::pin(async move {